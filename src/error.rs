@@ -5,30 +5,76 @@ use std::array::TryFromSliceError;
 use std::fmt::Display;
 use std::str::Utf8Error;
 use std::string::FromUtf8Error;
-use std::sync::PoisonError;
+use std::sync::{Arc, PoisonError};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum Error {
     ParserError(String),
     InternalError(String),
+    /// Internal error caused by an underlying failure (IO, serialization, ...),
+    /// the original error is kept and returned by `source()`
+    ExternalError {
+        msg: String,
+        source: Arc<dyn std::error::Error + Send + Sync>,
+    },
     WriteConflict,
 }
 
+impl Error {
+    fn external(err: impl std::error::Error + Send + Sync + 'static) -> Self {
+        Error::ExternalError {
+            msg: err.to_string(),
+            source: Arc::new(err),
+        }
+    }
+
+    /// External error whose message starts with what was being done when it failed
+    pub(crate) fn external_with_context(
+        context: impl Display,
+        err: impl std::error::Error + Send + Sync + 'static,
+    ) -> Self {
+        Error::ExternalError {
+            msg: format!("{context}: {err}"),
+            source: Arc::new(err),
+        }
+    }
+}
+
+// Errors are compared by their message, the source of an external error is ignored
+impl PartialEq for Error {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Error::ParserError(a), Error::ParserError(b)) => a == b,
+            (Error::InternalError(a), Error::InternalError(b)) => a == b,
+            (Error::ExternalError { msg: a, .. }, Error::ExternalError { msg: b, .. }) => a == b,
+            (Error::WriteConflict, Error::WriteConflict) => true,
+            _ => false,
+        }
+    }
+}
+
 impl From<FromUtf8Error> for Error {
     fn from(value: FromUtf8Error) -> Self {
-        Error::InternalError(value.to_string())
+        Error::external(value)
     }
 }
 
 impl From<Utf8Error> for Error {
     fn from(value: Utf8Error) -> Self {
-        Error::InternalError(value.to_string())
+        Error::external(value)
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::ExternalError { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 impl From<std::num::ParseIntError> for Error {
     fn from(err: std::num::ParseIntError) -> Self {
@@ -44,7 +90,7 @@ impl From<std::num::ParseFloatError> for Error {
 
 impl From<std::io::Error> for Error {
     fn from(err: std::io::Error) -> Self {
-        Error::InternalError(err.to_string())
+        Error::external(err)
     }
 }
 
@@ -56,19 +102,19 @@ impl<T> From<PoisonError<T>> for Error {
 
 impl From<TryFromSliceError> for Error {
     fn from(value: TryFromSliceError) -> Self {
-        Error::InternalError(value.to_string())
+        Error::external(value)
     }
 }
 
 impl From<EncodeError> for Error {
     fn from(err: EncodeError) -> Self {
-        Error::InternalError(err.to_string())
+        Error::external(err)
     }
 }
 
 impl From<DecodeError> for Error {
     fn from(err: DecodeError) -> Self {
-        Error::InternalError(err.to_string())
+        Error::external(err)
     }
 }
 
@@ -89,7 +135,31 @@ impl Display for Error {
         match self {
             Error::ParserError(msg) => write!(f, "Parser error: {}", msg),
             Error::InternalError(msg) => write!(f, "Internal error: {}", msg),
+            Error::ExternalError { msg, .. } => write!(f, "Internal error: {}", msg),
             Error::WriteConflict => write!(f, "MVCC Write conflict, try transaction"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Error;
+    use std::error::Error as _;
+    use std::io;
+
+    #[test]
+    fn test_io_error_source() {
+        let err = Error::from(io::Error::new(io::ErrorKind::NotFound, "log file missing"));
+        assert_eq!(err.to_string(), "Internal error: log file missing");
+
+        let source = err.source().expect("io error should be kept as source");
+        let io_err = source.downcast_ref::<io::Error>().unwrap();
+        assert_eq!(io_err.kind(), io::ErrorKind::NotFound);
+
+        // Only the message is compared
+        let other = Error::from(io::Error::other("log file missing"));
+        assert_eq!(err, other);
+        assert_ne!(err, Error::InternalError("log file missing".to_string()));
+        assert!(Error::InternalError("x".to_string()).source().is_none());
+    }
+}
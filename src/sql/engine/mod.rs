@@ -1,8 +1,15 @@
-use super::{executor::ResultSet, parser::Parser, plan::Plan, schema::Table, types::{Row, Value}};
+use super::{
+    executor::ResultSet,
+    parser::Parser,
+    plan::Plan,
+    schema::{Column, Table},
+    types::{Row, Value},
+};
 use crate::error::{Error, Result};
-use crate::sql::parser::ast::Expression;
+use crate::sql::parser::ast::{Predicate, Statement};
+use std::cell::{Cell, RefCell, RefMut};
 
-mod kv;
+pub mod kv;
 
 /// Abstract SQL Engine definition, currently only KV Engine is supported
 pub trait Engine: Clone {
@@ -10,9 +17,16 @@ pub trait Engine: Clone {
 
     fn begin(&self) -> Result<Self::Transaction>;
 
+    /// Begin a read-only transaction seeing the data as of `timestamp`, in unix milliseconds
+    fn begin_as_of(&self, timestamp: u64) -> Result<Self::Transaction>;
+
     fn session(&self) -> Result<Session<Self>> {
         Ok(Session {
             engine: self.clone(),
+            row_limit: None,
+            case_insensitive: false,
+            txn: RefCell::new(None),
+            aborted: Cell::new(false),
         })
     }
 }
@@ -29,9 +43,51 @@ pub trait Transaction {
     // DDL operations
     fn create_table(&mut self, table: Table) -> Result<()>;
 
+    // Create a table that is dropped when the transaction ends
+    fn create_temp_table(&mut self, table: Table) -> Result<()>;
+
+    // Remove a table along with all its rows
+    fn drop_table(&mut self, table_name: &str) -> Result<()>;
+
+    // Names of all the tables, sorted
+    fn list_tables(&mut self) -> Result<Vec<String>>;
+
+    // Primary keys of all the rows of a table, sorted
+    fn list_primary_keys(&mut self, table_name: &str) -> Result<Vec<Value>>;
+
+    // Add a column to a table, the rows it already has get the column's default value
+    fn add_column(&mut self, table_name: &str, column: Column) -> Result<()>;
+
     fn create_row(&mut self, table: String, row: Row) -> Result<()>;
 
-    fn scan_table(&mut self, table_name: String, filter: Option<(String, Expression)>) -> Result<Vec<Row>>;
+    // Insert many rows of a table at once, all or none of them. Returns the number of rows inserted.
+    // With `skip_existing`, a row whose primary key is already taken is skipped instead of
+    // failing the insert
+    fn bulk_insert(
+        &mut self,
+        table_name: &str,
+        rows: Vec<Row>,
+        skip_existing: bool,
+    ) -> Result<usize>;
+
+    // Scan the rows of a table in primary key order, descending when `reverse` is set
+    fn scan_table(
+        &mut self,
+        table_name: String,
+        filter: Option<Predicate>,
+        reverse: bool,
+    ) -> Result<Vec<Row>> {
+        self.scan_table_limit(table_name, filter, reverse, usize::MAX)
+    }
+
+    // Same as `scan_table`, but stops once `limit` rows matched the filter
+    fn scan_table_limit(
+        &mut self,
+        table_name: String,
+        filter: Option<Predicate>,
+        reverse: bool,
+        limit: usize,
+    ) -> Result<Vec<Row>>;
 
     fn update_row(&mut self, table: &Table, id: &Value, row: Row) -> Result<()>;
 
@@ -51,16 +107,119 @@ pub trait Transaction {
 /// Client SQL Session definition
 pub struct Session<E: Engine> {
     engine: E,
+    /// Maximum number of rows a single query may return, unlimited when None
+    row_limit: Option<usize>,
+    /// Whether unquoted identifiers are resolved case-insensitively
+    case_insensitive: bool,
+    /// Transaction opened by BEGIN, the statements run in it until COMMIT or ROLLBACK
+    txn: RefCell<Option<E::Transaction>>,
+    /// Set when a statement failed inside of BEGIN: the transaction was rolled back, and only
+    /// COMMIT or ROLLBACK are accepted until one of them ends it
+    aborted: Cell<bool>,
 }
 
 impl<E: Engine + 'static> Session<E> {
+    /// Cap the number of rows a query may return, None (the default) is unlimited
+    pub fn set_row_limit(&mut self, row_limit: Option<usize>) {
+        self.row_limit = row_limit;
+    }
+
+    /// Fold unquoted table and column names to lowercase, off by default.
+    /// Names are stored as written, so tables created with the option off keep their case.
+    pub fn set_case_insensitive(&mut self, case_insensitive: bool) {
+        self.case_insensitive = case_insensitive;
+    }
+
+    fn parser<'a>(&self, sql: &'a str) -> Parser<'a> {
+        match self.case_insensitive {
+            true => Parser::new_case_insensitive(sql),
+            false => Parser::new(sql),
+        }
+    }
+
+    /// Check that a statement would be accepted without executing it: it is parsed, planned
+    /// and checked against the current schema in a transaction that is rolled back.
+    /// Inside of BEGIN it is checked in the open transaction, seeing the schema changes made
+    /// in it.
+    pub fn validate(&self, sql: &str) -> Result<()> {
+        let mut stmt = self.parser(sql).parse()?;
+        let as_of = stmt.take_as_of();
+        if self.in_transaction() && as_of.is_some() {
+            return Err(as_of_in_transaction());
+        }
+        let plan = Plan::build(stmt)?;
+        if self.in_transaction() {
+            return plan.validate(&mut *self.open_txn()?);
+        }
+        let mut txn = match as_of {
+            Some(timestamp) => self.engine.begin_as_of(timestamp)?,
+            None => self.engine.begin()?,
+        };
+        let result = plan.validate(&mut txn);
+        txn.rollback()?;
+        result
+    }
+
+    /// Describe how a statement would run: it is parsed and planned, and the plan tree is
+    /// returned without executing it or touching the storage
+    pub fn explain_plan(&self, sql: &str) -> Result<String> {
+        let mut stmt = self.parser(sql).parse()?;
+        // The plan is the same at any point in time
+        stmt.take_as_of();
+        Ok(Plan::build(stmt)?.explain()?.to_string())
+    }
+
     /// Execute client SQL statements
     pub fn execute(&self, sql: &str) -> Result<ResultSet> {
-        let stmt = Parser::new(sql).parse()?;
-        let mut txn = self.engine.begin()?;
+        self.execute_statement(self.parser(sql).parse()?)
+    }
+
+    /// Execute a statement built without SQL text, it goes straight to the planner
+    pub fn execute_statement(&self, mut stmt: Statement) -> Result<ResultSet> {
+        match stmt {
+            Statement::Begin => {
+                if self.in_transaction() {
+                    return Err(Error::InternalError(
+                        "a transaction is already open".to_string(),
+                    ));
+                }
+                *self.txn.borrow_mut() = Some(self.engine.begin()?);
+                return Ok(ResultSet::Begin);
+            }
+            // A COMMIT can't keep anything of an aborted transaction, it was rolled back
+            Statement::Commit | Statement::Rollback if self.aborted.replace(false) => {
+                return Ok(ResultSet::Rollback);
+            }
+            Statement::Commit | Statement::Rollback => {
+                let Some(mut txn) = self.txn.take() else {
+                    return Err(Error::InternalError(format!(
+                        "no transaction is open, {stmt} needs a BEGIN first"
+                    )));
+                };
+                return match stmt {
+                    Statement::Commit => txn.commit().map(|_| ResultSet::Commit),
+                    _ => txn.rollback().map(|_| ResultSet::Rollback),
+                };
+            }
+            _ => {}
+        }
+
+        // Inside of BEGIN the statement runs in the open transaction
+        if self.in_transaction() {
+            if stmt.take_as_of().is_some() {
+                return Err(as_of_in_transaction());
+            }
+            let plan = Plan::build(stmt)?;
+            return self.run_in_transaction(|txn| plan.execute_with_row_limit(txn, self.row_limit));
+        }
+
+        let mut txn = match stmt.take_as_of() {
+            Some(timestamp) => self.engine.begin_as_of(timestamp)?,
+            None => self.engine.begin()?,
+        };
 
         // Build plan and execute SQL statement
-        match Plan::build(stmt)?.execute(&mut txn) {
+        match Plan::build(stmt)?.execute_with_row_limit(&mut txn, self.row_limit) {
             Ok(rs) => {
                 txn.commit()?;
                 Ok(rs)
@@ -71,4 +230,124 @@ impl<E: Engine + 'static> Session<E> {
             }
         }
     }
+
+    /// Execute a script of semicolon-terminated statements in a single transaction, returning
+    /// the result of each. It is committed once at the end, and nothing is kept if any
+    /// statement fails. Inside of BEGIN the script runs in the open transaction instead, left
+    /// for COMMIT to commit, and a failing statement aborts it.
+    pub fn execute_script(&self, sql: &str) -> Result<Vec<ResultSet>> {
+        let stmts = self.parser(sql).parse_all()?;
+        let run = |txn: &mut E::Transaction| {
+            stmts
+                .into_iter()
+                .map(|stmt| Plan::build(stmt)?.execute_with_row_limit(txn, self.row_limit))
+                .collect::<Result<Vec<_>>>()
+        };
+        if self.in_transaction() {
+            return self.run_in_transaction(run);
+        }
+
+        let mut txn = self.engine.begin()?;
+        match run(&mut txn) {
+            Ok(results) => {
+                txn.commit()?;
+                Ok(results)
+            }
+            Err(e) => {
+                txn.rollback()?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Pin a snapshot of the database, the queries run on it all see the data as it was when
+    /// it was taken, whatever is committed in the meantime. It can't be taken inside of
+    /// BEGIN, its queries wouldn't see the writes of the open transaction.
+    pub fn snapshot(&self) -> Result<Snapshot<'_, E>> {
+        if self.in_transaction() {
+            return Err(Error::InternalError(
+                "a snapshot can't be taken while a transaction is open".to_string(),
+            ));
+        }
+        Ok(Snapshot {
+            session: self,
+            txn: self.engine.begin()?,
+        })
+    }
+
+    // Whether BEGIN opened a transaction that isn't ended yet, aborted or not
+    fn in_transaction(&self) -> bool {
+        self.aborted.get() || self.txn.borrow().is_some()
+    }
+
+    // The transaction opened by BEGIN, refused once a failed statement aborted it
+    fn open_txn(&self) -> Result<RefMut<'_, E::Transaction>> {
+        RefMut::filter_map(self.txn.borrow_mut(), Option::as_mut).map_err(|_| {
+            Error::InternalError(
+                "the transaction was aborted by a failed statement, only ROLLBACK can end it"
+                    .to_string(),
+            )
+        })
+    }
+
+    // Run `f` in the transaction opened by BEGIN. A failure may have written part of its
+    // rows, which can't be undone on their own: the whole transaction is rolled back, and
+    // stays aborted until COMMIT or ROLLBACK ends it.
+    fn run_in_transaction<T>(&self, f: impl FnOnce(&mut E::Transaction) -> Result<T>) -> Result<T> {
+        let result = f(&mut *self.open_txn()?);
+        if result.is_err() {
+            self.aborted.set(true);
+            if let Some(mut txn) = self.txn.take() {
+                txn.rollback()?;
+            }
+        }
+        result
+    }
+}
+
+fn as_of_in_transaction() -> Error {
+    Error::InternalError("AS OF TIMESTAMP can't be used in an open transaction".to_string())
+}
+
+/// Read-only view of the database, for running several queries that have to agree with each
+/// other, like the parts of a report. It is a transaction that never writes and stays open
+/// until the snapshot is dropped, the garbage collection keeps the versions it can see.
+pub struct Snapshot<'a, E: Engine> {
+    session: &'a Session<E>,
+    txn: E::Transaction,
+}
+
+impl<E: Engine + 'static> Snapshot<'_, E> {
+    /// Run a query on the snapshot, statements that would write are refused
+    pub fn query(&mut self, sql: &str) -> Result<ResultSet> {
+        let stmt = self.session.parser(sql).parse()?;
+        if !matches!(
+            stmt,
+            Statement::Select { .. }
+                | Statement::Union { .. }
+                | Statement::ShowTables
+                | Statement::Describe { .. }
+        ) {
+            return Err(Error::InternalError(format!(
+                "a snapshot is read-only, {stmt} can't run on it"
+            )));
+        }
+        Plan::build(stmt)?.execute_with_row_limit(&mut self.txn, self.session.row_limit)
+    }
+}
+
+impl<E: Engine> Drop for Session<E> {
+    fn drop(&mut self) {
+        // A transaction left open is rolled back, as if the client disconnected
+        if let Some(mut txn) = self.txn.get_mut().take() {
+            let _ = txn.rollback();
+        }
+    }
+}
+
+impl<E: Engine> Drop for Snapshot<'_, E> {
+    fn drop(&mut self) {
+        // Nothing was written, rolling back only ends the transaction
+        let _ = self.txn.rollback();
+    }
 }
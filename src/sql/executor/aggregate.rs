@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+
+use super::{query::row_limit_exceeded, Executor, ResultSet};
+use crate::{
+    error::{Error, Result},
+    sql::{
+        engine::Transaction,
+        parser::ast::Expression,
+        types::{OrderedValue, Row, Value},
+    },
+};
+
+pub struct Aggregate<T> {
+    exprs: Vec<(Expression, Option<String>)>,
+    group_by: Vec<String>,
+    source: Box<dyn Executor<T>>,
+    row_cap: Option<usize>,
+}
+
+impl<T: Transaction> Aggregate<T> {
+    /// An aggregate with a `row_cap` fails as soon as one group more than the cap shows up
+    pub fn new(
+        exprs: Vec<(Expression, Option<String>)>,
+        group_by: Vec<String>,
+        source: Box<dyn Executor<T>>,
+        row_cap: Option<usize>,
+    ) -> Box<Self> {
+        Box::new(Self {
+            exprs,
+            group_by,
+            source,
+            row_cap,
+        })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Aggregate<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        match self.source.execute(txn)? {
+            ResultSet::Scan {
+                columns: source_columns,
+                rows,
+            } => {
+                let index = |name: &String| {
+                    source_columns
+                        .iter()
+                        .position(|c| c == name)
+                        .ok_or(Error::InternalError(format!("column {name} not found")))
+                };
+                let group_by = self
+                    .group_by
+                    .iter()
+                    .map(index)
+                    .collect::<Result<Vec<_>>>()?;
+
+                // Rows bucketed by the values of their GROUP BY columns, groups come out in the
+                // order of these values. Without GROUP BY all the rows make one group, even
+                // when there are none.
+                let mut groups = BTreeMap::new();
+                if group_by.is_empty() {
+                    groups.insert(vec![], rows);
+                } else {
+                    for row in rows {
+                        let key = group_by
+                            .iter()
+                            .map(|&i| OrderedValue(row[i].clone()))
+                            .collect::<Vec<_>>();
+                        groups.entry(key).or_insert_with(Vec::new).push(row);
+                        if groups.len() > self.row_cap.unwrap_or(usize::MAX) {
+                            return Err(row_limit_exceeded());
+                        }
+                    }
+                }
+
+                let mut columns = Vec::new();
+                for (expr, alias) in self.exprs.iter() {
+                    columns.push(alias.clone().unwrap_or_else(|| column_name(expr)));
+                }
+
+                let mut output = Vec::with_capacity(groups.len());
+                for (key, rows) in groups {
+                    let mut row = Vec::with_capacity(self.exprs.len());
+                    for (expr, _) in self.exprs.iter() {
+                        let value = match expr {
+                            Expression::Function(name, arg) => {
+                                aggregate(name, arg, &source_columns, &rows)?
+                            }
+                            Expression::Field(name) if self.group_by.contains(name) => {
+                                let i = self.group_by.iter().position(|c| c == name).unwrap();
+                                key[i].0.clone()
+                            }
+                            _ => {
+                                return Err(Error::InternalError(format!(
+                                    "unsupported aggregate expression {expr}"
+                                )))
+                            }
+                        };
+                        row.push(value);
+                    }
+                    output.push(row);
+                }
+
+                Ok(ResultSet::Scan {
+                    columns,
+                    rows: output,
+                })
+            }
+
+            _ => Err(Error::InternalError("Unexpected result set".into())),
+        }
+    }
+}
+
+// Fold the values of a column over the rows with the named function. Like in standard SQL,
+// NULLs are skipped and every function but COUNT returns NULL when no value is left.
+fn aggregate(name: &str, arg: &str, columns: &[String], rows: &[Row]) -> Result<Value> {
+    if arg == "*" {
+        return match name {
+            "COUNT" => Ok(Value::Integer(rows.len() as i64)),
+            _ => Err(Error::InternalError(format!("{name}(*) is not supported"))),
+        };
+    }
+
+    let index = columns
+        .iter()
+        .position(|c| c == arg)
+        .ok_or(Error::InternalError(format!("column {arg} not found")))?;
+    let values = rows
+        .iter()
+        .map(|row| &row[index])
+        .filter(|v| **v != Value::Null);
+
+    Ok(match name {
+        "COUNT" => Value::Integer(values.count() as i64),
+        "SUM" => sum(name, values)?.0,
+        "AVG" => match sum(name, values)? {
+            (_, 0) => Value::Null,
+            (Value::Integer(sum), count) => Value::Float(sum as f64 / count as f64),
+            (Value::Float(sum), count) => Value::Float(sum / count as f64),
+            _ => unreachable!(),
+        },
+        "MIN" | "MAX" => {
+            let mut result = Value::Null;
+            for value in values {
+                let Some(ordering) = value.partial_cmp(&result) else {
+                    return Err(Error::InternalError(format!(
+                        "{name} can't compare {value} with {result}"
+                    )));
+                };
+                // Any value is greater than NULL, so the first one is always taken
+                let better = match name {
+                    "MIN" => ordering.is_lt(),
+                    _ => ordering.is_gt(),
+                };
+                if better || result == Value::Null {
+                    result = value.clone();
+                }
+            }
+            result
+        }
+        _ => {
+            return Err(Error::InternalError(format!(
+                "unsupported aggregate function {name}"
+            )))
+        }
+    })
+}
+
+// Sum numeric values along with their count. The sum is an integer while all the values
+// are, and NULL when there are none.
+fn sum<'a>(name: &str, values: impl Iterator<Item = &'a Value>) -> Result<(Value, usize)> {
+    let mut total = Value::Null;
+    let mut count = 0;
+    for value in values {
+        total = match (&total, value) {
+            (Value::Null, Value::Integer(_) | Value::Float(_)) => value.clone(),
+            (Value::Integer(a), Value::Integer(b)) => Value::Integer(
+                a.checked_add(*b)
+                    .ok_or(Error::InternalError(format!("{name} overflows an integer")))?,
+            ),
+            (Value::Integer(a), Value::Float(b)) => Value::Float(*a as f64 + b),
+            (Value::Float(a), Value::Integer(b)) => Value::Float(a + *b as f64),
+            (Value::Float(a), Value::Float(b)) => Value::Float(a + b),
+            _ => {
+                return Err(Error::InternalError(format!(
+                    "{name} expects numbers, got {value}"
+                )))
+            }
+        };
+        count += 1;
+    }
+    Ok((total, count))
+}
+
+/// Default output column name of an expression without alias, e.g. `count(*)` or `sum(age)`
+pub fn column_name(expr: &Expression) -> String {
+    match expr {
+        Expression::Function(name, arg) => format!("{}({arg})", name.to_lowercase()),
+        expr => expr.to_string(),
+    }
+}
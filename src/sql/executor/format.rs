@@ -0,0 +1,333 @@
+use std::fmt::{Display, Formatter};
+
+use super::ResultSet;
+use crate::sql::types::Value;
+
+/// Options controlling how a `ResultSet` is rendered as a table or as CSV.
+/// `Value`'s own `Display` is left untouched, these only affect the formatters below.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatOptions {
+    /// Text written for `Value::Null`, e.g. `NULL`, an empty string or `\N`
+    pub null: String,
+    /// Field separator used by the CSV export
+    pub delimiter: char,
+    /// Always wrap string fields in double quotes in the CSV export,
+    /// otherwise only fields that need it (delimiter, quote or newline inside) are quoted
+    pub quote_strings: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            null: "NULL".to_string(),
+            delimiter: ',',
+            quote_strings: false,
+        }
+    }
+}
+
+impl FormatOptions {
+    // Render a single value for the table view, strings are shown without quotes
+    fn cell(&self, value: &Value) -> String {
+        match value {
+            Value::Null => self.null.clone(),
+            Value::String(s) => s.clone(),
+            v => v.to_string(),
+        }
+    }
+
+    // Render a single value for the CSV export
+    fn csv_field(&self, value: &Value) -> String {
+        match value {
+            Value::Null => self.null.clone(),
+            Value::String(s) => self.csv_quote(s, self.quote_strings),
+            v => v.to_string(),
+        }
+    }
+
+    // Quote a field following RFC 4180, a `"` inside the field is doubled. An empty field
+    // or one that reads like the NULL text is quoted too, to tell it apart from a NULL
+    fn csv_quote(&self, field: &str, force: bool) -> String {
+        let needs_quote = field.contains(self.delimiter)
+            || field.contains('"')
+            || field.contains('\n')
+            || field.contains('\r')
+            || field.is_empty()
+            || field == self.null;
+        if force || needs_quote {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    }
+}
+
+impl ResultSet {
+    /// Render the result set as an aligned text table
+    ///
+    /// ```text
+    /// id | name
+    /// ---+------
+    /// 1  | Alice
+    /// 2  | NULL
+    /// ```
+    pub fn to_table(&self, options: &FormatOptions) -> String {
+        match self {
+            ResultSet::Scan { columns, rows } => {
+                let cells = rows
+                    .iter()
+                    .map(|row| row.iter().map(|v| options.cell(v)).collect::<Vec<_>>())
+                    .collect::<Vec<_>>();
+
+                // The width of each column is the widest of its header and its cells, in
+                // chars as `{:<width$}` pads by chars
+                let mut widths = columns
+                    .iter()
+                    .map(|c| c.chars().count())
+                    .collect::<Vec<_>>();
+                for row in cells.iter() {
+                    for (i, cell) in row.iter().enumerate() {
+                        widths[i] = widths[i].max(cell.chars().count());
+                    }
+                }
+
+                let format_line = |fields: &[String]| {
+                    fields
+                        .iter()
+                        .zip(widths.iter())
+                        .map(|(field, width)| format!("{field:<width$}"))
+                        .collect::<Vec<_>>()
+                        .join(" | ")
+                        .trim_end()
+                        .to_string()
+                };
+
+                let mut lines = vec![format_line(columns)];
+                lines.push(
+                    widths
+                        .iter()
+                        .map(|w| "-".repeat(*w))
+                        .collect::<Vec<_>>()
+                        .join("-+-"),
+                );
+                lines.extend(cells.iter().map(|row| format_line(row)));
+
+                lines.join("\n")
+            }
+            // One line per column: name, type, nullability, default and primary key flag.
+            // A column without a default has an empty one, unlike a NULL default
+            ResultSet::Describe { table } => {
+                let yes_no = |b: bool| Value::String(if b { "YES" } else { "NO" }.to_string());
+                ResultSet::Scan {
+                    columns: ["column", "type", "nullable", "default", "primary key"]
+                        .map(String::from)
+                        .to_vec(),
+                    rows: table
+                        .columns
+                        .iter()
+                        .map(|c| {
+                            vec![
+                                Value::String(c.name.clone()),
+                                Value::String(match c.width {
+                                    Some(16) => "SMALLINT".to_string(),
+                                    _ => c.datatype.to_string(),
+                                }),
+                                yes_no(c.nullable),
+                                Value::String(
+                                    c.default
+                                        .as_ref()
+                                        .map(|v| v.to_sql_literal())
+                                        .unwrap_or_default(),
+                                ),
+                                yes_no(c.primary_key),
+                            ]
+                        })
+                        .collect(),
+                }
+                .to_table(options)
+            }
+            rs => rs.to_string(),
+        }
+    }
+
+    /// Export the rows of a scan result as CSV, the first line is the header.
+    /// Non-scan results have no rows and export as an empty string.
+    pub fn to_csv(&self, options: &FormatOptions) -> String {
+        match self {
+            ResultSet::Scan { columns, rows } => {
+                let delimiter = options.delimiter.to_string();
+                let mut lines = vec![columns
+                    .iter()
+                    .map(|c| options.csv_quote(c, false))
+                    .collect::<Vec<_>>()
+                    .join(&delimiter)];
+                lines.extend(rows.iter().map(|row| {
+                    row.iter()
+                        .map(|v| options.csv_field(v))
+                        .collect::<Vec<_>>()
+                        .join(&delimiter)
+                }));
+
+                lines.join("\n")
+            }
+            _ => String::new(),
+        }
+    }
+}
+
+impl Display for ResultSet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResultSet::CreateTable { table_name } => write!(f, "CREATE TABLE {table_name}"),
+            ResultSet::DropTable { table_name } => write!(f, "DROP TABLE {table_name}"),
+            ResultSet::AlterTable { table_name } => write!(f, "ALTER TABLE {table_name}"),
+            ResultSet::ShowTables { names } => write!(f, "{}", names.join("\n")),
+            ResultSet::Insert { count } => write!(f, "INSERT {count} rows"),
+            ResultSet::Scan { .. } | ResultSet::Describe { .. } => {
+                write!(f, "{}", self.to_table(&FormatOptions::default()))
+            }
+            ResultSet::Update { count } => write!(f, "UPDATE {count} rows"),
+            ResultSet::Delete { count, .. } => write!(f, "DELETE {count} rows"),
+            ResultSet::Begin => write!(f, "BEGIN"),
+            ResultSet::Commit => write!(f, "COMMIT"),
+            ResultSet::Rollback => write!(f, "ROLLBACK"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FormatOptions;
+    use crate::sql::{executor::ResultSet, types::Value};
+
+    fn scan_with_nulls() -> ResultSet {
+        ResultSet::Scan {
+            columns: vec!["id".to_string(), "name".to_string(), "score".to_string()],
+            rows: vec![
+                vec![
+                    Value::Integer(1),
+                    Value::String("Alice".to_string()),
+                    Value::Float(9.5),
+                ],
+                vec![Value::Integer(2), Value::Null, Value::Null],
+                vec![
+                    Value::Integer(3),
+                    Value::String("Smith, Bob".to_string()),
+                    Value::Boolean(true),
+                ],
+            ],
+        }
+    }
+
+    #[test]
+    fn test_table_null_representations() {
+        let rs = scan_with_nulls();
+
+        assert_eq!(
+            rs.to_table(&FormatOptions::default()),
+            [
+                "id | name       | score",
+                "---+------------+------",
+                "1  | Alice      | 9.5",
+                "2  | NULL       | NULL",
+                "3  | Smith, Bob | TRUE",
+            ]
+            .join("\n")
+        );
+
+        let options = FormatOptions {
+            null: String::new(),
+            ..FormatOptions::default()
+        };
+        assert_eq!(
+            rs.to_table(&options),
+            [
+                "id | name       | score",
+                "---+------------+------",
+                "1  | Alice      | 9.5",
+                "2  |            |",
+                "3  | Smith, Bob | TRUE",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_table_non_ascii() {
+        let rs = ResultSet::Scan {
+            columns: vec!["id".to_string(), "nom".to_string()],
+            rows: vec![
+                vec![Value::Integer(1), Value::String("ééé".to_string())],
+                vec![Value::Integer(2), Value::String("a".to_string())],
+            ],
+        };
+        assert_eq!(
+            rs.to_table(&FormatOptions::default()),
+            ["id | nom", "---+----", "1  | ééé", "2  | a"].join(
+                "
+"
+            )
+        );
+    }
+
+    #[test]
+    fn test_csv_null_representations() {
+        let rs = scan_with_nulls();
+
+        // Empty cells for NULL, only the field containing the delimiter is quoted
+        let options = FormatOptions {
+            null: String::new(),
+            ..FormatOptions::default()
+        };
+        assert_eq!(
+            rs.to_csv(&options),
+            "id,name,score\n1,Alice,9.5\n2,,\n3,\"Smith, Bob\",TRUE"
+        );
+
+        // `\N` for NULL, tab separated and every string quoted
+        let options = FormatOptions {
+            null: "\\N".to_string(),
+            delimiter: '\t',
+            quote_strings: true,
+        };
+        assert_eq!(
+            rs.to_csv(&options),
+            "id\tname\tscore\n1\t\"Alice\"\t9.5\n2\t\\N\t\\N\n3\t\"Smith, Bob\"\tTRUE"
+        );
+    }
+
+    #[test]
+    fn test_csv_strings_like_null() {
+        let rs = ResultSet::Scan {
+            columns: vec!["s".to_string()],
+            rows: vec![
+                vec![Value::String(String::new())],
+                vec![Value::String("NULL".to_string())],
+                vec![Value::Null],
+            ],
+        };
+        assert_eq!(
+            rs.to_csv(&FormatOptions::default()),
+            "s\n\"\"\n\"NULL\"\nNULL"
+        );
+
+        let options = FormatOptions {
+            null: String::new(),
+            ..FormatOptions::default()
+        };
+        // The string NULL can't be taken for a NULL any more, it is left unquoted
+        assert_eq!(rs.to_csv(&options), "s\n\"\"\nNULL\n");
+    }
+
+    #[test]
+    fn test_csv_escape_quotes() {
+        let rs = ResultSet::Scan {
+            columns: vec!["quote".to_string()],
+            rows: vec![vec![Value::String("say \"hi\"".to_string())]],
+        };
+        assert_eq!(
+            rs.to_csv(&FormatOptions::default()),
+            "quote\n\"say \"\"hi\"\"\""
+        );
+    }
+}
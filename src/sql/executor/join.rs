@@ -0,0 +1,126 @@
+use super::{result_table, Executor, ResultSet};
+use crate::{
+    error::{Error, Result},
+    sql::{
+        engine::Transaction,
+        parser::ast::{JoinKind, Operator},
+        types::Value,
+    },
+};
+
+/// Joins two sources by comparing every row of the left one with every row of the right one
+pub struct NestedLoopJoin<T> {
+    left: Box<dyn Executor<T>>,
+    right: Box<dyn Executor<T>>,
+    kind: JoinKind,
+    /// Tables read by each side, used to qualify the columns of an inner join
+    tables: (Option<String>, Option<String>),
+}
+
+impl<T: Transaction> NestedLoopJoin<T> {
+    pub fn new(
+        left: Box<dyn Executor<T>>,
+        right: Box<dyn Executor<T>>,
+        kind: JoinKind,
+        tables: (Option<String>, Option<String>),
+    ) -> Box<Self> {
+        Box::new(Self {
+            left,
+            right,
+            kind,
+            tables,
+        })
+    }
+}
+
+/// Name the columns of one side of an inner join after its table, `table.column`. Columns
+/// of a source that isn't a table, or already qualified by an earlier join, are kept as is.
+pub(crate) fn qualify(columns: Vec<String>, table: Option<&str>) -> Vec<String> {
+    match table {
+        Some(table) => columns
+            .into_iter()
+            .map(|c| match c.contains('.') {
+                true => c,
+                false => format!("{table}.{c}"),
+            })
+            .collect(),
+        None => columns,
+    }
+}
+
+impl<T: Transaction> Executor<T> for NestedLoopJoin<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let (
+            ResultSet::Scan {
+                columns: left_cols,
+                rows: left_rows,
+            },
+            ResultSet::Scan {
+                columns: right_cols,
+                rows: right_rows,
+            },
+        ) = (self.left.execute(txn)?, self.right.execute(txn)?)
+        else {
+            return Err(Error::InternalError("Unexpected result set".into()));
+        };
+
+        match self.kind {
+            JoinKind::Natural => {
+                // Pairs of (left index, right index) of the columns both sides have. The right
+                // copy of a common column is left out of the output. Without any common column
+                // this is a cross join.
+                let common = left_cols
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(l, name)| {
+                        right_cols.iter().position(|c| c == name).map(|r| (l, r))
+                    })
+                    .collect::<Vec<_>>();
+                let right_only = (0..right_cols.len())
+                    .filter(|r| !common.iter().any(|(_, c)| c == r))
+                    .collect::<Vec<_>>();
+
+                let mut columns = left_cols;
+                columns.extend(right_only.iter().map(|&r| right_cols[r].clone()));
+
+                let mut rows = vec![];
+                for left in left_rows.iter() {
+                    for right in right_rows.iter() {
+                        // NULL never matches, not even another NULL
+                        let matched = common.iter().all(|&(l, r)| {
+                            left[l] != Value::Null && Operator::Equal.compare(&left[l], &right[r])
+                        });
+                        if matched {
+                            let mut row = left.clone();
+                            row.extend(right_only.iter().map(|&r| right[r].clone()));
+                            rows.push(row);
+                        }
+                    }
+                }
+
+                Ok(ResultSet::Scan { columns, rows })
+            }
+            JoinKind::Inner(on) => {
+                let mut columns = qualify(left_cols, self.tables.0.as_deref());
+                columns.extend(qualify(right_cols, self.tables.1.as_deref()));
+
+                // The ON predicate is evaluated against the combined row, through a table
+                // made of the output columns
+                let table = result_table(&columns);
+
+                let mut rows = vec![];
+                for left in left_rows.iter() {
+                    for right in right_rows.iter() {
+                        let mut row = left.clone();
+                        row.extend(right.iter().cloned());
+                        if on.evaluate(&table, &row)? {
+                            rows.push(row);
+                        }
+                    }
+                }
+
+                Ok(ResultSet::Scan { columns, rows })
+            }
+        }
+    }
+}
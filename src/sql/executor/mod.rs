@@ -1,9 +1,24 @@
-use super::{engine::Transaction, plan::Node, types::Row};
+use super::{
+    engine::Transaction,
+    plan::Node,
+    schema::{Column, Table},
+    types::{DataType, Row, Value},
+};
 use crate::error::Result;
+use aggregate::Aggregate;
+use join::NestedLoopJoin;
 use mutation::{Delete, Insert, Update};
-use query::{Limit, Offset, Order, Scan};
-use schema::CreateTable;
+use query::{EmptyScan, Limit, Offset, Order, Projection, RowCap, Scan, Union, ValuesScan};
+use schema::{AddColumn, CreateTable, CreateTableAs, Describe, DropTable, ShowTables};
 
+pub use format::FormatOptions;
+pub(crate) use aggregate::column_name;
+pub(crate) use join::qualify;
+pub(crate) use mutation::insert_row;
+
+mod aggregate;
+mod format;
+mod join;
 mod mutation;
 mod query;
 mod schema;
@@ -15,24 +30,125 @@ pub trait Executor<T: Transaction> {
 impl<T: Transaction + 'static> dyn Executor<T> {
     pub fn build(node: Node) -> Box<dyn Executor<T>> {
         match node {
-            Node::CreateTable { schema } => CreateTable::new(schema),
+            Node::CreateTable {
+                schema,
+                if_not_exists,
+                temporary,
+            } => CreateTable::new(schema, if_not_exists, temporary),
+            Node::CreateTableAs {
+                table_name,
+                if_not_exists,
+                temporary,
+                source,
+            } => CreateTableAs::new(table_name, if_not_exists, temporary, Self::build(*source)),
+            Node::DropTable {
+                table_name,
+                if_exists,
+            } => DropTable::new(table_name, if_exists),
+            Node::ShowTables => ShowTables::new(),
+            Node::Describe { table_name } => Describe::new(table_name),
+            Node::AddColumn { table_name, column } => AddColumn::new(table_name, column),
             Node::Insert {
                 table_name,
                 columns,
                 values,
-            } => Insert::new(table_name, columns, values),
-            Node::Scan { table_name, filter } => Scan::new(table_name, filter),
+                if_not_exists,
+            } => Insert::new(table_name, columns, values, if_not_exists),
+            Node::Scan { table_name, filter } => Scan::new(table_name, filter, None),
+            Node::EmptyScan { table_name } => EmptyScan::new(table_name),
+            Node::ValuesScan { columns, rows } => ValuesScan::new(columns, rows),
+            Node::Union { left, right, all } => {
+                Union::new(Self::build(*left), Self::build(*right), all)
+            }
+            Node::Join { left, right, kind } => {
+                let tables = (left.table_name(), right.table_name());
+                NestedLoopJoin::new(Self::build(*left), Self::build(*right), kind, tables)
+            }
             Node::Update {
                 table_name,
                 columns,
                 source,
             } => Update::new(table_name, columns, Self::build(*source)),
             Node::Delete { table_name, source } => Delete::new(table_name, Self::build(*source)),
+            Node::Aggregate {
+                source,
+                exprs,
+                group_by,
+            } => Aggregate::new(exprs, group_by, Self::build(*source), None),
+            Node::Projection { source, exprs } => Projection::new(exprs, Self::build(*source)),
             Node::Order { order_by, source } => Order::new(order_by, Self::build(*source)),
             Node::Limit { source, limit } => Limit::new(limit, Self::build(*source)),
             Node::Offset { source, offset } => Offset::new(offset, Self::build(*source)),
         }
     }
+
+    /// Build the executor of a query whose result may hold at most `row_limit` rows
+    pub fn build_with_row_limit(node: Node, row_limit: Option<usize>) -> Box<dyn Executor<T>> {
+        match row_limit {
+            Some(cap) => RowCap::new(cap, Self::build_capped(node, cap)),
+            None => Self::build(node),
+        }
+    }
+
+    // Build a node whose rows each end up in the result, so one row more than `cap` fails
+    // the query. The cap goes down to the scans and aggregates that gather the rows, through
+    // the nodes that keep every row of their source.
+    fn build_capped(node: Node, cap: usize) -> Box<dyn Executor<T>> {
+        match node {
+            Node::Scan { table_name, filter } => Scan::new(table_name, filter, Some(cap)),
+            // The rows of the source are folded into groups, only the groups are counted
+            Node::Aggregate {
+                source,
+                exprs,
+                group_by,
+            } => Aggregate::new(exprs, group_by, Self::build(*source), Some(cap)),
+            Node::Projection { source, exprs } => {
+                Projection::new(exprs, Self::build_capped(*source, cap))
+            }
+            Node::Order { order_by, source } => {
+                Order::new(order_by, Self::build_capped(*source, cap))
+            }
+            // A larger limit only cuts the result once it is over the cap
+            Node::Limit { source, limit } if limit > cap => {
+                Limit::new(limit, Self::build_capped(*source, cap))
+            }
+            Node::Offset { source, offset } => Offset::new(
+                offset,
+                Self::build_capped(*source, cap.saturating_add(offset)),
+            ),
+            // Duplicates removed by UNION may bring either side back under the cap
+            Node::Union {
+                left,
+                right,
+                all: true,
+            } => Union::new(
+                Self::build_capped(*left, cap),
+                Self::build_capped(*right, cap),
+                true,
+            ),
+            node => Self::build(node),
+        }
+    }
+}
+
+// Table made of the columns of a result, to evaluate expressions against its rows. The types
+// of the columns aren't known, nothing relies on them when evaluating
+fn result_table(columns: &[String]) -> Table {
+    Table {
+        name: String::new(),
+        columns: columns
+            .iter()
+            .map(|name| Column {
+                name: name.clone(),
+                datatype: DataType::String,
+                nullable: true,
+                default: None,
+                primary_key: false,
+                width: None,
+                index: false,
+            })
+            .collect(),
+    }
 }
 
 #[derive(Debug)]
@@ -41,6 +157,24 @@ pub enum ResultSet {
         table_name: String,
     },
 
+    DropTable {
+        table_name: String,
+    },
+
+    AlterTable {
+        table_name: String,
+    },
+
+    /// Names of all the tables, in order
+    ShowTables {
+        names: Vec<String>,
+    },
+
+    /// Schema of a table
+    Describe {
+        table: Table,
+    },
+
     Insert {
         count: usize,
     },
@@ -56,5 +190,11 @@ pub enum ResultSet {
 
     Delete {
         count: usize,
+        /// Primary keys of the deleted rows, in the order they were deleted
+        deleted_keys: Vec<Value>,
     },
+
+    Begin,
+    Commit,
+    Rollback,
 }
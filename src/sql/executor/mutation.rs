@@ -1,7 +1,7 @@
 use super::{Executor, ResultSet};
 use crate::error::Error;
 use crate::sql::schema::Table;
-use crate::sql::types::{Row, Value};
+use crate::sql::types::Row;
 use crate::{
     error::Result,
     sql::{engine::Transaction, parser::ast::Expression},
@@ -12,6 +12,7 @@ pub struct Insert {
     table_name: String,
     columns: Vec<String>,
     values: Vec<Vec<Expression>>,
+    if_not_exists: bool,
 }
 
 impl Insert {
@@ -19,11 +20,13 @@ impl Insert {
         table_name: String,
         columns: Vec<String>,
         values: Vec<Vec<Expression>>,
+        if_not_exists: bool,
     ) -> Box<Self> {
         Box::new(Self {
             table_name,
             columns,
             values,
+            if_not_exists,
         })
     }
 }
@@ -41,7 +44,7 @@ fn pad_row(table: &Table, row: &Row) -> Result<Row> {
             results.push(default_value.clone());
         } else {
             return Err(Error::InternalError(format!(
-                "No default value for column {}",
+                "No default value for column {}, it is NOT NULL and must be given a value",
                 column.name
             )));
         }
@@ -54,7 +57,7 @@ fn pad_row(table: &Table, row: &Row) -> Result<Row> {
 // insert into tbl(d, c) values(1, 2);
 //    a          b       c          d
 // default   default     2          1
-fn make_row(table: &Table, columns: &Vec<String>, values: &Row) -> Result<Row> {
+fn make_row(table: &Table, columns: &[String], values: &Row) -> Result<Row> {
     // Determine if the number of columns is consistent with the number of values
     if columns.len() != values.len() {
         return Err(Error::InternalError(format!(
@@ -76,7 +79,7 @@ fn make_row(table: &Table, columns: &Vec<String>, values: &Row) -> Result<Row> {
                 Ok(default_value.clone())
             } else {
                 Err(Error::InternalError(format!(
-                    "No default value for column {}",
+                    "No default value for column {}, it is NOT NULL and must be given a value",
                     column.name
                 )))
             }
@@ -84,30 +87,49 @@ fn make_row(table: &Table, columns: &Vec<String>, values: &Row) -> Result<Row> {
         .collect()
 }
 
+/// Build the full row of the table from the values of an INSERT
+pub fn insert_row(table: &Table, columns: &[String], values: &[Expression]) -> Result<Row> {
+    // Convert the expression into a value, there is no row to take columns from
+    let row_values = values
+        .iter()
+        .map(|expr| {
+            if !expr.is_constant() {
+                return Err(Error::InternalError(format!(
+                    "INSERT values must be constant expressions, got {expr}"
+                )));
+            }
+            expr.evaluate(None)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    // If the inserted column is not specified
+    let row = if columns.is_empty() {
+        pad_row(table, &row_values)?
+    } else {
+        // If the inserted column is specified, the value information needs to be organized
+        make_row(table, columns, &row_values)?
+    };
+    // Values are converted to the type of their column where the column allows it
+    row.into_iter()
+        .enumerate()
+        .map(|(i, value)| match table.columns.get(i) {
+            Some(column) => column.coerce(value),
+            None => Ok(value),
+        })
+        .collect()
+}
+
 impl<T: Transaction> Executor<T> for Insert {
     fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
-        let mut count = 0;
         // First, retrieve the table information
         let table = txn.must_get_table(&self.table_name)?;
-        for express in self.values {
-            // Convert the expression into a value
-            let row_values = express
-                .into_iter()
-                .map(|e| Value::from(&e))
-                .collect::<Vec<_>>();
-            // If the inserted column is not specified
-            let insert_row = if self.columns.is_empty() {
-                pad_row(&table, &row_values)?
-            } else {
-                // If the inserted column is specified, the value information needs to be organized
-                make_row(&table, &self.columns, &row_values)?
-            };
-
-            // Insert data
-            println!("insert row: {:?}", insert_row);
-            txn.create_row(self.table_name.clone(), insert_row)?;
-            count += 1;
-        }
+        let rows = self
+            .values
+            .iter()
+            .map(|express| insert_row(&table, &self.columns, express))
+            .collect::<Result<Vec<_>>>()?;
+
+        // Insert data, the count is of the rows actually inserted
+        let count = txn.bulk_insert(&self.table_name, rows, self.if_not_exists)?;
 
         Ok(ResultSet::Insert { count })
     }
@@ -146,20 +168,44 @@ impl<T: Transaction> Executor<T> for Update<T> {
                     return Ok(ResultSet::Update { count: 0 });
                 }
 
+                // Rows whose primary key changes are moved once all the new rows are known:
+                // with `SET id = id + 1` the new key of a row is the old key of the next one
+                let mut moved = vec![];
                 for row in rows.iter() {
                     // Get primary key for each row
                     let pk = table.get_primary_key(row)?;
                     let mut new_row = row.clone();
 
                     for (i, column) in columns.iter().enumerate() {
-                        if let Some(expr) = self.columns.get(column) {
-                            new_row[i] = Value::from(expr);
-                        }
+                        new_row[i] = match self.columns.get(column) {
+                            Some(Expression::Default) => {
+                                table.columns[i].default.clone().ok_or_else(|| {
+                                    Error::InternalError(format!(
+                                        "column {column} has no default value"
+                                    ))
+                                })?
+                            }
+                            Some(expr) => expr.evaluate(Some((&table, row)))?,
+                            None => continue,
+                        };
                     }
 
-                    txn.update_row(&table, pk, new_row)?;
+                    if table.get_primary_key(&new_row)? == pk {
+                        txn.update_row(&table, pk, new_row)?;
+                    } else {
+                        moved.push((pk.clone(), new_row));
+                    }
                     count += 1;
                 }
+
+                // Take them all out first, then put them back under their new keys, which
+                // fails if one is already taken
+                for (pk, _) in moved.iter() {
+                    txn.delete_row(&table, pk.clone())?;
+                }
+                for (_, row) in moved {
+                    txn.create_row(table.name.clone(), row)?;
+                }
             }
             _ => return Err(Error::InternalError("Unexpected result set".into())),
         }
@@ -180,29 +226,26 @@ impl<T: Transaction> Delete<T> {
 }
 impl<T: Transaction> Executor<T> for Delete<T> {
     fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
-        let mut count = 0;
+        let mut deleted_keys = vec![];
 
         match self.source.execute(txn)? {
             ResultSet::Scan { columns: _, rows } => {
                 let table = txn.must_get_table(&self.table_name)?;
-                
-                // Check if rows is empty
-                if rows.is_empty() {
-                    // No rows to delete, return empty result
-                    return Ok(ResultSet::Delete { count: 0 });
-                }
 
                 for row in rows.iter() {
                     // Get primary key for each row
                     let pk = table.get_primary_key(row)?;
                     txn.delete_row(&table, pk.clone())?;
-                    count += 1;
+                    deleted_keys.push(pk.clone());
                 }
             }
 
             _ => return Err(Error::InternalError("Unexpected result set".into())),
         }
 
-        Ok(ResultSet::Delete { count })
+        Ok(ResultSet::Delete {
+            count: deleted_keys.len(),
+            deleted_keys,
+        })
     }
 }
@@ -1,29 +1,50 @@
-use std::{cmp::Ordering, collections::HashMap};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+};
 
-use super::{Executor, ResultSet};
+use super::{column_name, result_table, Executor, ResultSet};
 use crate::{
     error::{Error, Result},
     sql::{
         engine::Transaction,
-        parser::ast::{Expression, OrderDirection},
+        parser::ast::{Expression, OrderDirection, Predicate},
+        types::OrderedValue,
     },
 };
 
 pub struct Scan {
     table_name: String,
-    filter: Option<(String, Expression)>,
+    filter: Option<Predicate>,
+    row_cap: Option<usize>,
 }
 
 impl Scan {
-    pub fn new(table_name: String, filter: Option<(String, Expression)>) -> Box<Self> {
-        Box::new(Self { table_name, filter })
+    /// A scan with a `row_cap` fails as soon as one row more than the cap matched
+    pub fn new(table_name: String, filter: Option<Predicate>, row_cap: Option<usize>) -> Box<Self> {
+        Box::new(Self {
+            table_name,
+            filter,
+            row_cap,
+        })
     }
 }
 
 impl<T: Transaction> Executor<T> for Scan {
     fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
         let table = txn.must_get_table(&self.table_name)?;
-        let rows = txn.scan_table(self.table_name.clone(), self.filter)?;
+        let rows = match self.row_cap {
+            Some(cap) => {
+                let limit = cap.saturating_add(1);
+                let rows =
+                    txn.scan_table_limit(self.table_name.clone(), self.filter, false, limit)?;
+                if rows.len() > cap {
+                    return Err(row_limit_exceeded());
+                }
+                rows
+            }
+            None => txn.scan_table(self.table_name.clone(), self.filter, false)?,
+        };
 
         Ok(ResultSet::Scan {
             columns: table.columns.iter().map(|c| c.name.clone()).collect(),
@@ -32,6 +53,164 @@ impl<T: Transaction> Executor<T> for Scan {
     }
 }
 
+pub struct EmptyScan {
+    table_name: String,
+}
+
+impl EmptyScan {
+    pub fn new(table_name: String) -> Box<Self> {
+        Box::new(Self { table_name })
+    }
+}
+
+impl<T: Transaction> Executor<T> for EmptyScan {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let table = txn.must_get_table(&self.table_name)?;
+
+        Ok(ResultSet::Scan {
+            columns: table.columns.into_iter().map(|c| c.name).collect(),
+            rows: vec![],
+        })
+    }
+}
+
+pub struct ValuesScan {
+    columns: Vec<String>,
+    rows: Vec<Vec<Expression>>,
+}
+
+impl ValuesScan {
+    pub fn new(columns: Vec<String>, rows: Vec<Vec<Expression>>) -> Box<Self> {
+        Box::new(Self { columns, rows })
+    }
+}
+
+impl<T: Transaction> Executor<T> for ValuesScan {
+    fn execute(self: Box<Self>, _txn: &mut T) -> Result<ResultSet> {
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(|expr| expr.evaluate(None)).collect())
+            .collect::<Result<_>>()?;
+
+        Ok(ResultSet::Scan {
+            columns: self.columns,
+            rows,
+        })
+    }
+}
+
+pub struct Union<T> {
+    left: Box<dyn Executor<T>>,
+    right: Box<dyn Executor<T>>,
+    all: bool,
+}
+
+impl<T: Transaction> Union<T> {
+    pub fn new(left: Box<dyn Executor<T>>, right: Box<dyn Executor<T>>, all: bool) -> Box<Self> {
+        Box::new(Self { left, right, all })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Union<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let (
+            ResultSet::Scan { columns, mut rows },
+            ResultSet::Scan {
+                columns: right_columns,
+                rows: right_rows,
+            },
+        ) = (self.left.execute(txn)?, self.right.execute(txn)?)
+        else {
+            return Err(Error::InternalError("Unexpected result set".into()));
+        };
+
+        if columns.len() != right_columns.len() {
+            return Err(Error::InternalError(format!(
+                "UNION of {} columns with {} columns",
+                columns.len(),
+                right_columns.len()
+            )));
+        }
+        // A column holds values of one type, NULL aside
+        for (i, name) in columns.iter().enumerate() {
+            let left_type = rows.iter().find_map(|row| row[i].datatype());
+            let right_type = right_rows.iter().find_map(|row| row[i].datatype());
+            if let (Some(l), Some(r)) = (left_type, right_type) {
+                if l != r {
+                    return Err(Error::InternalError(format!(
+                        "UNION column {name} mixes {l} and {r} values"
+                    )));
+                }
+            }
+        }
+
+        rows.extend(right_rows);
+        // Keep the first of equal rows, as compared by `OrderedValue`
+        if !self.all {
+            let mut seen = HashSet::new();
+            rows.retain(|row| {
+                seen.insert(row.iter().cloned().map(OrderedValue).collect::<Vec<_>>())
+            });
+        }
+
+        Ok(ResultSet::Scan { columns, rows })
+    }
+}
+
+pub struct Projection<T> {
+    exprs: Vec<(Expression, Option<String>)>,
+    source: Box<dyn Executor<T>>,
+}
+
+impl<T: Transaction> Projection<T> {
+    pub fn new(
+        exprs: Vec<(Expression, Option<String>)>,
+        source: Box<dyn Executor<T>>,
+    ) -> Box<Self> {
+        Box::new(Self { exprs, source })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Projection<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        match self.source.execute(txn)? {
+            ResultSet::Scan { columns, rows } => {
+                // Columns are checked up front, so that they are found even without rows
+                for (expr, _) in self.exprs.iter() {
+                    for name in expr.columns() {
+                        if !columns.iter().any(|c| c == name) {
+                            return Err(Error::InternalError(format!("column {name} not found")));
+                        }
+                    }
+                }
+
+                let table = result_table(&columns);
+                let rows = rows
+                    .into_iter()
+                    .map(|row| {
+                        self.exprs
+                            .iter()
+                            .map(|(expr, _)| expr.evaluate(Some((&table, &row))))
+                            .collect()
+                    })
+                    .collect::<Result<_>>()?;
+                let names = self
+                    .exprs
+                    .into_iter()
+                    .map(|(expr, alias)| alias.unwrap_or_else(|| column_name(&expr)))
+                    .collect();
+                Ok(ResultSet::Scan {
+                    columns: names,
+                    rows,
+                })
+            }
+
+            _ => Err(Error::InternalError("Unexpected result set".into())),
+        }
+    }
+}
+
 pub struct Order<T> {
     order_by: Vec<(String, OrderDirection)>,
     source: Box<dyn Executor<T>>,
@@ -83,7 +262,7 @@ impl<T: Transaction> Executor<T> for Order<T> {
                 Ok(ResultSet::Scan { columns, rows })
             }
 
-            _ => return Err(Error::InternalError("Unexpected result set".into())),
+            _ => Err(Error::InternalError("Unexpected result set".into())),
         }
     }
 }
@@ -106,7 +285,33 @@ impl<T: Transaction> Executor<T> for Offset<T> {
                 Ok(ResultSet::Scan { columns, rows })
             }
 
-            _ => return Err(Error::InternalError("Unexpected result set".into())),
+            _ => Err(Error::InternalError("Unexpected result set".into())),
+        }
+    }
+}
+
+pub(super) fn row_limit_exceeded() -> Error {
+    Error::InternalError("result set exceeds configured row limit".into())
+}
+
+/// Guards the final result of a query, failing when it holds more rows than the cap. The
+/// executors the cap is passed to fail earlier, before the rows are all gathered.
+pub struct RowCap<T> {
+    cap: usize,
+    source: Box<dyn Executor<T>>,
+}
+
+impl<T: Transaction> RowCap<T> {
+    pub fn new(cap: usize, source: Box<dyn Executor<T>>) -> Box<Self> {
+        Box::new(Self { cap, source })
+    }
+}
+
+impl<T: Transaction> Executor<T> for RowCap<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        match self.source.execute(txn)? {
+            ResultSet::Scan { rows, .. } if rows.len() > self.cap => Err(row_limit_exceeded()),
+            rs => Ok(rs),
         }
     }
 }
@@ -129,7 +334,7 @@ impl<T: Transaction> Executor<T> for Limit<T> {
                 Ok(ResultSet::Scan { columns, rows })
             }
 
-            _ => return Err(Error::InternalError("Unexpected result set".into())),
+            _ => Err(Error::InternalError("Unexpected result set".into())),
         }
     }
 }
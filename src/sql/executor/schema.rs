@@ -1,25 +1,262 @@
 use super::{Executor, ResultSet};
 use crate::{
-    error::Result,
-    sql::{engine::Transaction, schema::Table},
+    error::{Error, Result},
+    sql::{
+        engine::Transaction,
+        schema::{Column, Table},
+        types::{DataType, Row, Value},
+    },
 };
 
 // Crate table
 pub struct CreateTable {
     schema: Table,
+    if_not_exists: bool,
+    temporary: bool,
 }
 
 impl CreateTable {
-    pub fn new(schema: Table) -> Box<Self> {
-        Box::new(Self { schema })
+    pub fn new(schema: Table, if_not_exists: bool, temporary: bool) -> Box<Self> {
+        Box::new(Self {
+            schema,
+            if_not_exists,
+            temporary,
+        })
     }
 }
 
 impl<T: Transaction> Executor<T> for CreateTable {
     fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
         let table_name = self.schema.name.clone();
-        txn.create_table(self.schema)?;
+        // IF NOT EXISTS turns creating an existing table into a no-op
+        if !(self.if_not_exists && txn.get_table(&table_name)?.is_some()) {
+            if self.temporary {
+                txn.create_temp_table(self.schema)?;
+            } else {
+                txn.create_table(self.schema)?;
+            }
+        }
 
         Ok(ResultSet::CreateTable { table_name })
     }
 }
+
+/// Name of the primary key column added to the tables created by CREATE TABLE AS
+const ROWID: &str = "rowid";
+
+// Create a table from the result of a query
+pub struct CreateTableAs<T> {
+    table_name: String,
+    if_not_exists: bool,
+    temporary: bool,
+    source: Box<dyn Executor<T>>,
+}
+
+impl<T: Transaction> CreateTableAs<T> {
+    pub fn new(
+        table_name: String,
+        if_not_exists: bool,
+        temporary: bool,
+        source: Box<dyn Executor<T>>,
+    ) -> Box<Self> {
+        Box::new(Self {
+            table_name,
+            if_not_exists,
+            temporary,
+            source,
+        })
+    }
+}
+
+impl<T: Transaction> Executor<T> for CreateTableAs<T> {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        let table_name = self.table_name;
+        if self.if_not_exists && txn.get_table(&table_name)?.is_some() {
+            return Ok(ResultSet::CreateTable { table_name });
+        }
+
+        let ResultSet::Scan { columns, rows } = self.source.execute(txn)? else {
+            return Err(Error::InternalError("Unexpected result set".into()));
+        };
+        let schema = infer_schema(&table_name, columns, &rows)?;
+        if self.temporary {
+            txn.create_temp_table(schema)?;
+        } else {
+            txn.create_table(schema)?;
+        }
+
+        for (i, row) in rows.into_iter().enumerate() {
+            let mut new_row = vec![rowid(i)?];
+            new_row.extend(row);
+            txn.create_row(table_name.clone(), new_row)?;
+        }
+
+        Ok(ResultSet::CreateTable { table_name })
+    }
+}
+
+// The rowid of the row at the given index. Running out of positive integers is an error, a
+// wrapped negative rowid would sort before the rows already stored.
+fn rowid(index: usize) -> Result<Value> {
+    i64::try_from(index)
+        .ok()
+        .and_then(|i| i.checked_add(1))
+        .map(Value::Integer)
+        .ok_or(Error::InternalError(
+            "auto-increment counter exhausted".into(),
+        ))
+}
+
+// Build the schema of a table holding the rows of a query. A query result has no primary key,
+// so an integer `rowid` numbering the rows from 1 comes first. The type of every other column
+// is the one of its non-NULL values, all of the same type, and STRING when there are none.
+// They are all nullable.
+fn infer_schema(table_name: &str, columns: Vec<String>, rows: &[Row]) -> Result<Table> {
+    let mut schema = vec![Column {
+        name: ROWID.to_string(),
+        datatype: DataType::Integer,
+        nullable: false,
+        default: None,
+        primary_key: true,
+        width: None,
+        index: false,
+    }];
+    for (i, name) in columns.into_iter().enumerate() {
+        if schema.iter().any(|c| c.name == name) {
+            return Err(Error::InternalError(format!(
+                "duplicate column {name} in table {table_name}, give it another name with AS"
+            )));
+        }
+
+        let mut datatype = None;
+        for dt in rows.iter().filter_map(|row| row[i].datatype()) {
+            match &datatype {
+                None => datatype = Some(dt),
+                Some(first) if *first != dt => {
+                    return Err(Error::InternalError(format!(
+                        "column {name} mixes {first} and {dt} values"
+                    )))
+                }
+                _ => {}
+            }
+        }
+
+        schema.push(Column {
+            name,
+            datatype: datatype.unwrap_or(DataType::String),
+            nullable: true,
+            default: Some(Value::Null),
+            primary_key: false,
+            width: None,
+            index: false,
+        });
+    }
+
+    Ok(Table {
+        name: table_name.to_string(),
+        columns: schema,
+    })
+}
+
+// Drop table
+pub struct DropTable {
+    table_name: String,
+    if_exists: bool,
+}
+
+impl DropTable {
+    pub fn new(table_name: String, if_exists: bool) -> Box<Self> {
+        Box::new(Self {
+            table_name,
+            if_exists,
+        })
+    }
+}
+
+impl<T: Transaction> Executor<T> for DropTable {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        // IF EXISTS turns dropping a missing table into a no-op
+        if !(self.if_exists && txn.get_table(&self.table_name)?.is_none()) {
+            txn.drop_table(&self.table_name)?;
+        }
+
+        Ok(ResultSet::DropTable {
+            table_name: self.table_name,
+        })
+    }
+}
+
+// List the tables
+pub struct ShowTables;
+
+impl ShowTables {
+    pub fn new() -> Box<Self> {
+        Box::new(Self)
+    }
+}
+
+impl<T: Transaction> Executor<T> for ShowTables {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        Ok(ResultSet::ShowTables {
+            names: txn.list_tables()?,
+        })
+    }
+}
+
+// Describe the columns of a table, nothing is written
+pub struct Describe {
+    table_name: String,
+}
+
+impl Describe {
+    pub fn new(table_name: String) -> Box<Self> {
+        Box::new(Self { table_name })
+    }
+}
+
+impl<T: Transaction> Executor<T> for Describe {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        Ok(ResultSet::Describe {
+            table: txn.must_get_table(&self.table_name)?,
+        })
+    }
+}
+
+// Add a column to a table
+pub struct AddColumn {
+    table_name: String,
+    column: Column,
+}
+
+impl AddColumn {
+    pub fn new(table_name: String, column: Column) -> Box<Self> {
+        Box::new(Self { table_name, column })
+    }
+}
+
+impl<T: Transaction> Executor<T> for AddColumn {
+    fn execute(self: Box<Self>, txn: &mut T) -> Result<ResultSet> {
+        txn.add_column(&self.table_name, self.column)?;
+
+        Ok(ResultSet::AlterTable {
+            table_name: self.table_name,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::rowid;
+    use crate::sql::types::Value;
+
+    #[test]
+    fn test_rowid_overflow() {
+        assert_eq!(rowid(0).unwrap(), Value::Integer(1));
+        assert_eq!(
+            rowid(i64::MAX as usize - 1).unwrap(),
+            Value::Integer(i64::MAX)
+        );
+        assert!(rowid(i64::MAX as usize).is_err());
+        assert!(rowid(usize::MAX).is_err());
+    }
+}
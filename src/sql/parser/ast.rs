@@ -1,21 +1,70 @@
 use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
 
-use crate::sql::types::DataType;
+use crate::error::{Error, Result};
+use crate::sql::schema::Table;
+use crate::sql::types::{DataType, Row, Value};
 
 #[derive(Debug, PartialEq)]
 pub enum Statement {
     CreateTable {
         name: String,
         columns: Vec<Column>,
+        if_not_exists: bool,
+        /// Dropped when the transaction that created it ends
+        temporary: bool,
+    },
+    /// CREATE TABLE ... AS SELECT, the table is created from the columns and rows of the query
+    CreateTableAs {
+        name: String,
+        if_not_exists: bool,
+        temporary: bool,
+        query: Box<Statement>,
+    },
+    DropTable {
+        name: String,
+        if_exists: bool,
+    },
+    /// SHOW TABLES, the names of all the tables
+    ShowTables,
+    /// DESCRIBE table, the definition of its columns
+    Describe {
+        table_name: String,
+    },
+    /// ALTER TABLE ... ADD COLUMN, the rows already in the table get the column's default
+    AddColumn {
+        table_name: String,
+        column: Column,
     },
     Insert {
         table_name: String,
         columns: Option<Vec<String>>,
         values: Vec<Vec<Expression>>,
+        /// Rows whose primary key already exists are skipped instead of failing the insert
+        if_not_exists: bool,
     },
     Select {
-        table_name: String,
-        where_clause: Option<(String, Expression)>,
+        /// Output expressions with their optional `AS` alias, empty for `SELECT *`
+        select: Vec<(Expression, Option<String>)>,
+        from: FromItem,
+        /// Tables joined to the FROM source, in order
+        joins: Vec<Join>,
+        /// Read the data as it was at this time, in unix milliseconds
+        as_of: Option<u64>,
+        where_clause: Option<Predicate>,
+        /// Columns whose values split the rows into groups, each folded into one output row
+        group_by: Vec<String>,
+        order_by: Vec<(String, OrderDirection)>,
+        limit: Option<Expression>,
+        offset: Option<Expression>,
+    },
+
+    /// Rows of both queries, duplicates removed unless `all`. An ORDER BY, LIMIT or OFFSET
+    /// after the last query sorts and cuts the rows of the whole union
+    Union {
+        left: Box<Statement>,
+        right: Box<Statement>,
+        all: bool,
         order_by: Vec<(String, OrderDirection)>,
         limit: Option<Expression>,
         offset: Option<Expression>,
@@ -24,15 +73,311 @@ pub enum Statement {
     Update {
         table_name: String,
         columns: BTreeMap<String, Expression>,
-        where_clause: Option<(String, Expression)>,
+        where_clause: Option<Predicate>,
+        limit: Option<Expression>,
     },
 
     Delete {
         table_name: String,
-        where_clause: Option<(String, Expression)>,
+        where_clause: Option<Predicate>,
+        limit: Option<Expression>,
+    },
+
+    /// BEGIN, the statements of the session run in one transaction until COMMIT or ROLLBACK
+    Begin,
+    Commit,
+    Rollback,
+}
+
+impl Statement {
+    /// Take the AS OF TIMESTAMP of a SELECT, the transaction running it has to begin at that
+    /// time, so it's handled before planning
+    pub fn take_as_of(&mut self) -> Option<u64> {
+        match self {
+            Statement::Select { as_of, .. } => as_of.take(),
+            _ => None,
+        }
+    }
+}
+
+/// Kind of a statement, told from its leading keyword
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StatementKind {
+    /// SELECT, and UNION of selects
+    Select,
+    Insert,
+    Update,
+    Delete,
+    /// CREATE, DROP and ALTER
+    Ddl,
+    /// SHOW TABLES and DESCRIBE, reading the schema
+    Show,
+    /// BEGIN, COMMIT and ROLLBACK
+    Transaction,
+}
+
+impl StatementKind {
+    /// Whether the statement only reads, so it can run on a snapshot
+    pub fn is_read_only(&self) -> bool {
+        matches!(self, StatementKind::Select | StatementKind::Show)
+    }
+}
+
+/// Condition of a WHERE clause, comparisons of columns against values combined with AND / OR
+#[derive(Debug, PartialEq, Clone)]
+pub enum Predicate {
+    /// expr <operator> expr, the left side is usually a column
+    Compare(Expression, Operator, Expression),
+    /// column BETWEEN low AND high, both bounds are inclusive
+    Between(String, Expression, Expression),
+    /// column LIKE pattern, where `%` matches any sequence of characters and `_` a single one
+    Like(String, Expression),
+    /// A boolean column on its own
+    Column(String),
+    And(Box<Predicate>, Box<Predicate>),
+    Or(Box<Predicate>, Box<Predicate>),
+    Not(Box<Predicate>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Operator {
+    Equal,
+    GreaterThan,
+    GreaterThanOrEqual,
+    LessThan,
+    LessThanOrEqual,
+}
+
+impl Predicate {
+    /// The columns referenced by the predicate, on either side of the comparisons
+    pub fn columns(&self) -> Vec<&str> {
+        match self {
+            Predicate::Compare(left, _, right) => {
+                let mut columns = left.columns();
+                columns.extend(right.columns());
+                columns
+            }
+            Predicate::Between(col, low, high) => {
+                let mut columns = vec![col.as_str()];
+                columns.extend(low.columns());
+                columns.extend(high.columns());
+                columns
+            }
+            Predicate::Like(col, pattern) => {
+                let mut columns = vec![col.as_str()];
+                columns.extend(pattern.columns());
+                columns
+            }
+            Predicate::Column(col) => vec![col],
+            Predicate::And(l, r) | Predicate::Or(l, r) => {
+                let mut columns = l.columns();
+                columns.extend(r.columns());
+                columns
+            }
+            Predicate::Not(p) => p.columns(),
+        }
+    }
+
+    /// Evaluate the predicate against a row of the given table. A row matches only when the
+    /// predicate is true, not when it is false or unknown (NULL)
+    pub fn evaluate(&self, table: &Table, row: &Row) -> Result<bool> {
+        Ok(self.evaluate_logic(table, row)?.unwrap_or(false))
+    }
+
+    // Three-valued evaluation where None is unknown. A comparison with a NULL operand is
+    // unknown, even NULL = NULL, so is a NULL boolean column, and NOT unknown stays unknown.
+    fn evaluate_logic(&self, table: &Table, row: &Row) -> Result<Option<bool>> {
+        let compare = |operator: &Operator, value: &Value, other: &Value| match (value, other) {
+            (Value::Null, _) | (_, Value::Null) => None,
+            _ => Some(operator.compare(value, other)),
+        };
+
+        Ok(match self {
+            Predicate::Compare(left, operator, right) => {
+                let value = left.evaluate(Some((table, row)))?;
+                let other = right.evaluate(Some((table, row)))?;
+                compare(operator, &value, &other)
+            }
+            Predicate::Between(col, low, high) => {
+                let value = &row[table.get_col_index(col)?];
+                let low = low.evaluate(Some((table, row)))?;
+                let high = high.evaluate(Some((table, row)))?;
+                // Both bounds have to hold: a false one decides, otherwise a NULL is unknown
+                match (
+                    compare(&Operator::GreaterThanOrEqual, value, &low),
+                    compare(&Operator::LessThanOrEqual, value, &high),
+                ) {
+                    (Some(false), _) | (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                }
+            }
+            Predicate::Like(col, pattern) => {
+                match (
+                    &row[table.get_col_index(col)?],
+                    pattern.evaluate(Some((table, row)))?,
+                ) {
+                    (Value::String(value), Value::String(pattern)) => {
+                        Some(like_matches(value, &pattern))
+                    }
+                    (Value::Null, _) | (_, Value::Null) => None,
+                    (Value::String(_), pattern) => {
+                        return Err(Error::InternalError(format!(
+                            "LIKE pattern must be a string, got {pattern}"
+                        )))
+                    }
+                    (v, _) => {
+                        return Err(Error::InternalError(format!(
+                            "column {col} is not a string, LIKE can't be applied to {v}"
+                        )))
+                    }
+                }
+            }
+            Predicate::Column(col) => match &row[table.get_col_index(col)?] {
+                Value::Boolean(b) => Some(*b),
+                Value::Null => None,
+                v => {
+                    return Err(Error::InternalError(format!(
+                        "column {col} is not a boolean, got {v}"
+                    )))
+                }
+            },
+            // AND and OR are unknown unless the known side alone decides the result
+            Predicate::And(l, r) => {
+                match (l.evaluate_logic(table, row)?, r.evaluate_logic(table, row)?) {
+                    (Some(false), _) | (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                }
+            }
+            Predicate::Or(l, r) => {
+                match (l.evaluate_logic(table, row)?, r.evaluate_logic(table, row)?) {
+                    (Some(true), _) | (_, Some(true)) => Some(true),
+                    (Some(false), Some(false)) => Some(false),
+                    _ => None,
+                }
+            }
+            Predicate::Not(p) => p.evaluate_logic(table, row)?.map(|b| !b),
+        })
+    }
+}
+
+// Whether the string matches the LIKE pattern as a whole. A `%` first matches nothing, and
+// takes one more character each time the rest of the pattern fails to match.
+fn like_matches(value: &str, pattern: &str) -> bool {
+    let value = value.chars().collect::<Vec<_>>();
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let (mut v, mut p) = (0, 0);
+    // Positions in the pattern after the last `%` and in the value where it resumes
+    let mut backtrack = None;
+
+    while v < value.len() {
+        match pattern.get(p) {
+            Some('%') => {
+                p += 1;
+                backtrack = Some((p, v));
+            }
+            Some(&c) if c == '_' || c == value[v] => {
+                p += 1;
+                v += 1;
+            }
+            _ => match backtrack {
+                Some((after, start)) => {
+                    p = after;
+                    v = start + 1;
+                    backtrack = Some((after, start + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '%')
+}
+
+impl Operator {
+    /// Compare two values, ordering comparisons against NULL or values of
+    /// incompatible types never match. Integers and floats are compared by
+    /// value, so `2` equals `2.0` as it does in ORDER BY
+    pub fn compare(&self, left: &Value, right: &Value) -> bool {
+        if *self != Operator::Equal && (*left == Value::Null || *right == Value::Null) {
+            return false;
+        }
+
+        match left.partial_cmp(right) {
+            Some(ordering) => match self {
+                Operator::Equal => ordering.is_eq(),
+                Operator::GreaterThan => ordering.is_gt(),
+                Operator::GreaterThanOrEqual => ordering.is_ge(),
+                Operator::LessThan => ordering.is_lt(),
+                Operator::LessThanOrEqual => ordering.is_le(),
+            },
+            None => false,
+        }
+    }
+}
+
+/// Source of the rows of a SELECT
+#[derive(Debug, PartialEq)]
+pub enum FromItem {
+    Table(String),
+    /// Inline rows of constants, `(VALUES (1, 'a'), ...) AS alias [(column, ...)]`.
+    /// Without a column list the columns are named column1, column2, ...
+    Values {
+        rows: Vec<Vec<Expression>>,
+        alias: String,
+        columns: Option<Vec<String>>,
     },
 }
 
+impl FromItem {
+    /// Name the source is referred to by, the table or the alias of the rows
+    pub fn name(&self) -> &str {
+        match self {
+            FromItem::Table(name) => name,
+            FromItem::Values { alias, .. } => alias,
+        }
+    }
+}
+
+impl Display for FromItem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FromItem::Table(name) => write!(f, "{name}"),
+            FromItem::Values {
+                rows,
+                alias,
+                columns,
+            } => {
+                let rows = rows
+                    .iter()
+                    .map(|row| format!("({})", join(row)))
+                    .collect::<Vec<_>>();
+                write!(f, "(VALUES {}) AS {alias}", rows.join(", "))?;
+                if let Some(columns) = columns {
+                    write!(f, " ({})", columns.join(", "))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A table joined to the rows of the FROM clause
+#[derive(Debug, PartialEq)]
+pub struct Join {
+    pub table_name: String,
+    pub kind: JoinKind,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum JoinKind {
+    /// Matches the rows on all the columns with the same name on both sides, which appear
+    /// once in the output. Without any common column every pair of rows matches.
+    Natural,
+    /// Matches the pairs of rows the ON predicate holds for. Columns are named after the
+    /// table they come from, `table.column`, so both sides can have the same names.
+    Inner(Predicate),
+}
 
 #[derive(Debug, PartialEq)]
 pub enum OrderDirection {
@@ -44,14 +389,27 @@ pub enum OrderDirection {
 pub struct Column {
     pub name: String,
     pub data_type: DataType,
+    /// Bit width of an integer type narrower than 64 bits, e.g. 16 for SMALLINT
+    pub width: Option<u32>,
     pub nullable: Option<bool>,
     pub default: Option<Expression>,
     pub primary_key: bool,
+    /// Whether the column has a secondary index
+    pub index: bool,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expression {
     Consts(Consts),
+    /// Reference to a column of the row being processed
+    Field(String),
+    Operation(Operation),
+    /// Aggregate function call: upper-cased function name and its argument, a column name or `*`
+    Function(String, String),
+    /// All the columns of a table in the select list, `table.*`
+    Wildcard(String),
+    /// `DEFAULT` as the new value of a column in UPDATE SET, stands for the column's default
+    Default,
 }
 
 impl From<Consts> for Expression {
@@ -60,6 +418,153 @@ impl From<Consts> for Expression {
     }
 }
 
+impl From<Operation> for Expression {
+    fn from(value: Operation) -> Self {
+        Self::Operation(value)
+    }
+}
+
+/// Arithmetic operations
+#[derive(Debug, PartialEq, Clone)]
+pub enum Operation {
+    Add(Box<Expression>, Box<Expression>),
+    Subtract(Box<Expression>, Box<Expression>),
+    Multiply(Box<Expression>, Box<Expression>),
+    Divide(Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    /// Whether the expression can be evaluated without a row, it references no column
+    pub fn is_constant(&self) -> bool {
+        match self {
+            Expression::Consts(_) => true,
+            Expression::Operation(op) => {
+                let (left, right) = op.operands();
+                left.is_constant() && right.is_constant()
+            }
+            Expression::Field(_)
+            | Expression::Function(..)
+            | Expression::Wildcard(_)
+            | Expression::Default => false,
+        }
+    }
+
+    /// Whether an aggregate function is called anywhere in the expression
+    pub fn has_function(&self) -> bool {
+        match self {
+            Expression::Function(..) => true,
+            Expression::Operation(op) => {
+                let (left, right) = op.operands();
+                left.has_function() || right.has_function()
+            }
+            _ => false,
+        }
+    }
+
+    /// The columns referenced by the expression
+    pub fn columns(&self) -> Vec<&str> {
+        match self {
+            Expression::Field(name) => vec![name],
+            Expression::Operation(op) => {
+                let (left, right) = op.operands();
+                let mut columns = left.columns();
+                columns.extend(right.columns());
+                columns
+            }
+            Expression::Consts(_)
+            | Expression::Function(..)
+            | Expression::Wildcard(_)
+            | Expression::Default => vec![],
+        }
+    }
+
+    /// Evaluate the expression, column references are resolved against the row of the
+    /// table when one is given, and fail otherwise
+    pub fn evaluate(&self, row: Option<(&Table, &Row)>) -> Result<Value> {
+        match self {
+            Expression::Consts(consts) => Ok(Value::from(consts)),
+            Expression::Field(name) => match row {
+                Some((table, row)) => Ok(row[table.get_col_index(name)?].clone()),
+                None => Err(Error::InternalError(format!(
+                    "column {name} can't be referenced here, a constant is expected"
+                ))),
+            },
+            Expression::Operation(op) => {
+                let (left, right) = op.operands();
+                op.apply(left.evaluate(row)?, right.evaluate(row)?)
+            }
+            Expression::Function(..) | Expression::Wildcard(_) => Err(Error::InternalError(
+                format!("{self} can only be used in the select list"),
+            )),
+            Expression::Default => Err(Error::InternalError(
+                "DEFAULT can only be used as a value in UPDATE SET".to_string(),
+            )),
+        }
+    }
+}
+
+impl Operation {
+    pub fn operands(&self) -> (&Expression, &Expression) {
+        match self {
+            Operation::Add(l, r)
+            | Operation::Subtract(l, r)
+            | Operation::Multiply(l, r)
+            | Operation::Divide(l, r) => (l, r),
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            Operation::Add(..) => "+",
+            Operation::Subtract(..) => "-",
+            Operation::Multiply(..) => "*",
+            Operation::Divide(..) => "/",
+        }
+    }
+
+    // Apply the operation to the values of its operands. Integers stay integers, with an
+    // error on overflow or division by zero, and anything mixed with a float is a float.
+    // NULL in gives NULL out.
+    fn apply(&self, left: Value, right: Value) -> Result<Value> {
+        Ok(match (left, right) {
+            (Value::Null, Value::Integer(_) | Value::Float(_) | Value::Null)
+            | (Value::Integer(_) | Value::Float(_), Value::Null) => Value::Null,
+            (Value::Integer(l), Value::Integer(r)) => {
+                let result = match self {
+                    Operation::Add(..) => l.checked_add(r),
+                    Operation::Subtract(..) => l.checked_sub(r),
+                    Operation::Multiply(..) => l.checked_mul(r),
+                    Operation::Divide(..) if r == 0 => {
+                        return Err(Error::InternalError("division by zero".to_string()))
+                    }
+                    Operation::Divide(..) => l.checked_div(r),
+                };
+                Value::Integer(result.ok_or_else(|| {
+                    Error::InternalError(format!("integer overflow in {l} {} {r}", self.symbol()))
+                })?)
+            }
+            (Value::Integer(l), Value::Float(r)) => Value::Float(self.apply_float(l as f64, r)),
+            (Value::Float(l), Value::Integer(r)) => Value::Float(self.apply_float(l, r as f64)),
+            (Value::Float(l), Value::Float(r)) => Value::Float(self.apply_float(l, r)),
+            (l, r) => {
+                return Err(Error::InternalError(format!(
+                    "can't apply {} to {l} and {r}",
+                    self.symbol()
+                )))
+            }
+        })
+    }
+
+    fn apply_float(&self, l: f64, r: f64) -> f64 {
+        match self {
+            Operation::Add(..) => l + r,
+            Operation::Subtract(..) => l - r,
+            Operation::Multiply(..) => l * r,
+            Operation::Divide(..) => l / r,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Consts {
     Null,
@@ -68,3 +573,297 @@ pub enum Consts {
     Boolean(bool),
     Float(f64),
 }
+
+// Render the AST back into SQL text that the parser accepts, keywords are written in upper case.
+// The trailing semicolon is not part of the statement and is left to the caller.
+impl Display for Statement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Statement::CreateTable {
+                name,
+                columns,
+                if_not_exists,
+                temporary,
+            } => {
+                if *temporary {
+                    write!(f, "CREATE TEMPORARY TABLE ")?;
+                } else {
+                    write!(f, "CREATE TABLE ")?;
+                }
+                if *if_not_exists {
+                    write!(f, "IF NOT EXISTS ")?;
+                }
+                write!(f, "{name} ({})", join(columns))
+            }
+            Statement::CreateTableAs {
+                name,
+                if_not_exists,
+                temporary,
+                query,
+            } => {
+                if *temporary {
+                    write!(f, "CREATE TEMPORARY TABLE ")?;
+                } else {
+                    write!(f, "CREATE TABLE ")?;
+                }
+                if *if_not_exists {
+                    write!(f, "IF NOT EXISTS ")?;
+                }
+                write!(f, "{name} AS {query}")
+            }
+            Statement::DropTable { name, if_exists } => {
+                write!(f, "DROP TABLE ")?;
+                if *if_exists {
+                    write!(f, "IF EXISTS ")?;
+                }
+                write!(f, "{name}")
+            }
+            Statement::ShowTables => write!(f, "SHOW TABLES"),
+            Statement::Begin => write!(f, "BEGIN"),
+            Statement::Commit => write!(f, "COMMIT"),
+            Statement::Rollback => write!(f, "ROLLBACK"),
+            Statement::Describe { table_name } => write!(f, "DESCRIBE {table_name}"),
+            Statement::AddColumn { table_name, column } => {
+                write!(f, "ALTER TABLE {table_name} ADD COLUMN {column}")
+            }
+            Statement::Insert {
+                table_name,
+                columns,
+                values,
+                if_not_exists,
+            } => {
+                write!(f, "INSERT INTO {table_name}")?;
+                if let Some(columns) = columns {
+                    write!(f, " ({})", columns.join(", "))?;
+                }
+                let values = values
+                    .iter()
+                    .map(|row| format!("({})", join(row)))
+                    .collect::<Vec<_>>();
+                write!(f, " VALUES {}", values.join(", "))?;
+                if *if_not_exists {
+                    write!(f, " IF NOT EXISTS")?;
+                }
+                Ok(())
+            }
+            Statement::Select {
+                select,
+                from,
+                joins,
+                as_of,
+                where_clause,
+                group_by,
+                order_by,
+                limit,
+                offset,
+            } => {
+                if select.is_empty() {
+                    write!(f, "SELECT * FROM {from}")?;
+                } else {
+                    let items = select
+                        .iter()
+                        .map(|(expr, alias)| match alias {
+                            Some(alias) => format!("{expr} AS {alias}"),
+                            None => expr.to_string(),
+                        })
+                        .collect::<Vec<_>>();
+                    write!(f, "SELECT {} FROM {from}", items.join(", "))?;
+                }
+                for join in joins {
+                    match &join.kind {
+                        JoinKind::Natural => write!(f, " NATURAL JOIN {}", join.table_name)?,
+                        JoinKind::Inner(on) => write!(f, " JOIN {} ON {on}", join.table_name)?,
+                    }
+                }
+                if let Some(timestamp) = as_of {
+                    write!(f, " AS OF TIMESTAMP {timestamp}")?;
+                }
+                write_where(f, where_clause)?;
+                if !group_by.is_empty() {
+                    write!(f, " GROUP BY {}", group_by.join(", "))?;
+                }
+                write_order_limit(f, order_by, limit, offset)
+            }
+            Statement::Union {
+                left,
+                right,
+                all,
+                order_by,
+                limit,
+                offset,
+            } => {
+                let all = if *all { " ALL" } else { "" };
+                write!(f, "{left} UNION{all} {right}")?;
+                write_order_limit(f, order_by, limit, offset)
+            }
+            Statement::Update {
+                table_name,
+                columns,
+                where_clause,
+                limit,
+            } => {
+                let sets = columns
+                    .iter()
+                    .map(|(col, expr)| format!("{col} = {expr}"))
+                    .collect::<Vec<_>>();
+                write!(f, "UPDATE {table_name} SET {}", sets.join(", "))?;
+                write_where(f, where_clause)?;
+                write_limit(f, limit)
+            }
+            Statement::Delete {
+                table_name,
+                where_clause,
+                limit,
+            } => {
+                write!(f, "DELETE FROM {table_name}")?;
+                write_where(f, where_clause)?;
+                write_limit(f, limit)
+            }
+        }
+    }
+}
+
+fn join<T: Display>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn write_where(f: &mut Formatter<'_>, where_clause: &Option<Predicate>) -> std::fmt::Result {
+    match where_clause {
+        Some(predicate) => write!(f, " WHERE {predicate}"),
+        None => Ok(()),
+    }
+}
+
+fn write_limit(f: &mut Formatter<'_>, limit: &Option<Expression>) -> std::fmt::Result {
+    match limit {
+        Some(limit) => write!(f, " LIMIT {limit}"),
+        None => Ok(()),
+    }
+}
+
+fn write_order_limit(
+    f: &mut Formatter<'_>,
+    order_by: &[(String, OrderDirection)],
+    limit: &Option<Expression>,
+    offset: &Option<Expression>,
+) -> std::fmt::Result {
+    if !order_by.is_empty() {
+        let orders = order_by
+            .iter()
+            .map(|(col, direction)| format!("{col} {direction}"))
+            .collect::<Vec<_>>();
+        write!(f, " ORDER BY {}", orders.join(", "))?;
+    }
+    write_limit(f, limit)?;
+    match offset {
+        Some(offset) => write!(f, " OFFSET {offset}"),
+        None => Ok(()),
+    }
+}
+
+impl Display for Predicate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Predicate::Compare(left, operator, right) => write!(f, "{left} {operator} {right}"),
+            Predicate::Between(col, low, high) => write!(f, "{col} BETWEEN {low} AND {high}"),
+            Predicate::Like(col, pattern) => write!(f, "{col} LIKE {pattern}"),
+            Predicate::Column(col) => write!(f, "{col}"),
+            Predicate::Not(p) => match **p {
+                Predicate::And(..) | Predicate::Or(..) => write!(f, "NOT ({p})"),
+                _ => write!(f, "NOT {p}"),
+            },
+            // The parser groups from the left and AND binds tighter than OR, anything else
+            // needs parentheses
+            Predicate::And(l, r) => {
+                match **l {
+                    Predicate::Or(..) => write!(f, "({l})")?,
+                    _ => write!(f, "{l}")?,
+                }
+                match **r {
+                    Predicate::And(..) | Predicate::Or(..) => write!(f, " AND ({r})"),
+                    _ => write!(f, " AND {r}"),
+                }
+            }
+            Predicate::Or(l, r) => match **r {
+                Predicate::Or(..) => write!(f, "{l} OR ({r})"),
+                _ => write!(f, "{l} OR {r}"),
+            },
+        }
+    }
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operator::Equal => write!(f, "="),
+            Operator::GreaterThan => write!(f, ">"),
+            Operator::GreaterThanOrEqual => write!(f, ">="),
+            Operator::LessThan => write!(f, "<"),
+            Operator::LessThanOrEqual => write!(f, "<="),
+        }
+    }
+}
+
+impl Display for OrderDirection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrderDirection::Asc => write!(f, "ASC"),
+            OrderDirection::Desc => write!(f, "DESC"),
+        }
+    }
+}
+
+impl Display for Column {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.width {
+            Some(16) => write!(f, "{} SMALLINT", self.name)?,
+            _ => write!(f, "{} {}", self.name, self.data_type)?,
+        }
+        if self.primary_key {
+            write!(f, " PRIMARY KEY")?;
+        }
+        match self.nullable {
+            Some(true) => write!(f, " NULL")?,
+            Some(false) => write!(f, " NOT NULL")?,
+            None => {}
+        }
+        if let Some(default) = &self.default {
+            write!(f, " DEFAULT {default}")?;
+        }
+        if self.index {
+            write!(f, " INDEX")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expression::Consts(c) => write!(f, "{c}"),
+            Expression::Field(name) => write!(f, "{name}"),
+            Expression::Operation(op) => {
+                // Nested operations are parenthesized to keep their precedence
+                let (left, right) = op.operands();
+                let operand = |e: &Expression| match e {
+                    Expression::Operation(_) => format!("({e})"),
+                    e => e.to_string(),
+                };
+                write!(f, "{} {} {}", operand(left), op.symbol(), operand(right))
+            }
+            Expression::Function(name, arg) => write!(f, "{name}({arg})"),
+            Expression::Wildcard(table) => write!(f, "{table}.*"),
+            Expression::Default => write!(f, "DEFAULT"),
+        }
+    }
+}
+
+impl Display for Consts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Value::from(self).to_sql_literal())
+    }
+}
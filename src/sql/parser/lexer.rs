@@ -21,6 +21,8 @@ pub enum Token {
 
     Comma, // Comma ,
 
+    Period, // Period .
+
     Semicolon, // Semicolon ;
 
     Asterisk, // Asterisk *
@@ -32,6 +34,14 @@ pub enum Token {
     Slash, // Slash /
 
     Equal, // Equal sign =
+
+    GreaterThan, // Greater than >
+
+    GreaterThanOrEqual, // Greater than or equal >=
+
+    LessThan, // Less than <
+
+    LessThanOrEqual, // Less than or equal <=
 }
 
 impl Display for Token {
@@ -44,12 +54,17 @@ impl Display for Token {
             Token::OpenParen => write!(f, "("),
             Token::CloseParen => write!(f, ")"),
             Token::Comma => write!(f, ","),
+            Token::Period => write!(f, "."),
             Token::Semicolon => write!(f, ";"),
             Token::Asterisk => write!(f, "*"),
             Token::Plus => write!(f, "+"),
             Token::Minus => write!(f, "-"),
             Token::Slash => write!(f, "/"),
             Token::Equal => write!(f, "="),
+            Token::GreaterThan => write!(f, ">"),
+            Token::GreaterThanOrEqual => write!(f, ">="),
+            Token::LessThan => write!(f, "<"),
+            Token::LessThanOrEqual => write!(f, "<="),
         }
     }
 }
@@ -58,10 +73,23 @@ impl Display for Token {
 #[strum(ascii_case_insensitive, serialize_all = "UPPERCASE")]
 pub enum Keyword {
     Create,
+    Drop,
+    Alter,
+    Add,
+    Column,
     Table,
+    If,
+    Exists,
+    Show,
+    Tables,
+    Describe,
+    Temp,
+    Temporary,
 
     Int,
     Integer,
+    SmallInt,
+    BigInt,
     Boolean,
     Bool,
     String,
@@ -74,6 +102,7 @@ pub enum Keyword {
     True,
     False,
     Key,
+    Index,
     Default,
     Not,
     Null,
@@ -88,11 +117,34 @@ pub enum Keyword {
     From,
     Where,
     Order,
+    Group,
     By,
     Asc,
     Desc,
     Limit,
-    Offset
+    Offset,
+    Fetch,
+    First,
+    Next,
+    Row,
+    Rows,
+    Only,
+    Between,
+    Like,
+    And,
+    Or,
+    As,
+    Natural,
+    Inner,
+    Join,
+    On,
+    Union,
+    All,
+    Of,
+    Timestamp,
+    Begin,
+    Commit,
+    Rollback,
 }
 
 /// Lexical Analyzer Lexer Definition
@@ -107,11 +159,12 @@ pub enum Keyword {
 ///    where data_type is:
 ///     - BOOLEAN(BOOL): true | false
 ///     - FLOAT(DOUBLE)
-///     - INTEGER(INT)
+///     - INTEGER(INT, BIGINT)
+///     - SMALLINT: an integer between -32768 and 32767
 ///     - STRING(TEXT, VARCHAR)
 ///
 ///    where column_constraint is:
-///    [ NOT NULL | NULL | DEFAULT expr ]
+///    [ NOT NULL | NULL | DEFAULT expr | PRIMARY KEY | INDEX ]
 ///
 /// 2. Insert Into
 /// -------------------------------------
@@ -124,12 +177,19 @@ pub enum Keyword {
 /// SELECT * FROM table_name;
 pub struct Lexer<'a> {
     iter: Peekable<Chars<'a>>,
+    /// Lowercase unquoted identifiers, quoted ones keep their case
+    lowercase_idents: bool,
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(sql_text: &'a str) -> Self {
+        Self::new_with_lowercase_idents(sql_text, false)
+    }
+
+    pub fn new_with_lowercase_idents(sql_text: &'a str, lowercase_idents: bool) -> Self {
         Self {
             iter: sql_text.chars().peekable(),
+            lowercase_idents,
         }
     }
 
@@ -139,6 +199,35 @@ impl<'a> Lexer<'a> {
         self.next_while(char::is_whitespace);
     }
 
+    // Remove whitespace and comments: `-- ...` up to the end of the line and `/* ... */`,
+    // block comments don't nest
+    fn erase_whitespace_and_comments(&mut self) -> Result<()> {
+        loop {
+            self.erase_whitespace();
+            let mut ahead = self.iter.clone();
+            match (ahead.next(), ahead.next()) {
+                (Some('-'), Some('-')) => {
+                    self.next_while(|c| c != '\n');
+                }
+                (Some('/'), Some('*')) => {
+                    self.iter.nth(1);
+                    loop {
+                        match self.iter.next() {
+                            Some('*') if self.next_if(|c| c == '/').is_some() => break,
+                            Some(_) => {}
+                            None => {
+                                return Err(Error::ParserError(
+                                    "[Lexer] Unexpected end of comment".to_string(),
+                                ))
+                            }
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
     /// If the condition is met, jump to the next character and return the character
     fn next_if<F: Fn(char) -> bool>(&mut self, predicate: F) -> Option<char> {
         self.iter.peek().filter(|&c| predicate(*c))?; // Return the current character if the condition is met
@@ -164,12 +253,13 @@ impl<'a> Lexer<'a> {
 
     // Scan to get the next Token
     fn scan(&mut self) -> Result<Option<Token>> {
-        // Remove whitespace characters in the string
-        self.erase_whitespace();
+        // Remove whitespace characters and comments in the string
+        self.erase_whitespace_and_comments()?;
         // Determine based on the first character
         match self.iter.peek() {
             Some('\'') => self.scan_string(), // Scan string
-            Some(c) if c.is_ascii_digit() => Ok(self.scan_number()), // Scan number
+            Some('"') => self.scan_quoted_ident(), // Scan quoted Ident
+            Some(c) if c.is_ascii_digit() => self.scan_number(), // Scan number
             Some(c) if c.is_alphabetic() => Ok(self.scan_ident()), // Scan Ident type
             Some(_) => Ok(self.scan_symbol()), // Scan symbol
             None => Ok(None),
@@ -186,6 +276,8 @@ impl<'a> Lexer<'a> {
         let mut val = String::new();
         loop {
             match self.iter.next() {
+                // A doubled quote stands for a quote in the string
+                Some('\'') if self.next_if(|c| c == '\'').is_some() => val.push('\''),
                 Some('\'') => break,
                 Some(c) => val.push(c),
                 None => {
@@ -200,9 +292,11 @@ impl<'a> Lexer<'a> {
     }
 
     // Scan number
-    fn scan_number(&mut self) -> Option<Token> {
+    fn scan_number(&mut self) -> Result<Option<Token>> {
         // Scan a part first
-        let mut num = self.next_while(|c| c.is_ascii_digit())?;
+        let Some(mut num) = self.next_while(|c| c.is_ascii_digit()) else {
+            return Ok(None);
+        };
         // If there is a decimal point in the middle, it means it is a floating point number
         if let Some(sep) = self.next_if(|c| c == '.') {
             num.push(sep);
@@ -211,8 +305,24 @@ impl<'a> Lexer<'a> {
                 num.push(c);
             }
         }
+        // Scientific notation, e.g. 1.5e10 or 2E-3. An identifier can't start with a digit,
+        // so a letter e right after one always belongs to the number and needs an exponent
+        if let Some(e) = self.next_if(|c| c == 'e' || c == 'E') {
+            num.push(e);
+            if let Some(sign) = self.next_if(|c| c == '+' || c == '-') {
+                num.push(sign);
+            }
+            match self.next_while(|c| c.is_ascii_digit()) {
+                Some(exponent) => num.push_str(&exponent),
+                None => {
+                    return Err(Error::ParserError(format!(
+                        "[Lexer] Invalid number {num}, expected the digits of the exponent"
+                    )))
+                }
+            }
+        }
 
-        Some(Token::Number(num))
+        Ok(Some(Token::Number(num)))
     }
 
     // Scan Ident types such as table names, column names, or keywords such as CREATE, TABLE
@@ -223,7 +333,40 @@ impl<'a> Lexer<'a> {
             val.push(c);
         }
 
-        Keyword::from_str(&val).map_or(Some(Token::Ident(val)), |k| Some(Token::Keyword(k)))
+        if let Ok(keyword) = Keyword::from_str(&val) {
+            return Some(Token::Keyword(keyword));
+        }
+        if self.lowercase_idents {
+            val = val.to_lowercase();
+        }
+        Some(Token::Ident(val))
+    }
+
+    // Scan an Ident wrapped in double quotes, taken as is: its case is kept and it is never a keyword
+    fn scan_quoted_ident(&mut self) -> Result<Option<Token>> {
+        if self.next_if(|c| c == '"').is_none() {
+            return Ok(None);
+        }
+
+        let mut val = String::new();
+        loop {
+            match self.iter.next() {
+                Some('"') => break,
+                Some(c) => val.push(c),
+                None => {
+                    return Err(Error::ParserError(
+                        "[Lexer] Unexpected end of quoted identifier".to_string(),
+                    ))
+                }
+            }
+        }
+        if val.is_empty() {
+            return Err(Error::ParserError(
+                "[Lexer] Empty quoted identifier".to_string(),
+            ));
+        }
+
+        Ok(Some(Token::Ident(val)))
     }
 
     // Scan symbol
@@ -232,14 +375,23 @@ impl<'a> Lexer<'a> {
             '(' => Some(Token::OpenParen),
             ')' => Some(Token::CloseParen),
             ',' => Some(Token::Comma),
+            '.' => Some(Token::Period),
             ';' => Some(Token::Semicolon),
             '*' => Some(Token::Asterisk),
             '+' => Some(Token::Plus),
             '-' => Some(Token::Minus),
             '/' => Some(Token::Slash),
             '=' => Some(Token::Equal),
+            '>' => Some(Token::GreaterThan),
+            '<' => Some(Token::LessThan),
             _ => None,
         })
+        .map(|token| match token {
+            // Two-character operators >= and <=
+            Token::GreaterThan if self.next_if(|c| c == '=').is_some() => Token::GreaterThanOrEqual,
+            Token::LessThan if self.next_if(|c| c == '=').is_some() => Token::LessThanOrEqual,
+            token => token,
+        })
     }
 }
 
@@ -412,6 +564,30 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_lexer_scientific_number() -> Result<()> {
+        let tokens = Lexer::new("1.5e10 2E-3 7e+2 col1e5")
+            .peekable()
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Number("1.5e10".to_string()),
+                Token::Number("2E-3".to_string()),
+                Token::Number("7e+2".to_string()),
+                Token::Ident("col1e5".to_string()),
+            ]
+        );
+
+        for sql in ["1e", "1e+", "2.5E-x", "3e FROM"] {
+            match Lexer::new(sql).collect::<Result<Vec<_>>>() {
+                Err(Error::ParserError(msg)) => assert!(msg.contains("exponent"), "{msg}"),
+                res => panic!("Expected an invalid number error for {sql}, got {res:?}"),
+            }
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_lexer_string_literal() -> Result<()> {
         let tokens = Lexer::new("'hello world'")
@@ -421,6 +597,96 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_lexer_string_escaped_quotes() -> Result<()> {
+        let tokens = Lexer::new("'O''Brien' '''' '' 'it''s ''quoted''', 'a'")
+            .peekable()
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            tokens,
+            vec![
+                Token::String("O'Brien".to_string()),
+                Token::String("'".to_string()),
+                Token::String("".to_string()),
+                Token::String("it's 'quoted'".to_string()),
+                Token::Comma,
+                Token::String("a".to_string()),
+            ]
+        );
+
+        // A doubled quote doesn't close the string
+        for sql in ["'O''", "'abc''"] {
+            match Lexer::new(sql).next() {
+                Some(Err(Error::ParserError(msg))) => {
+                    assert!(msg.contains("Unexpected end of string"))
+                }
+                other => panic!("Expected an error for unclosed string {sql}, got {other:?}"),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexer_comments() -> Result<()> {
+        let tokens = Lexer::new(
+            "-- users of the application
+            CREATE TABLE users ( /* one row per account */
+                id INT PRIMARY KEY, -- generated
+                /* display name,
+                   may be changed */ name TEXT
+            ) -- the end
+            ;
+            -- nothing follows",
+        )
+        .peekable()
+        .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Keyword(Keyword::Create),
+                Token::Keyword(Keyword::Table),
+                Token::Ident("users".to_string()),
+                Token::OpenParen,
+                Token::Ident("id".to_string()),
+                Token::Keyword(Keyword::Int),
+                Token::Keyword(Keyword::Primary),
+                Token::Keyword(Keyword::Key),
+                Token::Comma,
+                Token::Ident("name".to_string()),
+                Token::Keyword(Keyword::Text),
+                Token::CloseParen,
+                Token::Semicolon,
+            ]
+        );
+
+        // A single - or / is still an operator, and comments don't apply inside strings
+        let tokens = Lexer::new("a - b/**/ / c*/**d**/e '--x' '/*y*/'")
+            .peekable()
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Ident("a".to_string()),
+                Token::Minus,
+                Token::Ident("b".to_string()),
+                Token::Slash,
+                Token::Ident("c".to_string()),
+                Token::Asterisk,
+                Token::Ident("e".to_string()),
+                Token::String("--x".to_string()),
+                Token::String("/*y*/".to_string()),
+            ]
+        );
+
+        match Lexer::new("SELECT /* unclosed */ * /* comment").nth(2) {
+            Some(Err(Error::ParserError(msg))) => {
+                assert!(msg.contains("Unexpected end of comment"), "{msg}")
+            }
+            other => panic!("Expected an error for unclosed comment, got {other:?}"),
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_lexer_error_unclosed_string() {
         let mut lex = Lexer::new("'unclosed string");
@@ -554,6 +820,57 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_lexer_qualified_wildcard() -> Result<()> {
+        let tokens = Lexer::new("t.*, 1.5")
+            .peekable()
+            .collect::<Result<Vec<_>>>()?;
+
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Ident("t".to_string()),
+                Token::Period,
+                Token::Asterisk,
+                Token::Comma,
+                Token::Number("1.5".to_string()),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lexer_comparison_operators() -> Result<()> {
+        let sql = "a > 1 b >= 2 c < 3 d <= 4 e between 5 and 6";
+        let tokens = Lexer::new(sql).peekable().collect::<Result<Vec<_>>>()?;
+
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Ident("a".to_string()),
+                Token::GreaterThan,
+                Token::Number("1".to_string()),
+                Token::Ident("b".to_string()),
+                Token::GreaterThanOrEqual,
+                Token::Number("2".to_string()),
+                Token::Ident("c".to_string()),
+                Token::LessThan,
+                Token::Number("3".to_string()),
+                Token::Ident("d".to_string()),
+                Token::LessThanOrEqual,
+                Token::Number("4".to_string()),
+                Token::Ident("e".to_string()),
+                Token::Keyword(Keyword::Between),
+                Token::Number("5".to_string()),
+                Token::Keyword(Keyword::And),
+                Token::Number("6".to_string()),
+            ]
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_lexer_mixed_case_keywords() -> Result<()> {
         let sql = "SeLeCt * FrOm users";
@@ -571,4 +888,36 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_lexer_ident_case() -> Result<()> {
+        let sql = r#"SELECT Age FROM "Users" WHERE "from" = 1"#;
+        let tokens = |lowercase| {
+            Lexer::new_with_lowercase_idents(sql, lowercase).collect::<Result<Vec<_>>>()
+        };
+        let expected = |age: &str| {
+            vec![
+                Token::Keyword(Keyword::Select),
+                Token::Ident(age.to_string()),
+                Token::Keyword(Keyword::From),
+                Token::Ident("Users".to_string()),
+                Token::Keyword(Keyword::Where),
+                Token::Ident("from".to_string()),
+                Token::Equal,
+                Token::Number("1".to_string()),
+            ]
+        };
+
+        // Quoted identifiers keep their case either way, and aren't keywords
+        assert_eq!(tokens(false)?, expected("Age"));
+        assert_eq!(tokens(true)?, expected("age"));
+
+        assert!(Lexer::new(r#"SELECT "a FROM t"#)
+            .collect::<Result<Vec<_>>>()
+            .is_err());
+        assert!(Lexer::new(r#"SELECT "" FROM t"#)
+            .collect::<Result<Vec<_>>>()
+            .is_err());
+        Ok(())
+    }
 }
@@ -1,13 +1,13 @@
 use std::{collections::BTreeMap, iter::Peekable};
 
-use ast::{Column, Expression, OrderDirection};
+use ast::{Column, Operator, OrderDirection, Predicate};
 use lexer::{Keyword, Lexer, Token};
 
 use crate::error::{Error, Result};
 
-use super::types::DataType;
+use super::types::{DataType, Value};
 
-pub(super) mod ast;
+pub mod ast;
 mod lexer;
 
 pub struct Parser<'a> {
@@ -22,6 +22,15 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Parser resolving names case-insensitively: unquoted identifiers are lowercased,
+    /// so `SELECT AGE FROM T` refers to column `age` of table `t`. Quoted identifiers
+    /// such as `"Age"` keep their case.
+    pub fn new_case_insensitive(input: &'a str) -> Self {
+        Parser {
+            lexer: Lexer::new_with_lowercase_idents(input, true).peekable(),
+        }
+    }
+
     /// Parse to get the abstract syntax tree
     pub fn parse(&mut self) -> Result<ast::Statement> {
         let stmt = self.parse_statement()?;
@@ -36,13 +45,76 @@ impl<'a> Parser<'a> {
         Ok(stmt)
     }
 
+    /// Parse a script of statements, each terminated by a semicolon, up to the end of input
+    pub fn parse_all(&mut self) -> Result<Vec<ast::Statement>> {
+        let mut stmts = Vec::new();
+        while self.peek()?.is_some() {
+            stmts.push(self.parse_statement()?);
+            self.next_expect(Token::Semicolon)?;
+        }
+        Ok(stmts)
+    }
+
+    /// Tell the kind of the next statement from its leading keyword without parsing it, the
+    /// statement can still be parsed afterwards
+    pub fn peek_statement_kind(&mut self) -> Result<ast::StatementKind> {
+        Ok(match self.peek()? {
+            Some(Token::Keyword(Keyword::Select)) => ast::StatementKind::Select,
+            Some(Token::Keyword(Keyword::Insert)) => ast::StatementKind::Insert,
+            Some(Token::Keyword(Keyword::Update)) => ast::StatementKind::Update,
+            Some(Token::Keyword(Keyword::Delete)) => ast::StatementKind::Delete,
+            Some(Token::Keyword(Keyword::Create | Keyword::Drop | Keyword::Alter)) => {
+                ast::StatementKind::Ddl
+            }
+            Some(Token::Keyword(Keyword::Show | Keyword::Describe)) => ast::StatementKind::Show,
+            Some(Token::Keyword(Keyword::Begin | Keyword::Commit | Keyword::Rollback)) => {
+                ast::StatementKind::Transaction
+            }
+            Some(token) => {
+                return Err(Error::ParserError(format!(
+                    "[Parser] Unexpected token {token}"
+                )))
+            }
+            None => {
+                return Err(Error::ParserError(
+                    "[Parser] Unexpected end of input".to_string(),
+                ))
+            }
+        })
+    }
+
     fn parse_statement(&mut self) -> Result<ast::Statement> {
         match self.peek()? {
-            Some(Token::Keyword(Keyword::Create)) => self.parse_ddl(),
-            Some(Token::Keyword(Keyword::Select)) => self.parse_select(),
+            Some(Token::Keyword(Keyword::Create))
+            | Some(Token::Keyword(Keyword::Drop))
+            | Some(Token::Keyword(Keyword::Alter)) => self.parse_ddl(),
+            Some(Token::Keyword(Keyword::Select)) => self.parse_union(),
+            Some(Token::Keyword(Keyword::Show)) => {
+                self.next()?;
+                self.next_expect(Token::Keyword(Keyword::Tables))?;
+                Ok(ast::Statement::ShowTables)
+            }
+            Some(Token::Keyword(Keyword::Describe)) => {
+                self.next()?;
+                Ok(ast::Statement::Describe {
+                    table_name: self.next_ident()?,
+                })
+            }
             Some(Token::Keyword(Keyword::Insert)) => self.parse_insert(),
             Some(Token::Keyword(Keyword::Update)) => self.parse_update(),
             Some(Token::Keyword(Keyword::Delete)) => self.parse_delete(),
+            Some(Token::Keyword(Keyword::Begin)) => {
+                self.next()?;
+                Ok(ast::Statement::Begin)
+            }
+            Some(Token::Keyword(Keyword::Commit)) => {
+                self.next()?;
+                Ok(ast::Statement::Commit)
+            }
+            Some(Token::Keyword(Keyword::Rollback)) => {
+                self.next()?;
+                Ok(ast::Statement::Rollback)
+            }
             Some(_) => Err(Error::ParserError("[Parser] Unexpected token".to_string())),
             None => Err(Error::ParserError(
                 "[Parser] Unexpected end of input".to_string(),
@@ -50,11 +122,27 @@ impl<'a> Parser<'a> {
         }
     }
 
-    // Parse Create DDL statements
+    // Parse Create, Drop and Alter DDL statements
     fn parse_ddl(&mut self) -> Result<ast::Statement> {
         match self.next()? {
             Token::Keyword(Keyword::Create) => match self.next()? {
-                Token::Keyword(Keyword::Table) => self.parse_ddl_create_table(),
+                Token::Keyword(Keyword::Table) => self.parse_ddl_create_table(false),
+                Token::Keyword(Keyword::Temp | Keyword::Temporary) => {
+                    self.next_expect(Token::Keyword(Keyword::Table))?;
+                    self.parse_ddl_create_table(true)
+                }
+                token => Err(Error::ParserError(format!(
+                    "[Parser] Unexpected token, expected TABLE but got {token}"
+                ))),
+            },
+            Token::Keyword(Keyword::Drop) => match self.next()? {
+                Token::Keyword(Keyword::Table) => self.parse_ddl_drop_table(),
+                token => Err(Error::ParserError(format!(
+                    "[Parser] Unexpected token, expected TABLE but got {token}"
+                ))),
+            },
+            Token::Keyword(Keyword::Alter) => match self.next()? {
+                Token::Keyword(Keyword::Table) => self.parse_ddl_alter_table(),
                 token => Err(Error::ParserError(format!(
                     "[Parser] Unexpected token, expected TABLE but got {token}"
                 ))),
@@ -67,9 +155,24 @@ impl<'a> Parser<'a> {
     }
 
     // Parse Create Table statement
-    fn parse_ddl_create_table(&mut self) -> Result<ast::Statement> {
+    fn parse_ddl_create_table(&mut self, temporary: bool) -> Result<ast::Statement> {
+        let if_not_exists = self.parse_if_not_exists()?;
         // Expect the Table name
         let table_name = self.next_ident()?;
+        // CREATE TABLE name AS SELECT ...
+        if self.next_if_token(Token::Keyword(Keyword::As)).is_some() {
+            return match self.peek()? {
+                Some(Token::Keyword(Keyword::Select)) => Ok(ast::Statement::CreateTableAs {
+                    name: table_name,
+                    if_not_exists,
+                    temporary,
+                    query: Box::new(self.parse_union()?),
+                }),
+                token => Err(Error::ParserError(format!(
+                    "[Parser] Expected SELECT after AS, got {token:?}"
+                ))),
+            };
+        }
         self.next_expect(Token::OpenParen)?;
         // There should be parentheses after the table name
         let mut columns = vec![];
@@ -77,42 +180,93 @@ impl<'a> Parser<'a> {
             columns.push(self.parse_ddl_column()?);
             // If there is no comma, the column parsing is complete, break out of the loop
             if self.next_if_token(Token::Comma).is_none() {
+                self.next_expect(Token::CloseParen)?;
+                break;
+            }
+            if self.next_if_list_end() {
                 break;
             }
         }
 
-        self.next_expect(Token::CloseParen)?;
         Ok(ast::Statement::CreateTable {
             name: table_name,
             columns,
+            if_not_exists,
+            temporary,
+        })
+    }
+
+    // Parse Drop Table statement
+    fn parse_ddl_drop_table(&mut self) -> Result<ast::Statement> {
+        let if_exists = self.parse_if_exists()?;
+        Ok(ast::Statement::DropTable {
+            name: self.next_ident()?,
+            if_exists,
+        })
+    }
+
+    // Parse Alter Table statement: ALTER TABLE name ADD [COLUMN] column_definition
+    fn parse_ddl_alter_table(&mut self) -> Result<ast::Statement> {
+        let table_name = self.next_ident()?;
+        self.next_expect(Token::Keyword(Keyword::Add))?;
+        self.next_if_token(Token::Keyword(Keyword::Column));
+        Ok(ast::Statement::AddColumn {
+            table_name,
+            column: self.parse_ddl_column()?,
         })
     }
 
+    // Parse an optional IF EXISTS clause, returns whether it was given
+    fn parse_if_exists(&mut self) -> Result<bool> {
+        if self.next_if_token(Token::Keyword(Keyword::If)).is_none() {
+            return Ok(false);
+        }
+        self.next_expect(Token::Keyword(Keyword::Exists))?;
+        Ok(true)
+    }
+
+    // Parse an optional IF NOT EXISTS clause, returns whether it was given
+    fn parse_if_not_exists(&mut self) -> Result<bool> {
+        if self.next_if_token(Token::Keyword(Keyword::If)).is_none() {
+            return Ok(false);
+        }
+        self.next_expect(Token::Keyword(Keyword::Not))?;
+        self.next_expect(Token::Keyword(Keyword::Exists))?;
+        Ok(true)
+    }
+
     // Parse column information
     fn parse_ddl_column(&mut self) -> Result<ast::Column> {
-        let mut column = Column {
-            name: self.next_ident()?,
-            data_type: match self.next()? {
-                Token::Keyword(Keyword::Int) | Token::Keyword(Keyword::Integer) => {
-                    DataType::Integer
-                }
-                Token::Keyword(Keyword::String)
-                | Token::Keyword(Keyword::Text)
-                | Token::Keyword(Keyword::Varchar) => DataType::String,
-                Token::Keyword(Keyword::Float) | Token::Keyword(Keyword::Double) => DataType::Float,
+        let name = self.next_ident()?;
+        let (data_type, width) = match self.next()? {
+            Token::Keyword(Keyword::Int)
+            | Token::Keyword(Keyword::Integer)
+            | Token::Keyword(Keyword::BigInt) => (DataType::Integer, None),
+            Token::Keyword(Keyword::SmallInt) => (DataType::Integer, Some(16)),
+            Token::Keyword(Keyword::String)
+            | Token::Keyword(Keyword::Text)
+            | Token::Keyword(Keyword::Varchar) => (DataType::String, None),
+            Token::Keyword(Keyword::Float) | Token::Keyword(Keyword::Double) => {
+                (DataType::Float, None)
+            }
 
-                Token::Keyword(Keyword::Boolean) | Token::Keyword(Keyword::Bool) => {
-                    DataType::Boolean
-                }
-                token => {
-                    return Err(Error::ParserError(format!(
-                        "[Parser] Unexpected token {token}"
-                    )))
-                }
-            },
+            Token::Keyword(Keyword::Boolean) | Token::Keyword(Keyword::Bool) => {
+                (DataType::Boolean, None)
+            }
+            token => {
+                return Err(Error::ParserError(format!(
+                    "[Parser] Unexpected token {token}"
+                )))
+            }
+        };
+        let mut column = Column {
+            name,
+            data_type,
+            width,
             nullable: None,
             primary_key: false,
             default: None,
+            index: false,
         };
 
         // Parse the default value of the column and whether it can be empty
@@ -128,6 +282,7 @@ impl<'a> Parser<'a> {
                     self.next_expect(Token::Keyword(Keyword::Key))?;
                     column.primary_key = true;
                 }
+                Keyword::Index => column.index = true,
                 keyword => {
                     return Err(Error::ParserError(format!(
                         "[Parser] Unexpected keyword {keyword}"
@@ -140,15 +295,67 @@ impl<'a> Parser<'a> {
     }
 
     // Parse expressions
+    // Parse an expression: term [(+ | -) term ...], operators of the same precedence
+    // associate to the left
     fn parse_expression(&mut self) -> Result<ast::Expression> {
+        let factor = self.parse_factor()?;
+        self.parse_expression_from(factor)
+    }
+
+    // Parse the rest of an expression whose first factor has already been parsed
+    fn parse_expression_from(&mut self, factor: ast::Expression) -> Result<ast::Expression> {
+        let mut expr = self.parse_term_from(factor)?;
+        loop {
+            expr = if self.next_if_token(Token::Plus).is_some() {
+                ast::Operation::Add(Box::new(expr), Box::new(self.parse_term()?)).into()
+            } else if self.next_if_token(Token::Minus).is_some() {
+                ast::Operation::Subtract(Box::new(expr), Box::new(self.parse_term()?)).into()
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    // Parse a term: factor [(* | /) factor ...]
+    fn parse_term(&mut self) -> Result<ast::Expression> {
+        let factor = self.parse_factor()?;
+        self.parse_term_from(factor)
+    }
+
+    fn parse_term_from(&mut self, factor: ast::Expression) -> Result<ast::Expression> {
+        let mut expr = factor;
+        loop {
+            expr = if self.next_if_token(Token::Asterisk).is_some() {
+                ast::Operation::Multiply(Box::new(expr), Box::new(self.parse_factor()?)).into()
+            } else if self.next_if_token(Token::Slash).is_some() {
+                ast::Operation::Divide(Box::new(expr), Box::new(self.parse_factor()?)).into()
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    // Parse a factor: a constant, a column name or a parenthesized expression, negated by a
+    // leading minus sign
+    fn parse_factor(&mut self) -> Result<ast::Expression> {
         Ok(match self.next()? {
-            Token::Number(n) => {
-                if n.chars().all(|c| c.is_ascii_digit()) {
-                    ast::Consts::Integer(n.parse()?).into()
-                } else {
-                    ast::Consts::Float(n.parse()?).into()
-                }
+            // A negative number is a constant of its own, the smallest integer can't be
+            // negated once parsed. Anything else is multiplied by -1.
+            Token::Minus => match self.next_if(|token| matches!(token, Token::Number(_))) {
+                Some(Token::Number(n)) => Self::number(format!("-{n}"))?,
+                _ => ast::Operation::Multiply(
+                    Box::new(ast::Consts::Integer(-1).into()),
+                    Box::new(self.parse_factor()?),
+                )
+                .into(),
+            },
+            Token::Ident(name) => ast::Expression::Field(self.qualified(name)?),
+            Token::OpenParen => {
+                let expr = self.parse_expression()?;
+                self.next_expect(Token::CloseParen)?;
+                expr
             }
+            Token::Number(n) => Self::number(n)?,
             Token::String(s) => ast::Consts::String(s).into(),
             Token::Keyword(Keyword::True) => ast::Consts::Boolean(true).into(),
             Token::Keyword(Keyword::False) => ast::Consts::Boolean(false).into(),
@@ -161,38 +368,182 @@ impl<'a> Parser<'a> {
         })
     }
 
+    // A number with a decimal point or an exponent is a float, otherwise an integer
+    fn number(n: String) -> Result<ast::Expression> {
+        if n.trim_start_matches('-')
+            .chars()
+            .all(|c| c.is_ascii_digit())
+        {
+            Ok(ast::Consts::Integer(n.parse()?).into())
+        } else {
+            Ok(ast::Consts::Float(n.parse()?).into())
+        }
+    }
+
+    // Parse SELECT ... [UNION [ALL] SELECT ...], unions chain from left to right. An ORDER BY,
+    // LIMIT or OFFSET after the last query applies to the whole union, so the queries before
+    // it can't have one
+    fn parse_union(&mut self) -> Result<ast::Statement> {
+        let mut stmt = self.parse_select()?;
+        while self.next_if_token(Token::Keyword(Keyword::Union)).is_some() {
+            let last = match &stmt {
+                ast::Statement::Union { right, .. } => right,
+                stmt => stmt,
+            };
+            if let ast::Statement::Select {
+                order_by,
+                limit,
+                offset,
+                ..
+            } = last
+            {
+                if !order_by.is_empty() || limit.is_some() || offset.is_some() {
+                    return Err(Error::ParserError(
+                        "[Parser] ORDER BY, LIMIT and OFFSET can only follow the last query of a UNION"
+                            .to_string(),
+                    ));
+                }
+            }
+            let all = self.next_if_token(Token::Keyword(Keyword::All)).is_some();
+            stmt = ast::Statement::Union {
+                left: Box::new(stmt),
+                right: Box::new(self.parse_select()?),
+                all,
+                order_by: vec![],
+                limit: None,
+                offset: None,
+            };
+        }
+
+        // Move the clauses the last query was parsed with up to the union
+        if let ast::Statement::Union {
+            right,
+            order_by,
+            limit,
+            offset,
+            ..
+        } = &mut stmt
+        {
+            if let ast::Statement::Select {
+                order_by: last_order_by,
+                limit: last_limit,
+                offset: last_offset,
+                ..
+            } = right.as_mut()
+            {
+                *order_by = std::mem::take(last_order_by);
+                *limit = last_limit.take();
+                *offset = last_offset.take();
+            }
+        }
+
+        Ok(stmt)
+    }
+
     fn parse_select(&mut self) -> Result<ast::Statement> {
         self.next_expect(Token::Keyword(Keyword::Select))?;
-        self.next_expect(Token::Asterisk)?;
+        let select = self.parse_select_clause()?;
         self.next_expect(Token::Keyword(Keyword::From))?;
-
-        // Expect the table name
-        let table_name = self.next_ident()?;
+        let from = self.parse_from_item()?;
+        let joins = self.parse_joins()?;
+        let as_of = self.parse_as_of()?;
+        let where_clause = self.parse_where_clause()?;
+        let group_by = self.parse_group_clause()?;
+        let order_by = self.parse_order_clause()?;
+        let (limit, offset) = self.parse_limit_offset()?;
 
         Ok(ast::Statement::Select {
-            table_name,
-            where_clause: self.parse_where_clause()?,
-            order_by: self.parse_order_clause()?,
-            limit: {
-                if self.next_if_token(Token::Keyword(Keyword::Limit)).is_some() {
-                    Some(self.parse_expression()?)
-                } else {
-                    None
-                }
-            },
-            offset: {
-                if self
-                    .next_if_token(Token::Keyword(Keyword::Offset))
-                    .is_some()
-                {
-                    Some(self.parse_expression()?)
-                } else {
-                    None
-                }
-            },
+            select,
+            from,
+            joins,
+            as_of,
+            where_clause,
+            group_by,
+            order_by,
+            limit,
+            offset,
         })
     }
 
+    // Parse the point in time to read the data at: AS OF TIMESTAMP 'YYYY-MM-DD HH:MM:SS' |
+    // AS OF TIMESTAMP unix_millis
+    fn parse_as_of(&mut self) -> Result<Option<u64>> {
+        if self.next_if_token(Token::Keyword(Keyword::As)).is_none() {
+            return Ok(None);
+        }
+        self.next_expect(Token::Keyword(Keyword::Of))?;
+        self.next_expect(Token::Keyword(Keyword::Timestamp))?;
+        match self.next()? {
+            Token::String(s) => parse_timestamp(&s).map(Some),
+            Token::Number(n) => n.parse().map(Some).map_err(|_| {
+                Error::ParserError(format!(
+                    "[Parser] Invalid timestamp {n}, expected unix milliseconds"
+                ))
+            }),
+            token => Err(Error::ParserError(format!(
+                "[Parser] Expected a timestamp after AS OF TIMESTAMP, got {token}"
+            ))),
+        }
+    }
+
+    // Parse the select list: * | expr [AS alias] [, ...], an empty list stands for *. An
+    // expression is an aggregate function call, or arithmetic over columns and constants
+    fn parse_select_clause(&mut self) -> Result<Vec<(ast::Expression, Option<String>)>> {
+        if self.next_if_token(Token::Asterisk).is_some() {
+            return Ok(vec![]);
+        }
+
+        let mut select = vec![];
+        loop {
+            let expr = match self.peek()? {
+                Some(Token::Ident(_)) => {
+                    let name = self.next_ident()?;
+                    let factor = if self.next_if_token(Token::Period).is_some() {
+                        // table.* can't be renamed
+                        if self.next_if_token(Token::Asterisk).is_some() {
+                            select.push((ast::Expression::Wildcard(name), None));
+                            if self.next_if_token(Token::Comma).is_none() {
+                                break;
+                            }
+                            continue;
+                        }
+                        ast::Expression::Field(format!("{name}.{}", self.next_ident()?))
+                    } else if self.peek()? == Some(Token::OpenParen) {
+                        self.parse_function(name)?
+                    } else {
+                        ast::Expression::Field(name)
+                    };
+                    self.parse_expression_from(factor)?
+                }
+                _ => self.parse_expression()?,
+            };
+            let alias = match self.next_if_token(Token::Keyword(Keyword::As)) {
+                Some(_) => Some(self.next_ident()?),
+                None => None,
+            };
+            select.push((expr, alias));
+
+            if self.next_if_token(Token::Comma).is_none() {
+                break;
+            }
+        }
+
+        Ok(select)
+    }
+
+    // Parse the rest of an aggregate function call after its name: (column) or (*)
+    fn parse_function(&mut self, name: String) -> Result<ast::Expression> {
+        let name = name.to_uppercase();
+        self.next_expect(Token::OpenParen)?;
+        let arg = match self.next_if_token(Token::Asterisk) {
+            Some(_) => "*".to_string(),
+            None => self.next_column()?,
+        };
+        self.next_expect(Token::CloseParen)?;
+
+        Ok(ast::Expression::Function(name, arg))
+    }
+
     fn parse_insert(&mut self) -> Result<ast::Statement> {
         self.next_expect(Token::Keyword(Keyword::Insert))?;
         self.next_expect(Token::Keyword(Keyword::Into))?;
@@ -212,6 +563,7 @@ impl<'a> Parser<'a> {
             table_name,
             columns,
             values,
+            if_not_exists: self.parse_if_not_exists()?,
         })
     }
 
@@ -224,6 +576,7 @@ impl<'a> Parser<'a> {
                 express.push(self.parse_expression()?);
                 match self.next()? {
                     Token::CloseParen => break,
+                    Token::Comma if self.next_if_list_end() => break,
                     Token::Comma => continue,
                     token => {
                         return Err(Error::ParserError(format!(
@@ -232,6 +585,16 @@ impl<'a> Parser<'a> {
                     }
                 }
             }
+            // Every row must have as many values as the first one
+            if let Some(first) = values.first().map(Vec::len) {
+                if express.len() != first {
+                    return Err(Error::ParserError(format!(
+                        "[Parser] VALUES row {} has {} values, expected {first} like the first row",
+                        values.len() + 1,
+                        express.len()
+                    )));
+                }
+            }
             values.push(express);
             if self.next_if_token(Token::Comma).is_none() {
                 break;
@@ -243,10 +606,11 @@ impl<'a> Parser<'a> {
     fn parse_insert_columns(&mut self) -> Result<Vec<String>> {
         let mut columns = vec![];
         loop {
+            columns.push(self.next_ident()?);
             match self.next()? {
-                Token::Ident(s) => columns.push(s),
-                Token::Comma => continue,
                 Token::CloseParen => break,
+                Token::Comma if self.next_if_list_end() => break,
+                Token::Comma => continue,
                 token => {
                     return Err(Error::ParserError(format!(
                         "[Parser] Unexpected token {token}"
@@ -265,7 +629,10 @@ impl<'a> Parser<'a> {
         loop {
             let column = self.next_ident()?;
             self.next_expect(Token::Equal)?;
-            let expr = self.parse_expression()?;
+            let expr = match self.next_if_token(Token::Keyword(Keyword::Default)) {
+                Some(_) => ast::Expression::Default,
+                None => self.parse_expression()?,
+            };
             if columns.contains_key(&column) {
                 return Err(Error::ParserError(format!(
                     "[Parser] Duplicate column name {column}"
@@ -281,6 +648,7 @@ impl<'a> Parser<'a> {
             table_name,
             columns,
             where_clause: self.parse_where_clause()?,
+            limit: self.parse_limit_clause()?,
         })
     }
 
@@ -294,20 +662,246 @@ impl<'a> Parser<'a> {
         Ok(ast::Statement::Delete {
             table_name,
             where_clause,
+            limit: self.parse_limit_clause()?,
+        })
+    }
+
+    // Parse the source of a SELECT: a table name or (VALUES ...) AS alias [(column, ...)]
+    fn parse_from_item(&mut self) -> Result<ast::FromItem> {
+        if self.next_if_token(Token::OpenParen).is_none() {
+            return Ok(ast::FromItem::Table(self.next_ident()?));
+        }
+
+        self.next_expect(Token::Keyword(Keyword::Values))?;
+        let rows = self.parse_values()?;
+        self.next_expect(Token::CloseParen)?;
+        // The alias is required, the AS before it isn't
+        self.next_if_token(Token::Keyword(Keyword::As));
+        let alias = self.next_ident()?;
+        let columns = match self.next_if_token(Token::OpenParen) {
+            Some(_) => Some(self.parse_insert_columns()?),
+            None => None,
+        };
+
+        Ok(ast::FromItem::Values {
+            rows,
+            alias,
+            columns,
+        })
+    }
+
+    // Parse the joins following the FROM table: NATURAL JOIN table | [INNER] JOIN table ON
+    // predicate [...]
+    fn parse_joins(&mut self) -> Result<Vec<ast::Join>> {
+        let mut joins = vec![];
+        loop {
+            let natural = match self.peek()? {
+                Some(Token::Keyword(Keyword::Natural)) => true,
+                Some(Token::Keyword(Keyword::Inner)) | Some(Token::Keyword(Keyword::Join)) => false,
+                _ => break,
+            };
+            // NATURAL and INNER come before JOIN
+            if self.next()? != Token::Keyword(Keyword::Join) {
+                self.next_expect(Token::Keyword(Keyword::Join))?;
+            }
+            let table_name = self.next_ident()?;
+            let kind = match natural {
+                true => ast::JoinKind::Natural,
+                false => {
+                    self.next_expect(Token::Keyword(Keyword::On))?;
+                    ast::JoinKind::Inner(self.parse_predicate()?)
+                }
+            };
+            joins.push(ast::Join { table_name, kind });
+        }
+
+        Ok(joins)
+    }
+
+    fn parse_limit_clause(&mut self) -> Result<Option<ast::Expression>> {
+        self.parse_row_count(Keyword::Limit)
+    }
+
+    fn parse_offset_clause(&mut self) -> Result<Option<ast::Expression>> {
+        self.parse_row_count(Keyword::Offset)
+    }
+
+    // Parse the rows to return, either `LIMIT m [OFFSET n]` or the standard form
+    // `[OFFSET n {ROW | ROWS}] [FETCH {FIRST | NEXT} [m] {ROW | ROWS} ONLY]`, where a missing
+    // count fetches a single row. Both end up as the same limit and offset.
+    fn parse_limit_offset(&mut self) -> Result<(Option<ast::Expression>, Option<ast::Expression>)> {
+        if let Some(limit) = self.parse_limit_clause()? {
+            return Ok((Some(limit), self.parse_offset_clause()?));
+        }
+
+        let offset = self.parse_offset_clause()?;
+        if offset.is_some() {
+            self.next_if_rows();
+        }
+        if self.next_if_token(Token::Keyword(Keyword::Fetch)).is_none() {
+            return Ok((None, offset));
+        }
+        match self.next()? {
+            Token::Keyword(Keyword::First) | Token::Keyword(Keyword::Next) => {}
+            token => {
+                return Err(Error::ParserError(format!(
+                    "[Parser] Expected FIRST or NEXT after FETCH, got token {token}"
+                )))
+            }
+        }
+        let limit = match self.next_if_rows() {
+            true => ast::Consts::Integer(1).into(),
+            false => {
+                let limit = self.parse_row_count_expression(Keyword::Fetch)?;
+                match self.next()? {
+                    Token::Keyword(Keyword::Row) | Token::Keyword(Keyword::Rows) => {}
+                    token => {
+                        return Err(Error::ParserError(format!(
+                            "[Parser] Expected ROW or ROWS, got token {token}"
+                        )))
+                    }
+                }
+                limit
+            }
+        };
+        self.next_expect(Token::Keyword(Keyword::Only))?;
+
+        Ok((Some(limit), offset))
+    }
+
+    // Skip ROW or ROWS, returning whether there was one
+    fn next_if_rows(&mut self) -> bool {
+        self.next_if(|t| {
+            matches!(
+                t,
+                Token::Keyword(Keyword::Row) | Token::Keyword(Keyword::Rows)
+            )
         })
+        .is_some()
+    }
+
+    // Parse the row count following LIMIT or OFFSET, which can't be negative
+    fn parse_row_count(&mut self, keyword: Keyword) -> Result<Option<ast::Expression>> {
+        if self
+            .next_if_token(Token::Keyword(keyword.clone()))
+            .is_none()
+        {
+            return Ok(None);
+        }
+        Ok(Some(self.parse_row_count_expression(keyword)?))
+    }
+
+    // Parse a row count, which can't be negative when it's a constant
+    fn parse_row_count_expression(&mut self, keyword: Keyword) -> Result<ast::Expression> {
+        let expr = self.parse_expression()?;
+        if expr.is_constant() {
+            if let Ok(Value::Integer(n)) = expr.evaluate(None) {
+                if n < 0 {
+                    return Err(Error::ParserError(format!(
+                        "[Parser] {keyword} must not be negative, got {n}"
+                    )));
+                }
+            }
+        }
+        Ok(expr)
     }
 
-    fn parse_where_clause(&mut self) -> Result<Option<(String, Expression)>> {
+    fn parse_where_clause(&mut self) -> Result<Option<Predicate>> {
         if self.next_if_token(Token::Keyword(Keyword::Where)).is_some() {
-            let column = self.next_ident()?;
-            self.next_expect(Token::Equal)?;
-            let value = self.parse_expression()?;
-            Ok(Some((column, value)))
+            Ok(Some(self.parse_predicate()?))
         } else {
             Ok(None)
         }
     }
 
+    // Parse a condition: conditions joined by OR, each made of comparisons joined by AND,
+    // so that AND binds tighter than OR
+    fn parse_predicate(&mut self) -> Result<Predicate> {
+        let mut predicate = self.parse_and_predicate()?;
+        while self.next_if_token(Token::Keyword(Keyword::Or)).is_some() {
+            predicate = Predicate::Or(Box::new(predicate), Box::new(self.parse_and_predicate()?));
+        }
+        Ok(predicate)
+    }
+
+    fn parse_and_predicate(&mut self) -> Result<Predicate> {
+        let mut predicate = self.parse_not_predicate()?;
+        while self.next_if_token(Token::Keyword(Keyword::And)).is_some() {
+            predicate = Predicate::And(Box::new(predicate), Box::new(self.parse_not_predicate()?));
+        }
+        Ok(predicate)
+    }
+
+    // NOT binds tighter than AND
+    fn parse_not_predicate(&mut self) -> Result<Predicate> {
+        if self.next_if_token(Token::Keyword(Keyword::Not)).is_some() {
+            return Ok(Predicate::Not(Box::new(self.parse_not_predicate()?)));
+        }
+        self.parse_comparison()
+    }
+
+    // Parse a single comparison: expr <op> expr | column BETWEEN expr AND expr |
+    // column LIKE expr | ( predicate ), or a column on its own, which tests a boolean column.
+    // A condition starting with a parenthesis is a predicate, so a left side like `(a + 1) * 2`
+    // is not supported
+    fn parse_comparison(&mut self) -> Result<Predicate> {
+        if self.next_if_token(Token::OpenParen).is_some() {
+            let predicate = self.parse_predicate()?;
+            self.next_expect(Token::CloseParen)?;
+            return Ok(predicate);
+        }
+
+        let left = self.parse_expression()?;
+        let operator = match self.peek()? {
+            Some(Token::Equal) => Operator::Equal,
+            Some(Token::GreaterThan) => Operator::GreaterThan,
+            Some(Token::GreaterThanOrEqual) => Operator::GreaterThanOrEqual,
+            Some(Token::LessThan) => Operator::LessThan,
+            Some(Token::LessThanOrEqual) => Operator::LessThanOrEqual,
+            token => {
+                // Anything but a comparison applies to a column
+                let ast::Expression::Field(column) = left else {
+                    return Err(Error::ParserError(format!(
+                        "[Parser] Expected a comparison operator after {left}"
+                    )));
+                };
+                return match token {
+                    Some(Token::Keyword(Keyword::Between)) => {
+                        self.next()?;
+                        let low = self.parse_expression()?;
+                        self.next_expect(Token::Keyword(Keyword::And))?;
+                        let high = self.parse_expression()?;
+                        Ok(Predicate::Between(column, low, high))
+                    }
+                    Some(Token::Keyword(Keyword::Like)) => {
+                        self.next()?;
+                        Ok(Predicate::Like(column, self.parse_expression()?))
+                    }
+                    _ => Ok(Predicate::Column(column)),
+                };
+            }
+        };
+        self.next()?;
+
+        Ok(Predicate::Compare(left, operator, self.parse_expression()?))
+    }
+
+    fn parse_group_clause(&mut self) -> Result<Vec<String>> {
+        let mut columns = vec![];
+        if self.next_if_token(Token::Keyword(Keyword::Group)).is_none() {
+            return Ok(columns);
+        }
+        self.next_expect(Token::Keyword(Keyword::By))?;
+
+        loop {
+            columns.push(self.next_column()?);
+            if self.next_if_token(Token::Comma).is_none() {
+                break;
+            }
+        }
+        Ok(columns)
+    }
+
     fn parse_order_clause(&mut self) -> Result<Vec<(String, OrderDirection)>> {
         let mut orders = vec![];
 
@@ -317,7 +911,7 @@ impl<'a> Parser<'a> {
         self.next_expect(Token::Keyword(Keyword::By))?;
 
         loop {
-            let col = self.next_ident()?;
+            let col = self.next_column()?;
             let ord = match self.next_if(|t| {
                 matches!(
                     t,
@@ -330,8 +924,23 @@ impl<'a> Parser<'a> {
             };
             orders.push((col, ord));
 
-            if self.next_if_token(Token::Comma).is_none() {
-                break;
+            if self.next_if_token(Token::Comma).is_some() {
+                continue;
+            }
+            // Only the clauses that can follow ORDER BY may end it, a misspelled direction
+            // would otherwise be reported as a missing semicolon
+            match self.peek()? {
+                None
+                | Some(Token::Semicolon)
+                | Some(Token::CloseParen)
+                | Some(Token::Keyword(
+                    Keyword::Limit | Keyword::Offset | Keyword::Fetch | Keyword::Union,
+                )) => break,
+                Some(token) => {
+                    return Err(Error::ParserError(format!(
+                        "[Parser] Expected ASC, DESC, comma, or end of order clause, got {token}"
+                    )))
+                }
             }
         }
 
@@ -359,6 +968,20 @@ impl<'a> Parser<'a> {
         }
     }
 
+    // A column name, optionally qualified by its table: column | table.column
+    fn next_column(&mut self) -> Result<String> {
+        let name = self.next_ident()?;
+        self.qualified(name)
+    }
+
+    // Complete a name already consumed with the column following `.`, if there's one
+    fn qualified(&mut self, name: String) -> Result<String> {
+        match self.next_if_token(Token::Period) {
+            Some(_) => Ok(format!("{name}.{}", self.next_ident()?)),
+            None => Ok(name),
+        }
+    }
+
     fn next_expect(&mut self, expected: Token) -> Result<()> {
         match self.next()? {
             token if token == expected => Ok(()),
@@ -380,11 +1003,90 @@ impl<'a> Parser<'a> {
     fn next_if_token(&mut self, expected: Token) -> Option<Token> {
         self.next_if(|token| *token == expected)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::sql::parser::ast::{Consts, Statement};
+    // Called after a comma in a parenthesized list: consumes the closing parenthesis if it
+    // follows, as generated SQL often leaves a single trailing comma
+    fn next_if_list_end(&mut self) -> bool {
+        self.next_if_token(Token::CloseParen).is_some()
+    }
+}
+
+// Parse a UTC timestamp 'YYYY-MM-DD[ HH:MM:SS[.fff]]' into unix milliseconds, a T may
+// separate the date and time, and a trailing Z is allowed
+fn parse_timestamp(s: &str) -> Result<u64> {
+    let invalid = || {
+        Error::ParserError(format!(
+            "[Parser] Invalid timestamp '{s}', expected YYYY-MM-DD HH:MM:SS[.fff] in UTC"
+        ))
+    };
+    let field = |part: &str, digits: usize, max: u64| -> Result<u64> {
+        match part.len() == digits && part.bytes().all(|b| b.is_ascii_digit()) {
+            true => part.parse().ok().filter(|n| *n <= max).ok_or_else(invalid),
+            false => Err(invalid()),
+        }
+    };
+
+    let s = s.trim();
+    let (date, time) = match s.split_once([' ', 'T']) {
+        Some((date, time)) => (date, time.strip_suffix('Z').unwrap_or(time)),
+        None => (s, "00:00:00"),
+    };
+
+    let mut parts = date.split('-');
+    let (Some(year), Some(month), Some(day), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid());
+    };
+    let (year, month, day) = (
+        field(year, 4, 9999)?,
+        field(month, 2, 12)?,
+        field(day, 2, 31)?,
+    );
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let month_days = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    if year < 1970 || month == 0 || day == 0 || day > month_days {
+        return Err(invalid());
+    }
+
+    let (time, millis) = match time.split_once('.') {
+        Some((time, fraction)) => (time, field(fraction, 3, 999)?),
+        None => (time, 0),
+    };
+    let mut parts = time.split(':');
+    let (Some(hour), Some(minute), Some(second), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid());
+    };
+    let (hour, minute, second) = (
+        field(hour, 2, 23)?,
+        field(minute, 2, 59)?,
+        field(second, 2, 59)?,
+    );
+
+    // Days since the epoch of the civil date, counting years from March so the leap day is last
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let (era, year_of_era) = (y / 400, y % 400);
+    let day_of_year = (153 * m + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    Ok(((days * 24 + hour) * 60 + minute) * 60_000 + second * 1000 + millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sql::parser::ast::{Consts, Expression, Statement};
 
 
     use super::*;
@@ -455,7 +1157,11 @@ mod tests {
         parse_eq!(
             "SELECT * FROM my_table;",
             ast::Statement::Select {
-                table_name: "my_table".to_string(),
+                select: vec![],
+                from: ast::FromItem::Table("my_table".to_string()),
+                joins: vec![],
+                as_of: None,
+                group_by: vec![],
                 where_clause: None,
                 order_by: vec![],
                 limit: None,
@@ -466,7 +1172,11 @@ mod tests {
         parse_eq!(
             "SELECT * FROM my_table ORDER by a, b asc, c desc;",
             ast::Statement::Select {
-                table_name: "my_table".to_string(),
+                select: vec![],
+                from: ast::FromItem::Table("my_table".to_string()),
+                joins: vec![],
+                as_of: None,
+                group_by: vec![],
                 where_clause: None,
                 order_by: vec![
                     ("a".to_string(), Asc),
@@ -484,8 +1194,16 @@ mod tests {
         parse_eq!(
             "SELECT * FROM my_table WHERE id = 42 limit 3 offset 3;",
             ast::Statement::Select {
-                table_name: "my_table".to_string(),
-                where_clause: Some(("id".to_string(), Expression::Consts(Consts::Integer(42)))),
+                select: vec![],
+                from: ast::FromItem::Table("my_table".to_string()),
+                joins: vec![],
+                as_of: None,
+                group_by: vec![],
+                where_clause: Some(Predicate::Compare(
+                    Expression::Field("id".to_string()),
+                    Operator::Equal,
+                    Expression::Consts(Consts::Integer(42))
+                )),
                 order_by: Vec::new(),
                 limit: Some(Expression::Consts(Consts::Integer(3))),
                 offset: Some(Expression::Consts(Consts::Integer(3)))
@@ -493,24 +1211,885 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_select_with_comparison() {
+        let select = |where_clause| ast::Statement::Select {
+            select: vec![],
+            from: ast::FromItem::Table("t".to_string()),
+            joins: vec![],
+            as_of: None,
+            group_by: vec![],
+            where_clause: Some(where_clause),
+            order_by: vec![],
+            limit: None,
+            offset: None,
+        };
+        let int = |i| Expression::Consts(Consts::Integer(i));
+
+        parse_eq!(
+            "SELECT * FROM t WHERE id > 2;",
+            select(Predicate::Compare(
+                Expression::Field("id".to_string()),
+                Operator::GreaterThan,
+                int(2)
+            ))
+        );
+        parse_eq!(
+            "SELECT * FROM t WHERE id >= 2;",
+            select(Predicate::Compare(
+                Expression::Field("id".to_string()),
+                Operator::GreaterThanOrEqual,
+                int(2)
+            ))
+        );
+        parse_eq!(
+            "SELECT * FROM t WHERE id < 2;",
+            select(Predicate::Compare(
+                Expression::Field("id".to_string()),
+                Operator::LessThan,
+                int(2)
+            ))
+        );
+        parse_eq!(
+            "SELECT * FROM t WHERE id <= 2;",
+            select(Predicate::Compare(
+                Expression::Field("id".to_string()),
+                Operator::LessThanOrEqual,
+                int(2)
+            ))
+        );
+        parse_eq!(
+            "SELECT * FROM t WHERE id BETWEEN 2 AND 4;",
+            select(Predicate::Between("id".to_string(), int(2), int(4)))
+        );
+
+        assert!(Parser::new("SELECT * FROM t WHERE id BETWEEN 2;")
+            .parse()
+            .is_err());
+        assert!(Parser::new("SELECT * FROM t WHERE id 2;").parse().is_err());
+    }
+
+    #[test]
+    fn test_scientific_number() -> Result<()> {
+        let value = |sql: &str| -> Result<Value> {
+            match Parser::new(&format!("SELECT * FROM t WHERE v = {sql};")).parse()? {
+                ast::Statement::Select {
+                    where_clause: Some(Predicate::Compare(_, _, expr)),
+                    ..
+                } => expr.evaluate(None),
+                stmt => panic!("Unexpected statement {stmt:?}"),
+            }
+        };
+        assert_eq!(value("1.5e10")?, Value::Float(1.5e10));
+        assert_eq!(value("2E-3")?, Value::Float(0.002));
+        assert_eq!(value("1e3 + 1")?, Value::Float(1001.0));
+        assert!(Parser::new("SELECT * FROM t WHERE v = 1e;")
+            .parse()
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unary_minus() -> Result<()> {
+        let value = |sql: &str| -> Result<Value> {
+            match Parser::new(&format!("INSERT INTO t VALUES ({sql});")).parse()? {
+                ast::Statement::Insert { values, .. } => values[0][0].evaluate(None),
+                stmt => panic!("Unexpected statement {stmt:?}"),
+            }
+        };
+        assert_eq!(value("-5")?, Value::Integer(-5));
+        assert_eq!(value("-1.5")?, Value::Float(-1.5));
+        assert_eq!(value("-2.5e-3")?, Value::Float(-0.0025));
+        assert_eq!(value("-9223372036854775808")?, Value::Integer(i64::MIN));
+        // It binds tighter than the other operators
+        assert_eq!(value("2 - -3")?, Value::Integer(5));
+        assert_eq!(value("-2 * 3 + 1")?, Value::Integer(-5));
+        assert_eq!(value("-(1 + 2)")?, Value::Integer(-3));
+        assert_eq!(value("- -4")?, Value::Integer(4));
+        assert!(value("-'a'").is_err());
+        assert!(Parser::new("INSERT INTO t VALUES (-);").parse().is_err());
+
+        parse_eq!(
+            "SELECT * FROM t WHERE a > -b;",
+            ast::Statement::Select {
+                select: vec![],
+                from: ast::FromItem::Table("t".to_string()),
+                joins: vec![],
+                as_of: None,
+                where_clause: Some(Predicate::Compare(
+                    Expression::Field("a".to_string()),
+                    Operator::GreaterThan,
+                    ast::Operation::Multiply(
+                        Box::new(Expression::Consts(Consts::Integer(-1))),
+                        Box::new(Expression::Field("b".to_string())),
+                    )
+                    .into(),
+                )),
+                group_by: vec![],
+                order_by: vec![],
+                limit: None,
+                offset: None,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_limit_offset() {
+        parse_eq!(
+            "SELECT * FROM t LIMIT 10 OFFSET 5;",
+            ast::Statement::Select {
+                select: vec![],
+                from: ast::FromItem::Table("t".to_string()),
+                joins: vec![],
+                as_of: None,
+                group_by: vec![],
+                where_clause: None,
+                order_by: vec![],
+                limit: Some(Expression::Consts(Consts::Integer(10))),
+                offset: Some(Expression::Consts(Consts::Integer(5))),
+            }
+        );
+
+        for sql in [
+            "SELECT * FROM t LIMIT 0 - 1;",
+            "SELECT * FROM t LIMIT 1 OFFSET 2 - 5;",
+        ] {
+            match Parser::new(sql).parse() {
+                Err(Error::ParserError(msg)) => {
+                    assert!(msg.contains("must not be negative"), "{msg}")
+                }
+                res => panic!("Expected a negative row count error, got {res:?}"),
+            }
+        }
+        assert!(Parser::new("SELECT * FROM t LIMIT -1;").parse().is_err());
+        assert!(Parser::new("SELECT * FROM t OFFSET;").parse().is_err());
+    }
+
+    #[test]
+    fn test_select_fetch_first() -> Result<()> {
+        let parse = |sql: &str| Parser::new(sql).parse();
+        assert_eq!(
+            parse("SELECT * FROM t ORDER BY id OFFSET 5 ROWS FETCH FIRST 10 ROWS ONLY;")?,
+            parse("SELECT * FROM t ORDER BY id LIMIT 10 OFFSET 5;")?
+        );
+        assert_eq!(
+            parse("SELECT * FROM t FETCH NEXT 10 ROWS ONLY;")?,
+            parse("SELECT * FROM t LIMIT 10;")?
+        );
+        assert_eq!(
+            parse("SELECT * FROM t FETCH FIRST ROW ONLY;")?,
+            parse("SELECT * FROM t LIMIT 1;")?
+        );
+        assert_eq!(
+            parse("SELECT * FROM t OFFSET 1 ROW;")?,
+            parse("SELECT * FROM t OFFSET 1;")?
+        );
+
+        assert!(parse("SELECT * FROM t FETCH FIRST 10 ROWS;").is_err());
+        assert!(parse("SELECT * FROM t FETCH 10 ROWS ONLY;").is_err());
+        assert!(parse("SELECT * FROM t FETCH FIRST 10 ONLY;").is_err());
+        assert!(parse("SELECT * FROM t FETCH FIRST 0 - 1 ROWS ONLY;").is_err());
+        assert!(parse("SELECT * FROM t LIMIT 1 FETCH FIRST 1 ROW ONLY;").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_where_and_or() {
+        let select = |where_clause| ast::Statement::Select {
+            select: vec![],
+            from: ast::FromItem::Table("t".to_string()),
+            joins: vec![],
+            as_of: None,
+            group_by: vec![],
+            where_clause: Some(where_clause),
+            order_by: vec![],
+            limit: None,
+            offset: None,
+        };
+        let eq = |col: &str, i| {
+            Box::new(Predicate::Compare(
+                Expression::Field(col.to_string()),
+                Operator::Equal,
+                Expression::Consts(Consts::Integer(i)),
+            ))
+        };
+        let and = |l, r| Box::new(Predicate::And(l, r));
+        let or = |l, r| Box::new(Predicate::Or(l, r));
+
+        // AND binds tighter than OR, both group from the left
+        parse_eq!(
+            "SELECT * FROM t WHERE a = 1 OR b = 2 AND c = 3 OR d = 4;",
+            select(Predicate::Or(
+                or(eq("a", 1), and(eq("b", 2), eq("c", 3))),
+                eq("d", 4)
+            ))
+        );
+        parse_eq!(
+            "SELECT * FROM t WHERE (a = 1 OR b = 2) AND c = 3;",
+            select(Predicate::And(or(eq("a", 1), eq("b", 2)), eq("c", 3)))
+        );
+        // The AND of BETWEEN belongs to it
+        parse_eq!(
+            "SELECT * FROM t WHERE a BETWEEN 1 AND 2 AND b = 2;",
+            select(Predicate::And(
+                Box::new(Predicate::Between(
+                    "a".to_string(),
+                    Expression::Consts(Consts::Integer(1)),
+                    Expression::Consts(Consts::Integer(2)),
+                )),
+                eq("b", 2)
+            ))
+        );
+
+        assert!(Parser::new("SELECT * FROM t WHERE (a = 1 OR b = 2;")
+            .parse()
+            .is_err());
+        assert!(Parser::new("SELECT * FROM t WHERE a = 1 AND;")
+            .parse()
+            .is_err());
+    }
+
+    #[test]
+    fn test_where_not() {
+        let select = |where_clause| ast::Statement::Select {
+            select: vec![],
+            from: ast::FromItem::Table("t".to_string()),
+            joins: vec![],
+            as_of: None,
+            group_by: vec![],
+            where_clause: Some(where_clause),
+            order_by: vec![],
+            limit: None,
+            offset: None,
+        };
+        let column = |col: &str| Box::new(Predicate::Column(col.to_string()));
+
+        parse_eq!(
+            "SELECT * FROM t WHERE NOT active;",
+            select(Predicate::Not(column("active")))
+        );
+        parse_eq!(
+            "SELECT * FROM t WHERE NOT NOT a;",
+            select(Predicate::Not(Box::new(Predicate::Not(column("a")))))
+        );
+        // NOT binds tighter than AND
+        parse_eq!(
+            "SELECT * FROM t WHERE NOT a AND b;",
+            select(Predicate::And(
+                Box::new(Predicate::Not(column("a"))),
+                column("b")
+            ))
+        );
+        parse_eq!(
+            "SELECT * FROM t WHERE NOT (a AND b);",
+            select(Predicate::Not(Box::new(Predicate::And(
+                column("a"),
+                column("b")
+            ))))
+        );
+
+        assert!(Parser::new("SELECT * FROM t WHERE NOT;").parse().is_err());
+    }
+
+    #[test]
+    fn test_where_like() {
+        let select = |where_clause| ast::Statement::Select {
+            select: vec![],
+            from: ast::FromItem::Table("t".to_string()),
+            joins: vec![],
+            as_of: None,
+            group_by: vec![],
+            where_clause: Some(where_clause),
+            order_by: vec![],
+            limit: None,
+            offset: None,
+        };
+        let like = |col: &str, pattern: &str| {
+            Predicate::Like(
+                col.to_string(),
+                Expression::Consts(Consts::String(pattern.to_string())),
+            )
+        };
+
+        parse_eq!(
+            "SELECT * FROM t WHERE name LIKE 'A%';",
+            select(like("name", "A%"))
+        );
+        parse_eq!(
+            "SELECT * FROM t WHERE NOT name LIKE '_b%' AND id = 1;",
+            select(Predicate::And(
+                Box::new(Predicate::Not(Box::new(like("name", "_b%")))),
+                Box::new(Predicate::Compare(
+                    Expression::Field("id".to_string()),
+                    Operator::Equal,
+                    Expression::Consts(Consts::Integer(1))
+                ))
+            ))
+        );
+        assert_eq!(
+            Parser::new("SELECT * FROM t WHERE name LIKE 'A%';")
+                .parse()
+                .unwrap()
+                .to_string(),
+            "SELECT * FROM t WHERE name LIKE 'A%'"
+        );
+
+        assert!(Parser::new("SELECT * FROM t WHERE name LIKE;")
+            .parse()
+            .is_err());
+    }
+
+    #[test]
+    fn test_if_exists_clauses() -> Result<()> {
+        let mut parser = Parser::new("IF EXISTS t;");
+        assert!(parser.parse_if_exists()?);
+        assert_eq!(parser.next()?, Token::Ident("t".to_string()));
+
+        let mut parser = Parser::new("if not exists t;");
+        assert!(parser.parse_if_not_exists()?);
+        assert_eq!(parser.next()?, Token::Ident("t".to_string()));
+
+        // Without the clause nothing is consumed
+        let mut parser = Parser::new("t;");
+        assert!(!parser.parse_if_exists()?);
+        assert!(!parser.parse_if_not_exists()?);
+        assert_eq!(parser.next()?, Token::Ident("t".to_string()));
+
+        assert!(Parser::new("IF t;").parse_if_exists().is_err());
+        assert!(Parser::new("IF EXISTS t;").parse_if_not_exists().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_show_tables() {
+        parse_eq!("SHOW TABLES;", ast::Statement::ShowTables);
+        parse_eq!("show tables;", ast::Statement::ShowTables);
+        assert!(Parser::new("SHOW;").parse().is_err());
+        assert!(Parser::new("SHOW TABLE;").parse().is_err());
+        assert!(Parser::new("SHOW TABLES t;").parse().is_err());
+    }
+
+    #[test]
+    fn test_describe() {
+        parse_eq!(
+            "DESCRIBE t;",
+            ast::Statement::Describe {
+                table_name: "t".to_string()
+            }
+        );
+        assert!(Parser::new("DESCRIBE;").parse().is_err());
+        assert!(Parser::new("DESCRIBE TABLE t;").parse().is_err());
+    }
+
+    #[test]
+    fn test_drop_table() {
+        parse_eq!(
+            "DROP TABLE t;",
+            ast::Statement::DropTable {
+                name: "t".to_string(),
+                if_exists: false
+            }
+        );
+        parse_eq!(
+            "drop table if exists t;",
+            ast::Statement::DropTable {
+                name: "t".to_string(),
+                if_exists: true
+            }
+        );
+        assert!(Parser::new("DROP t;").parse().is_err());
+        assert!(Parser::new("DROP TABLE IF NOT EXISTS t;").parse().is_err());
+        assert!(
+            Parser::new("CREATE TABLE IF EXISTS t (id INT PRIMARY KEY);")
+                .parse()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_alter_table_add_column() -> Result<()> {
+        let column = Column {
+            name: "c".to_string(),
+            data_type: DataType::Integer,
+            nullable: None,
+            default: Some(Consts::Integer(0).into()),
+            primary_key: false,
+            width: None,
+            index: false,
+        };
+        parse_eq!(
+            "ALTER TABLE t ADD COLUMN c INT DEFAULT 0;",
+            ast::Statement::AddColumn {
+                table_name: "t".to_string(),
+                column,
+            }
+        );
+        // COLUMN is optional
+        assert_eq!(
+            Parser::new("alter table t add c int default 0;").parse()?,
+            Parser::new("ALTER TABLE t ADD COLUMN c INT DEFAULT 0;").parse()?
+        );
+        assert!(Parser::new("ALTER t ADD COLUMN c INT;").parse().is_err());
+        assert!(Parser::new("ALTER TABLE t COLUMN c INT;").parse().is_err());
+        assert!(Parser::new("ALTER TABLE t ADD COLUMN c;").parse().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_temporary_table() -> Result<()> {
+        for sql in [
+            "CREATE TEMP TABLE t (id INT PRIMARY KEY);",
+            "create temporary table if not exists t (id int primary key);",
+        ] {
+            match Parser::new(sql).parse()? {
+                ast::Statement::CreateTable { temporary, .. } => assert!(temporary),
+                stmt => panic!("Expected CreateTable, got {stmt:?}"),
+            }
+        }
+        match Parser::new("CREATE TABLE t (id INT PRIMARY KEY);").parse()? {
+            ast::Statement::CreateTable { temporary, .. } => assert!(!temporary),
+            stmt => panic!("Expected CreateTable, got {stmt:?}"),
+        }
+        assert!(Parser::new("CREATE TEMP t (id INT PRIMARY KEY);")
+            .parse()
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_table_as() -> Result<()> {
+        match Parser::new("CREATE TEMP TABLE s AS SELECT * FROM t ORDER BY id;").parse()? {
+            ast::Statement::CreateTableAs {
+                name,
+                if_not_exists,
+                temporary,
+                query,
+            } => {
+                assert_eq!(name, "s");
+                assert!(!if_not_exists);
+                assert!(temporary);
+                assert_eq!(*query, Parser::new("SELECT * FROM t ORDER BY id;").parse()?);
+            }
+            stmt => panic!("Expected CreateTableAs, got {stmt:?}"),
+        }
+        assert!(Parser::new("CREATE TABLE s AS;").parse().is_err());
+        assert!(Parser::new("CREATE TABLE s AS DELETE FROM t;")
+            .parse()
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_arithmetic() {
+        let field = |name: &str| Box::new(Expression::Field(name.to_string()));
+        let int = |i: i64| Box::new(Expression::Consts(Consts::Integer(i)));
+        parse_eq!(
+            "INSERT INTO t VALUES (1 + 2 * 3, (1 + 2) * 3, a - 1 - b / 2);",
+            ast::Statement::Insert {
+                table_name: "t".to_string(),
+                columns: None,
+                if_not_exists: false,
+                values: vec![vec![
+                    ast::Operation::Add(
+                        int(1),
+                        Box::new(ast::Operation::Multiply(int(2), int(3)).into())
+                    )
+                    .into(),
+                    ast::Operation::Multiply(
+                        Box::new(ast::Operation::Add(int(1), int(2)).into()),
+                        int(3)
+                    )
+                    .into(),
+                    ast::Operation::Subtract(
+                        Box::new(ast::Operation::Subtract(field("a"), int(1)).into()),
+                        Box::new(ast::Operation::Divide(field("b"), int(2)).into()),
+                    )
+                    .into(),
+                ]],
+            }
+        );
+        assert!(Parser::new("INSERT INTO t VALUES (1 +);").parse().is_err());
+        assert!(Parser::new("INSERT INTO t VALUES ((1 + 2);")
+            .parse()
+            .is_err());
+
+        // Computed columns and comparisons of expressions
+        parse_eq!(
+            "SELECT a * 2 AS twice, 1 + t.b, a FROM t WHERE a + 1 = b * 2;",
+            ast::Statement::Select {
+                select: vec![
+                    (
+                        ast::Operation::Multiply(field("a"), int(2)).into(),
+                        Some("twice".to_string())
+                    ),
+                    (ast::Operation::Add(int(1), field("t.b")).into(), None),
+                    (Expression::Field("a".to_string()), None),
+                ],
+                from: ast::FromItem::Table("t".to_string()),
+                joins: vec![],
+                as_of: None,
+                group_by: vec![],
+                where_clause: Some(Predicate::Compare(
+                    ast::Operation::Add(field("a"), int(1)).into(),
+                    Operator::Equal,
+                    ast::Operation::Multiply(field("b"), int(2)).into(),
+                )),
+                order_by: vec![],
+                limit: None,
+                offset: None,
+            }
+        );
+        // Only a column can be tested with BETWEEN or LIKE, or stand on its own
+        assert!(Parser::new("SELECT * FROM t WHERE a + 1;").parse().is_err());
+        assert!(Parser::new("SELECT * FROM t WHERE a + 1 BETWEEN 1 AND 2;")
+            .parse()
+            .is_err());
+    }
+
+    #[test]
+    fn test_values_arity() {
+        match Parser::new("INSERT INTO t VALUES (1, 2), (3);").parse() {
+            Err(Error::ParserError(msg)) => {
+                assert!(msg.contains("row 2 has 1 values, expected 2"), "{msg}")
+            }
+            res => panic!("Expected an arity error, got {res:?}"),
+        }
+        assert!(Parser::new("INSERT INTO t VALUES (1), (2, 3), (4);")
+            .parse()
+            .is_err());
+        assert!(Parser::new("SELECT * FROM (VALUES (1, 2), (3)) AS v;")
+            .parse()
+            .is_err());
+        assert!(Parser::new("INSERT INTO t VALUES (1, 2), (3, 4);")
+            .parse()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_trailing_comma() {
+        // A single trailing comma before the closing parenthesis parses as if it weren't there
+        for (lenient, strict) in [
+            (
+                "CREATE TABLE t (a INT PRIMARY KEY, b VARCHAR,);",
+                "CREATE TABLE t (a INT PRIMARY KEY, b VARCHAR);",
+            ),
+            (
+                "INSERT INTO t (a, b,) VALUES (1, 2,), (3, 4,);",
+                "INSERT INTO t (a, b) VALUES (1, 2), (3, 4);",
+            ),
+            (
+                "SELECT * FROM (VALUES (1,)) AS v (a,);",
+                "SELECT * FROM (VALUES (1)) AS v (a);",
+            ),
+        ] {
+            assert_eq!(
+                Parser::new(lenient).parse().unwrap(),
+                Parser::new(strict).parse().unwrap()
+            );
+        }
+
+        for sql in [
+            "CREATE TABLE t (a INT PRIMARY KEY,,);",
+            "CREATE TABLE t (,);",
+            "INSERT INTO t (a,,) VALUES (1);",
+            "INSERT INTO t (a b) VALUES (1, 2);",
+            "INSERT INTO t () VALUES (1);",
+            "INSERT INTO t VALUES (1,,);",
+            "INSERT INTO t VALUES (,);",
+            "INSERT INTO t VALUES (1), ;",
+        ] {
+            assert!(Parser::new(sql).parse().is_err(), "{sql}");
+        }
+    }
+
+    #[test]
+    fn test_select_from_values() {
+        let int = |i: i64| Expression::Consts(Consts::Integer(i));
+        let string = |s: &str| Expression::Consts(Consts::String(s.to_string()));
+        parse_eq!(
+            "SELECT * FROM (VALUES (1, 'a'), (2, 'b')) AS t(id, name);",
+            ast::Statement::Select {
+                select: vec![],
+                from: ast::FromItem::Values {
+                    rows: vec![vec![int(1), string("a")], vec![int(2), string("b")]],
+                    alias: "t".to_string(),
+                    columns: Some(vec!["id".to_string(), "name".to_string()]),
+                },
+                joins: vec![],
+                as_of: None,
+                group_by: vec![],
+                where_clause: None,
+                order_by: vec![],
+                limit: None,
+                offset: None,
+            }
+        );
+        assert!(Parser::new("SELECT * FROM (VALUES (1)) t;").parse().is_ok());
+        assert!(Parser::new("SELECT * FROM (VALUES (1));").parse().is_err());
+        assert!(Parser::new("SELECT * FROM (VALUES) AS t;").parse().is_err());
+    }
+
+    #[test]
+    fn test_select_union() -> Result<()> {
+        let select = |sql: &str| Parser::new(sql).parse();
+        parse_eq!(
+            "SELECT * FROM a UNION SELECT * FROM b UNION ALL SELECT * FROM c;",
+            ast::Statement::Union {
+                left: Box::new(ast::Statement::Union {
+                    left: Box::new(select("SELECT * FROM a;")?),
+                    right: Box::new(select("SELECT * FROM b;")?),
+                    all: false,
+                    order_by: vec![],
+                    limit: None,
+                    offset: None,
+                }),
+                right: Box::new(select("SELECT * FROM c;")?),
+                all: true,
+                order_by: vec![],
+                limit: None,
+                offset: None,
+            }
+        );
+        assert!(select("SELECT * FROM a UNION;").is_err());
+        assert!(select("SELECT * FROM a UNION DELETE FROM b;").is_err());
+
+        // The clauses after the last query belong to the union, the other queries can't have
+        // any
+        parse_eq!(
+            "SELECT * FROM a UNION SELECT * FROM b ORDER BY id DESC LIMIT 2 OFFSET 1;",
+            ast::Statement::Union {
+                left: Box::new(select("SELECT * FROM a;")?),
+                right: Box::new(select("SELECT * FROM b;")?),
+                all: false,
+                order_by: vec![("id".to_string(), OrderDirection::Desc)],
+                limit: Some(Expression::Consts(Consts::Integer(2))),
+                offset: Some(Expression::Consts(Consts::Integer(1))),
+            }
+        );
+        assert!(select("SELECT * FROM a ORDER BY id UNION SELECT * FROM b;").is_err());
+        assert!(select("SELECT * FROM a LIMIT 1 UNION SELECT * FROM b;").is_err());
+        assert!(
+            select("SELECT * FROM a UNION SELECT * FROM b OFFSET 1 UNION SELECT * FROM c;")
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_natural_join() {
+        parse_eq!(
+            "SELECT * FROM a NATURAL JOIN b;",
+            ast::Statement::Select {
+                select: vec![],
+                from: ast::FromItem::Table("a".to_string()),
+                joins: vec![ast::Join {
+                    table_name: "b".to_string(),
+                    kind: ast::JoinKind::Natural,
+                }],
+                as_of: None,
+                where_clause: None,
+                group_by: vec![],
+                order_by: vec![],
+                limit: None,
+                offset: None,
+            }
+        );
+        assert!(Parser::new("SELECT * FROM a NATURAL b;").parse().is_err());
+        assert!(Parser::new("SELECT * FROM a NATURAL JOIN;")
+            .parse()
+            .is_err());
+    }
+
+    #[test]
+    fn test_select_inner_join() {
+        let on = Predicate::Compare(
+            Expression::Field("a.id".to_string()),
+            Operator::Equal,
+            Expression::Field("b.a_id".to_string()),
+        );
+        parse_eq!(
+            "SELECT a.name, b.score FROM a JOIN b ON a.id = b.a_id ORDER BY b.score;",
+            ast::Statement::Select {
+                select: vec![
+                    (Expression::Field("a.name".to_string()), None),
+                    (Expression::Field("b.score".to_string()), None),
+                ],
+                from: ast::FromItem::Table("a".to_string()),
+                joins: vec![ast::Join {
+                    table_name: "b".to_string(),
+                    kind: ast::JoinKind::Inner(on.clone()),
+                }],
+                as_of: None,
+                where_clause: None,
+                group_by: vec![],
+                order_by: vec![("b.score".to_string(), OrderDirection::Asc)],
+                limit: None,
+                offset: None,
+            }
+        );
+        parse_eq!(
+            "SELECT * FROM a INNER JOIN b ON a.id = b.a_id;",
+            ast::Statement::Select {
+                select: vec![],
+                from: ast::FromItem::Table("a".to_string()),
+                joins: vec![ast::Join {
+                    table_name: "b".to_string(),
+                    kind: ast::JoinKind::Inner(on),
+                }],
+                as_of: None,
+                where_clause: None,
+                group_by: vec![],
+                order_by: vec![],
+                limit: None,
+                offset: None,
+            }
+        );
+        assert!(Parser::new("SELECT * FROM a JOIN b;").parse().is_err());
+        assert!(Parser::new("SELECT * FROM a INNER b ON a.id = b.id;")
+            .parse()
+            .is_err());
+        assert!(Parser::new("SELECT * FROM a JOIN b ON;").parse().is_err());
+        assert!(Parser::new("SELECT * FROM a JOIN b ON a. = b.id;")
+            .parse()
+            .is_err());
+    }
+
+    #[test]
+    fn test_select_qualified_wildcard() {
+        parse_eq!(
+            "SELECT t.* FROM t;",
+            ast::Statement::Select {
+                select: vec![(Expression::Wildcard("t".to_string()), None)],
+                from: ast::FromItem::Table("t".to_string()),
+                joins: vec![],
+                as_of: None,
+                group_by: vec![],
+                where_clause: None,
+                order_by: vec![],
+                limit: None,
+                offset: None,
+            }
+        );
+        assert!(Parser::new("SELECT t. FROM t;").parse().is_err());
+        assert!(Parser::new("SELECT t.* AS x FROM t;").parse().is_err());
+    }
+
+    #[test]
+    fn test_select_columns() {
+        parse_eq!(
+            "SELECT name, id AS key_id FROM t;",
+            ast::Statement::Select {
+                select: vec![
+                    (Expression::Field("name".to_string()), None),
+                    (
+                        Expression::Field("id".to_string()),
+                        Some("key_id".to_string())
+                    ),
+                ],
+                from: ast::FromItem::Table("t".to_string()),
+                joins: vec![],
+                as_of: None,
+                group_by: vec![],
+                where_clause: None,
+                order_by: vec![],
+                limit: None,
+                offset: None,
+            }
+        );
+        assert!(Parser::new("SELECT id, FROM t;").parse().is_err());
+        assert!(Parser::new("SELECT id name FROM t;").parse().is_err());
+    }
+
+    #[test]
+    fn test_select_group_by() {
+        parse_eq!(
+            "SELECT age, COUNT(*) FROM t WHERE id > 1 GROUP BY age, name ORDER BY age;",
+            ast::Statement::Select {
+                select: vec![
+                    (Expression::Field("age".to_string()), None),
+                    (
+                        Expression::Function("COUNT".to_string(), "*".to_string()),
+                        None
+                    ),
+                ],
+                from: ast::FromItem::Table("t".to_string()),
+                joins: vec![],
+                as_of: None,
+                where_clause: Some(Predicate::Compare(
+                    Expression::Field("id".to_string()),
+                    Operator::GreaterThan,
+                    Expression::Consts(Consts::Integer(1))
+                )),
+                group_by: vec!["age".to_string(), "name".to_string()],
+                order_by: vec![("age".to_string(), OrderDirection::Asc)],
+                limit: None,
+                offset: None,
+            }
+        );
+
+        assert!(Parser::new("SELECT age FROM t GROUP age;").parse().is_err());
+        assert!(Parser::new("SELECT age FROM t GROUP BY;").parse().is_err());
+        assert!(Parser::new("SELECT age FROM t GROUP BY age,;")
+            .parse()
+            .is_err());
+    }
+
+    #[test]
+    fn test_select_aggregate() {
+        parse_eq!(
+            "SELECT count(*), COUNT(*) AS total FROM t WHERE id > 1;",
+            ast::Statement::Select {
+                select: vec![
+                    (
+                        Expression::Function("COUNT".to_string(), "*".to_string()),
+                        None
+                    ),
+                    (
+                        Expression::Function("COUNT".to_string(), "*".to_string()),
+                        Some("total".to_string())
+                    ),
+                ],
+                from: ast::FromItem::Table("t".to_string()),
+                joins: vec![],
+                as_of: None,
+                group_by: vec![],
+                where_clause: Some(Predicate::Compare(
+                    Expression::Field("id".to_string()),
+                    Operator::GreaterThan,
+                    Expression::Consts(Consts::Integer(1))
+                )),
+                order_by: vec![],
+                limit: None,
+                offset: None,
+            }
+        );
+
+        assert!(Parser::new("SELECT COUNT(*) AS FROM t;").parse().is_err());
+        assert!(Parser::new("SELECT COUNT( FROM t;").parse().is_err());
+        assert!(Parser::new("SELECT FROM t;").parse().is_err());
+    }
+
     #[test]
     fn test_insert_with_columns() {
-        let mut vals = Vec::new();
         // 这里只关注行数和列数，也可以把具体 Expression 展开
-        vals.push(vec![
-            Expression::Consts(Consts::Integer(1)),
-            Expression::Consts(Consts::String("Alice".into())),
-        ]);
-        vals.push(vec![
-            Expression::Consts(Consts::Integer(2)),
-            Expression::Consts(Consts::String("Bob".into())),
-        ]);
+        let vals = vec![
+            vec![
+                Expression::Consts(Consts::Integer(1)),
+                Expression::Consts(Consts::String("Alice".into())),
+            ],
+            vec![
+                Expression::Consts(Consts::Integer(2)),
+                Expression::Consts(Consts::String("Bob".into())),
+            ],
+        ];
         parse_eq!(
             "INSERT INTO my_table (id, name) VALUES (1, 'Alice'), (2, 'Bob');",
             Statement::Insert {
                 table_name: "my_table".to_string(),
                 columns: Some(vec!["id".to_string(), "name".to_string()]),
                 values: vals,
+                if_not_exists: false,
             }
         );
     }
@@ -526,8 +2105,25 @@ mod tests {
                     Expression::Consts(Consts::Integer(1)),
                     Expression::Consts(Consts::String("Alice".into())),
                 ]],
+                if_not_exists: false,
+            }
+        );
+
+        parse_eq!(
+            "INSERT INTO my_table VALUES (1, 'Alice') IF NOT EXISTS;",
+            Statement::Insert {
+                table_name: "my_table".to_string(),
+                columns: None,
+                values: vec![vec![
+                    Expression::Consts(Consts::Integer(1)),
+                    Expression::Consts(Consts::String("Alice".into())),
+                ]],
+                if_not_exists: true,
             }
         );
+        assert!(Parser::new("INSERT INTO my_table VALUES (1) IF EXISTS;")
+            .parse()
+            .is_err());
     }
 
     #[test]
@@ -535,6 +2131,137 @@ mod tests {
         assert!(Parser::new("SELECT * FROM my_table").parse().is_err());
     }
 
+    #[test]
+    fn test_parse_all() -> Result<()> {
+        let stmts = Parser::new(
+            "CREATE TABLE t (a INT PRIMARY KEY);
+             INSERT INTO t VALUES (1); -- a comment
+             SELECT * FROM t;",
+        )
+        .parse_all()?;
+        assert_eq!(stmts.len(), 3);
+        assert!(matches!(stmts[0], ast::Statement::CreateTable { .. }));
+        assert!(matches!(stmts[1], ast::Statement::Insert { .. }));
+        assert!(matches!(stmts[2], ast::Statement::Select { .. }));
+
+        assert!(Parser::new("  ").parse_all()?.is_empty());
+        // Every statement needs its semicolon, the last one included
+        assert!(Parser::new("SELECT * FROM t; SELECT * FROM t")
+            .parse_all()
+            .is_err());
+        assert!(Parser::new("SELECT * FROM t SELECT * FROM t;")
+            .parse_all()
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_peek_statement_kind() -> Result<()> {
+        use ast::StatementKind;
+        for (sql, kind) in [
+            ("SELECT * FROM t;", StatementKind::Select),
+            (
+                "SELECT a FROM t UNION SELECT a FROM u;",
+                StatementKind::Select,
+            ),
+            ("INSERT INTO t VALUES (1);", StatementKind::Insert),
+            ("UPDATE t SET a = 1;", StatementKind::Update),
+            ("DELETE FROM t;", StatementKind::Delete),
+            ("CREATE TABLE t (a INT PRIMARY KEY);", StatementKind::Ddl),
+            ("DROP TABLE t;", StatementKind::Ddl),
+            ("ALTER TABLE t ADD COLUMN b INT;", StatementKind::Ddl),
+            ("SHOW TABLES;", StatementKind::Show),
+            ("DESCRIBE t;", StatementKind::Show),
+            ("BEGIN;", StatementKind::Transaction),
+            ("commit;", StatementKind::Transaction),
+            ("ROLLBACK;", StatementKind::Transaction),
+            (
+                "  -- leading comment\n select * from t;",
+                StatementKind::Select,
+            ),
+        ] {
+            let mut parser = Parser::new(sql);
+            assert_eq!(parser.peek_statement_kind()?, kind, "{sql}");
+            // Nothing was consumed
+            parser.parse()?;
+        }
+        assert!(StatementKind::Show.is_read_only());
+        assert!(!StatementKind::Insert.is_read_only());
+
+        assert!(Parser::new("").peek_statement_kind().is_err());
+        assert!(Parser::new("FROM t;").peek_statement_kind().is_err());
+        // Only the leading keyword is looked at
+        assert_eq!(
+            Parser::new("SELECT FROM;").peek_statement_kind()?,
+            StatementKind::Select
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_order_by_bad_direction() {
+        for (sql, token) in [
+            ("SELECT * FROM t ORDER BY a FOO;", "FOO"),
+            ("SELECT * FROM t ORDER BY a DESC BAR;", "BAR"),
+            ("SELECT * FROM t ORDER BY a, b ASC WHERE;", "WHERE"),
+        ] {
+            match Parser::new(sql).parse() {
+                Err(Error::ParserError(msg)) => assert_eq!(
+                    msg,
+                    format!(
+                        "[Parser] Expected ASC, DESC, comma, or end of order clause, got {token}"
+                    ),
+                    "{sql}"
+                ),
+                r => panic!("Expected a parser error for {sql}, got {r:?}"),
+            }
+        }
+        for sql in [
+            "SELECT * FROM t ORDER BY a DESC LIMIT 1 OFFSET 2;",
+            "SELECT * FROM t ORDER BY a OFFSET 2 ROWS;",
+            "SELECT * FROM t ORDER BY a FETCH FIRST 1 ROWS ONLY;",
+            "SELECT * FROM t UNION SELECT * FROM u ORDER BY a;",
+        ] {
+            assert!(Parser::new(sql).parse().is_ok(), "{sql}");
+        }
+    }
+
+    #[test]
+    fn test_as_of() -> Result<()> {
+        let as_of =
+            |sql: &str| -> Result<Option<u64>> { Ok(Parser::new(sql).parse()?.take_as_of()) };
+        assert_eq!(
+            as_of("SELECT * FROM t AS OF TIMESTAMP 1500 WHERE a = 1;")?,
+            Some(1500)
+        );
+        assert_eq!(
+            as_of("SELECT * FROM t AS OF TIMESTAMP '1970-01-01 00:00:00';")?,
+            Some(0)
+        );
+        assert_eq!(
+            as_of("SELECT * FROM a NATURAL JOIN b AS OF TIMESTAMP '2000-03-01T12:34:56.789Z';")?,
+            Some(951914096789)
+        );
+        assert_eq!(
+            as_of("SELECT * FROM t AS OF TIMESTAMP '2024-02-29';")?,
+            Some(1709164800000)
+        );
+        assert_eq!(as_of("SELECT * FROM t;")?, None);
+
+        for timestamp in [
+            "'2023-02-29'",
+            "'2024-13-01 00:00:00'",
+            "'2024-01-01 24:00:00'",
+            "'2024-1-01'",
+            "'yesterday'",
+            "-1",
+        ] {
+            let sql = format!("SELECT * FROM t AS OF TIMESTAMP {timestamp};");
+            assert!(Parser::new(&sql).parse().is_err(), "{sql}");
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_unexpected_token_error() {
         assert!(Parser::new("RANDOM TOKEN;").parse().is_err());
@@ -553,9 +2280,63 @@ mod tests {
             Statement::Update {
                 table_name: "my_table".to_string(),
                 columns: cols,
-                where_clause: Some(("id".to_string(), Expression::Consts(Consts::Integer(1)))),
+                where_clause: Some(Predicate::Compare(
+                    Expression::Field("id".to_string()),
+                    Operator::Equal,
+                    Expression::Consts(Consts::Integer(1))
+                )),
+                limit: None,
+            }
+        );
+
+        let mut cols = BTreeMap::new();
+        cols.insert("age".to_string(), Expression::Default);
+        parse_eq!(
+            "UPDATE my_table SET age = DEFAULT;",
+            Statement::Update {
+                table_name: "my_table".to_string(),
+                columns: cols,
+                where_clause: None,
+                limit: None,
             }
         );
+        // DEFAULT stands for the whole value, it isn't an operand
+        assert!(Parser::new("UPDATE t SET a = DEFAULT + 1;")
+            .parse()
+            .is_err());
+        assert!(Parser::new("UPDATE t SET a = 1 + DEFAULT;")
+            .parse()
+            .is_err());
+    }
+
+    #[test]
+    fn test_mutation_limit() {
+        parse_eq!(
+            "DELETE FROM t WHERE id > 1 LIMIT 2;",
+            Statement::Delete {
+                table_name: "t".to_string(),
+                where_clause: Some(Predicate::Compare(
+                    Expression::Field("id".to_string()),
+                    Operator::GreaterThan,
+                    Expression::Consts(Consts::Integer(1))
+                )),
+                limit: Some(Expression::Consts(Consts::Integer(2))),
+            }
+        );
+        parse_eq!(
+            "UPDATE t SET a = 1 LIMIT 3;",
+            Statement::Update {
+                table_name: "t".to_string(),
+                columns: BTreeMap::from([(
+                    "a".to_string(),
+                    Expression::Consts(Consts::Integer(1))
+                )]),
+                where_clause: None,
+                limit: Some(Expression::Consts(Consts::Integer(3))),
+            }
+        );
+        assert!(Parser::new("DELETE FROM t LIMIT;").parse().is_err());
+        assert!(Parser::new("DELETE FROM t LIMIT 1 - 2;").parse().is_err());
     }
 
     #[test]
@@ -589,4 +2370,148 @@ mod tests {
         let result = parser.parse();
         assert!(result.is_err(), "Should fail with incomplete WHERE clause");
     }
+
+    // Parse the sql, render the AST back to sql, parse it again and expect the same AST
+    macro_rules! round_trip {
+        ($sql:expr) => {
+            let stmt = Parser::new($sql).parse().unwrap();
+            let rendered = format!("{stmt};");
+            let reparsed = Parser::new(&rendered)
+                .parse()
+                .unwrap_or_else(|e| panic!("failed to reparse {rendered}: {e}"));
+            assert_eq!(stmt, reparsed, "round trip of {} via {}", $sql, rendered);
+        };
+    }
+
+    #[test]
+    fn test_round_trip_create_table() {
+        round_trip!("create table t (id int primary key, a integer not null, b float null);");
+        round_trip!(
+            "CREATE TABLE tbl (
+                id INT PRIMARY KEY,
+                b BOOL DEFAULT true,
+                c VARCHAR DEFAULT 'abc',
+                d TEXT NOT NULL DEFAULT 'x',
+                e DOUBLE DEFAULT 1.0,
+                f FLOAT DEFAULT 3.25,
+                g STRING NULL DEFAULT NULL,
+                h BOOLEAN DEFAULT false
+            );"
+        );
+    }
+
+    #[test]
+    fn test_round_trip_drop_table() {
+        round_trip!("drop table t;");
+        round_trip!("DROP TABLE IF EXISTS t;");
+        round_trip!("CREATE TABLE IF NOT EXISTS t (id INT PRIMARY KEY);");
+        round_trip!("CREATE TEMP TABLE t (id INT PRIMARY KEY);");
+        round_trip!("CREATE TABLE t (id INT PRIMARY KEY, small SMALLINT DEFAULT 1);");
+        round_trip!("CREATE TABLE t (id INT PRIMARY KEY, email TEXT NOT NULL INDEX);");
+        round_trip!("CREATE TABLE IF NOT EXISTS s AS SELECT COUNT(*) AS n FROM t WHERE a > 1;");
+    }
+
+    #[test]
+    fn test_round_trip_insert() {
+        round_trip!("insert into t values (1, 'a', true, 2.5, null);");
+        round_trip!("INSERT INTO t (id, name) VALUES (1, 'Alice'), (2, 'Bob');");
+        round_trip!("INSERT INTO t VALUES (1 + 2 * (3 - 4), 1 - (2 - 3));");
+        round_trip!("INSERT INTO t VALUES ('O''Brien', '''', '');");
+        round_trip!("INSERT INTO t VALUES (1, 'a') IF NOT EXISTS;");
+    }
+
+    #[test]
+    fn test_round_trip_select() {
+        round_trip!("SELECT a * 2 AS d, (a + 1) / b FROM t WHERE a - b >= 2 * c;");
+        round_trip!("select * from t;");
+        round_trip!("SELECT * FROM t WHERE name = 'Alice';");
+        round_trip!("SELECT * FROM t WHERE f = 10.0 ORDER BY a, b asc, c desc;");
+        round_trip!("SELECT * FROM t ORDER BY a DESC LIMIT 10 OFFSET 5;");
+        round_trip!("SELECT * FROM t WHERE ok = false OFFSET 5;");
+        round_trip!("SELECT * FROM t WHERE id > 1 ORDER BY id;");
+        round_trip!("SELECT COUNT(*) AS n FROM t WHERE id > 1;");
+        round_trip!("SELECT t.* FROM t;");
+        round_trip!("SELECT a, b, MAX(c) AS m FROM t GROUP BY a, b ORDER BY m DESC;");
+        round_trip!("SELECT name, id AS key_id FROM t ORDER BY id DESC;");
+        round_trip!("SELECT * FROM t WHERE NOT (a = 1 AND b = 2) OR NOT c AND d;");
+        round_trip!("SELECT * FROM a NATURAL JOIN b AS OF TIMESTAMP 1500 WHERE a.x = 1;");
+        round_trip!(
+            "SELECT * FROM t WHERE (a = 1 OR b > 2) AND (c < 3 AND d = 'x') OR e BETWEEN 1 AND 2;"
+        );
+        round_trip!("UPDATE t SET a = 1 WHERE a = 1 OR (b = 2 OR c = 3);");
+        round_trip!("SELECT * FROM a UNION ALL SELECT COUNT(*) FROM b ORDER BY id LIMIT 1;");
+        round_trip!("SELECT * FROM (VALUES (1, 'a'), (2, NULL)) AS t (id, name) ORDER BY id;");
+        round_trip!("SELECT COUNT(*) FROM (VALUES (1 + 1)) AS v;");
+        round_trip!("SELECT * FROM a NATURAL JOIN b NATURAL JOIN c ORDER BY id;");
+        round_trip!("SELECT * FROM t WHERE id <= 1.5;");
+        round_trip!("SELECT * FROM t WHERE id BETWEEN 1 AND 10 LIMIT 2;");
+    }
+
+    #[test]
+    fn test_round_trip_update() {
+        round_trip!("update t set a = 1;");
+        round_trip!("UPDATE t SET name = 'Bob', age = 30, score = 1.5 WHERE id = 1;");
+        round_trip!("UPDATE t SET a = 1 WHERE id > 1 LIMIT 2;");
+        round_trip!("UPDATE t SET a = a + 1, b = (a - 1) * 2 / c WHERE id = 1;");
+        round_trip!("UPDATE t SET a = DEFAULT, b = 2 WHERE id = 1;");
+    }
+
+    #[test]
+    fn test_round_trip_delete() {
+        round_trip!("delete from t;");
+        round_trip!("DELETE FROM t WHERE id = 3;");
+        round_trip!("DELETE FROM t WHERE id >= 3;");
+        round_trip!("DELETE FROM t LIMIT 10;");
+    }
+
+    #[test]
+    fn test_round_trip_negative() -> Result<()> {
+        round_trip!("INSERT INTO t VALUES (-5, -1.5, -2.5e-8, -9223372036854775808);");
+        round_trip!("SELECT * FROM t WHERE a > -1 AND b BETWEEN -2.5 AND -(c + 1);");
+        round_trip!("UPDATE t SET a = 1 - -a, b = -1e20 WHERE id = -3;");
+        round_trip!("CREATE TABLE t (id INT PRIMARY KEY, v FLOAT DEFAULT -0.5);");
+
+        // Constants built without SQL text render as literals the parser reads back
+        for value in [
+            Consts::Integer(i64::MIN),
+            Consts::Integer(-7),
+            Consts::Float(-1.5),
+            Consts::Float(-0.0),
+            Consts::Float(1e20),
+            Consts::Float(-1e-7),
+        ] {
+            let stmt = ast::Statement::Insert {
+                table_name: "t".to_string(),
+                columns: None,
+                values: vec![vec![Expression::Consts(value)]],
+                if_not_exists: false,
+            };
+            assert_eq!(Parser::new(&format!("{stmt};")).parse()?, stmt, "{stmt}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip_transaction() {
+        round_trip!("begin;");
+        round_trip!("COMMIT;");
+        round_trip!("Rollback;");
+    }
+
+    #[test]
+    fn test_display_statement() -> Result<()> {
+        let stmt = Parser::new("select * from t where b = 'x' order by a desc limit 3;").parse()?;
+        assert_eq!(
+            stmt.to_string(),
+            "SELECT * FROM t WHERE b = 'x' ORDER BY a DESC LIMIT 3"
+        );
+
+        let stmt =
+            Parser::new("create table t (id int primary key, v float default 1.0);").parse()?;
+        assert_eq!(
+            stmt.to_string(),
+            "CREATE TABLE t (id INTEGER PRIMARY KEY, v FLOAT DEFAULT 1.0)"
+        );
+        Ok(())
+    }
 }
@@ -1,13 +1,21 @@
 use super::{
     engine::Transaction,
     executor::{Executor, ResultSet},
-    parser::ast::OrderDirection,
+    parser::ast::{JoinKind, OrderDirection},
 };
 use crate::error::Result;
-use crate::sql::{parser::ast, parser::ast::Expression, plan::planner::Planner, schema::Table};
+use crate::sql::{
+    parser::ast,
+    parser::ast::{Expression, Predicate},
+    plan::planner::Planner,
+    schema::{Column, Table},
+};
 use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
 
+mod optimizer;
 mod planner;
+mod validate;
 
 /// Execution Node
 #[derive(Debug, PartialEq)]
@@ -15,19 +23,76 @@ pub enum Node {
     // Create Table
     CreateTable {
         schema: Table,
+        if_not_exists: bool,
+        temporary: bool,
+    },
+
+    // Create a table from the result of the source
+    CreateTableAs {
+        table_name: String,
+        if_not_exists: bool,
+        temporary: bool,
+        source: Box<Node>,
+    },
+
+    // Drop Table
+    DropTable {
+        table_name: String,
+        if_exists: bool,
+    },
+
+    // List the tables
+    ShowTables,
+
+    // Describe the columns of a table
+    Describe {
+        table_name: String,
+    },
+
+    // Add a column to a table
+    AddColumn {
+        table_name: String,
+        column: Column,
     },
 
-    // Insert Data
+    // Insert Data, skipping the rows whose primary key exists with `if_not_exists`
     Insert {
         table_name: String,
         columns: Vec<String>,
         values: Vec<Vec<Expression>>,
+        if_not_exists: bool,
     },
 
     // Scan Node
     Scan {
         table_name: String,
-        filter: Option<(String, Expression)>,
+        filter: Option<Predicate>,
+    },
+
+    // Scan that reads none of the rows of the table, only its columns, for sources whose
+    // rows would all be thrown away
+    EmptyScan {
+        table_name: String,
+    },
+
+    // Inline rows of constant expressions
+    ValuesScan {
+        columns: Vec<String>,
+        rows: Vec<Vec<Expression>>,
+    },
+
+    // Union Node, the rows of the left source followed by the ones of the right source
+    Union {
+        left: Box<Node>,
+        right: Box<Node>,
+        all: bool,
+    },
+
+    // Join Node, combines the rows of both sources
+    Join {
+        left: Box<Node>,
+        right: Box<Node>,
+        kind: JoinKind,
     },
 
     // Update Node
@@ -43,6 +108,20 @@ pub enum Node {
         source: Box<Node>,
     },
 
+    // Aggregate Node, folds the source rows into one row per group, or into a single row
+    // without GROUP BY
+    Aggregate {
+        source: Box<Node>,
+        exprs: Vec<(Expression, Option<String>)>,
+        group_by: Vec<String>,
+    },
+
+    // Projection Node, picks and renames the columns of the source rows
+    Projection {
+        source: Box<Node>,
+        exprs: Vec<(Expression, Option<String>)>,
+    },
+
     // Order Node
     Order {
         order_by: Vec<(String, OrderDirection)>,
@@ -69,8 +148,190 @@ impl Plan {
         Planner::new().build(stmt)
     }
 
+    /// The plan as it runs, rewritten by the optimizer. The sorts it drops are checked
+    /// against the schema seen by the transaction first, so a sort on a missing column
+    /// fails the query whether it is dropped or not.
+    pub fn optimize<T: Transaction>(self, txn: &mut T) -> Result<Self> {
+        let mut check_sort = |order_by: &[_], source: &Node| {
+            validate::validate_order(order_by, source, txn).map(drop)
+        };
+        optimizer::optimize(self.0, &mut check_sort).map(Plan)
+    }
+
+    /// The plan as it would run, for describing it without reading the schema. Nothing is
+    /// checked, a sort on a missing column may be dropped.
+    pub fn explain(self) -> Result<Self> {
+        optimizer::optimize(self.0, &mut |_, _| Ok(())).map(Plan)
+    }
+
     pub fn execute<T: Transaction + 'static>(self, txn: &mut T) -> Result<ResultSet> {
-        <dyn Executor<T>>::build(self.0).execute(txn)
+        self.execute_with_row_limit(txn, None)
+    }
+
+    /// Execute the plan, erroring when the result would hold more than `row_limit` rows
+    pub fn execute_with_row_limit<T: Transaction + 'static>(
+        self,
+        txn: &mut T,
+        row_limit: Option<usize>,
+    ) -> Result<ResultSet> {
+        let plan = self.optimize(txn)?;
+        <dyn Executor<T>>::build_with_row_limit(plan.0, row_limit).execute(txn)
+    }
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+// Select expressions with their alias, as written in the select list
+fn format_exprs(exprs: &[(Expression, Option<String>)]) -> String {
+    exprs
+        .iter()
+        .map(|(expr, alias)| match alias {
+            Some(alias) => format!("{expr} AS {alias}"),
+            None => expr.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Node {
+    /// The table the node reads, when it's a scan of one
+    pub(crate) fn table_name(&self) -> Option<String> {
+        match self {
+            Node::Scan { table_name, .. } | Node::EmptyScan { table_name } => {
+                Some(table_name.clone())
+            }
+            _ => None,
+        }
+    }
+
+    // Write the node on one line, then its sources one level deeper
+    fn format(&self, f: &mut Formatter<'_>, depth: usize) -> std::fmt::Result {
+        if depth > 0 {
+            write!(f, "\n{}-> ", "   ".repeat(depth - 1))?;
+        }
+        let sources: Vec<&Node> = match self {
+            Node::CreateTable { schema, .. } => {
+                write!(f, "Create Table: {}", schema.name)?;
+                vec![]
+            }
+            Node::CreateTableAs {
+                table_name, source, ..
+            } => {
+                write!(f, "Create Table As: {table_name}")?;
+                vec![source]
+            }
+            Node::DropTable { table_name, .. } => {
+                write!(f, "Drop Table: {table_name}")?;
+                vec![]
+            }
+            Node::ShowTables => {
+                write!(f, "Show Tables")?;
+                vec![]
+            }
+            Node::Describe { table_name } => {
+                write!(f, "Describe: {table_name}")?;
+                vec![]
+            }
+            Node::AddColumn { table_name, column } => {
+                write!(f, "Add Column: {table_name}.{}", column.name)?;
+                vec![]
+            }
+            Node::Insert {
+                table_name, values, ..
+            } => {
+                write!(f, "Insert: {table_name} ({} rows)", values.len())?;
+                vec![]
+            }
+            Node::Scan { table_name, filter } => {
+                write!(f, "Scan: {table_name}")?;
+                if let Some(filter) = filter {
+                    write!(f, " (filter: {filter})")?;
+                }
+                vec![]
+            }
+            Node::EmptyScan { table_name } => {
+                write!(f, "Empty Scan: {table_name}")?;
+                vec![]
+            }
+            Node::ValuesScan { columns, rows } => {
+                write!(f, "Values: {} ({} rows)", columns.join(", "), rows.len())?;
+                vec![]
+            }
+            Node::Union { left, right, all } => {
+                write!(f, "Union{}", if *all { " All" } else { "" })?;
+                vec![left, right]
+            }
+            Node::Join { left, right, kind } => {
+                match kind {
+                    JoinKind::Natural => write!(f, "Natural Join")?,
+                    JoinKind::Inner(on) => write!(f, "Inner Join (on: {on})")?,
+                }
+                vec![left, right]
+            }
+            Node::Update {
+                table_name,
+                columns,
+                source,
+            } => {
+                let sets = columns
+                    .iter()
+                    .map(|(col, expr)| format!("{col} = {expr}"))
+                    .collect::<Vec<_>>();
+                write!(f, "Update: {table_name} ({})", sets.join(", "))?;
+                vec![source]
+            }
+            Node::Delete { table_name, source } => {
+                write!(f, "Delete: {table_name}")?;
+                vec![source]
+            }
+            Node::Aggregate {
+                source,
+                exprs,
+                group_by,
+            } => {
+                write!(f, "Aggregate: {}", format_exprs(exprs))?;
+                if !group_by.is_empty() {
+                    write!(f, " (group by: {})", group_by.join(", "))?;
+                }
+                vec![source]
+            }
+            Node::Projection { source, exprs } => {
+                write!(f, "Projection: {}", format_exprs(exprs))?;
+                vec![source]
+            }
+            Node::Order { order_by, source } => {
+                let orders = order_by
+                    .iter()
+                    .map(|(col, direction)| format!("{col} {direction}"))
+                    .collect::<Vec<_>>();
+                write!(f, "Order: {}", orders.join(", "))?;
+                vec![source]
+            }
+            Node::Limit { source, limit } => {
+                write!(f, "Limit: {limit}")?;
+                vec![source]
+            }
+            Node::Offset { source, offset } => {
+                write!(f, "Offset: {offset}")?;
+                vec![source]
+            }
+        };
+
+        for source in sources {
+            source.format(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// The plan tree, one node per line with its sources indented below it
+impl Display for Node {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.format(f, 0)
     }
 }
 
@@ -105,6 +366,8 @@ mod tests {
                             nullable: false,
                             default: None,
                             primary_key: true,
+                            width: None,
+                            index: false,
                         },
                         Column {
                             name: "a".to_string(),
@@ -112,6 +375,8 @@ mod tests {
                             nullable: true, // If NOT NULL is not specified, it defaults to allowing null
                             default: Some(Value::Integer(100)),
                             primary_key: false,
+                            width: None,
+                            index: false,
                         },
                         Column {
                             name: "b".to_string(),
@@ -119,6 +384,8 @@ mod tests {
                             nullable: false,
                             default: None,
                             primary_key: false,
+                            width: None,
+                            index: false,
                         },
                         Column {
                             name: "c".to_string(),
@@ -126,6 +393,8 @@ mod tests {
                             nullable: true,
                             default: Some(Value::Null),
                             primary_key: false,
+                            width: None,
+                            index: false,
                         },
                         Column {
                             name: "d".to_string(),
@@ -133,9 +402,13 @@ mod tests {
                             nullable: true,
                             default: Some(Value::Boolean(true)),
                             primary_key: false,
+                            width: None,
+                            index: false,
                         },
                     ]
-                }
+                },
+                if_not_exists: false,
+                temporary: false,
             })
         );
 
@@ -160,6 +433,7 @@ mod tests {
                     Expression::Consts(ast::Consts::String("a".to_string())),
                     Expression::Consts(ast::Consts::Boolean(true)),
                 ]],
+                if_not_exists: false,
             })
         );
 
@@ -184,6 +458,7 @@ mod tests {
                         Expression::Consts(ast::Consts::Boolean(false)),
                     ],
                 ],
+                if_not_exists: false,
             })
         );
         Ok(())
@@ -203,4 +478,188 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_plan_select_qualified_wildcard() -> Result<()> {
+        let plan = Plan::build(Parser::new("SELECT tbl1.* FROM tbl1 WHERE id > 1;").parse()?)?;
+        let expected = Plan::build(Parser::new("SELECT * FROM tbl1 WHERE id > 1;").parse()?)?;
+        assert_eq!(plan, expected);
+
+        // The qualifier must name the FROM table
+        assert!(Plan::build(Parser::new("SELECT t2.* FROM tbl1;").parse()?).is_err());
+        assert!(Plan::build(Parser::new("SELECT tbl1.*, COUNT(*) FROM tbl1;").parse()?).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_projection() -> Result<()> {
+        // Rows are sorted before the projection, then paged
+        let plan =
+            Plan::build(Parser::new("SELECT name AS n FROM t ORDER BY id LIMIT 2;").parse()?)?;
+        assert_eq!(
+            plan,
+            Plan(Node::Limit {
+                source: Box::new(Node::Projection {
+                    source: Box::new(Node::Order {
+                        order_by: vec![("id".to_string(), OrderDirection::Asc)],
+                        source: Box::new(Node::Scan {
+                            table_name: "t".to_string(),
+                            filter: None,
+                        }),
+                    }),
+                    exprs: vec![(Expression::Field("name".to_string()), Some("n".to_string()))],
+                }),
+                limit: 2,
+            })
+        );
+
+        assert!(Plan::build(Parser::new("SELECT id, COUNT(*) FROM t;").parse()?).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_group_by() -> Result<()> {
+        let plan = Plan::build(
+            Parser::new("SELECT a, COUNT(*) FROM t GROUP BY a, b ORDER BY a;").parse()?,
+        )?;
+        assert_eq!(
+            plan,
+            Plan(Node::Order {
+                order_by: vec![("a".to_string(), OrderDirection::Asc)],
+                source: Box::new(Node::Aggregate {
+                    source: Box::new(Node::Scan {
+                        table_name: "t".to_string(),
+                        filter: None,
+                    }),
+                    exprs: vec![
+                        (Expression::Field("a".to_string()), None),
+                        (
+                            Expression::Function("COUNT".to_string(), "*".to_string()),
+                            None
+                        ),
+                    ],
+                    group_by: vec!["a".to_string(), "b".to_string()],
+                }),
+            })
+        );
+
+        // Only the grouped columns can be selected outside of aggregates
+        assert!(
+            Plan::build(Parser::new("SELECT c, COUNT(*) FROM t GROUP BY a;").parse()?).is_err()
+        );
+        assert!(Plan::build(Parser::new("SELECT * FROM t GROUP BY a;").parse()?).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_display() -> Result<()> {
+        let plan = Plan::build(
+            Parser::new(
+                "SELECT name AS n FROM t WHERE id > 1 ORDER BY score DESC, id LIMIT 10 OFFSET 5;",
+            )
+            .parse()?,
+        )?;
+        assert_eq!(
+            plan.to_string(),
+            [
+                "Limit: 10",
+                "-> Offset: 5",
+                "   -> Projection: name AS n",
+                "      -> Order: score DESC, id ASC",
+                "         -> Scan: t (filter: id > 1)",
+            ]
+            .join("\n")
+        );
+
+        let plan = Plan::build(
+            Parser::new("SELECT * FROM a NATURAL JOIN b UNION ALL SELECT * FROM c;").parse()?,
+        )?;
+        assert_eq!(
+            plan.to_string(),
+            [
+                "Union All",
+                "-> Natural Join",
+                "   -> Scan: a",
+                "   -> Scan: b",
+                "-> Scan: c",
+            ]
+            .join("\n")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_limit_offset() -> Result<()> {
+        let plan = Plan::build(Parser::new("SELECT * FROM t LIMIT 10 OFFSET 5;").parse()?)?;
+        assert_eq!(
+            plan,
+            Plan(Node::Limit {
+                source: Box::new(Node::Offset {
+                    source: Box::new(Node::Scan {
+                        table_name: "t".to_string(),
+                        filter: None,
+                    }),
+                    offset: 5,
+                }),
+                limit: 10,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_natural_join() -> Result<()> {
+        let plan =
+            Plan::build(Parser::new("SELECT * FROM a NATURAL JOIN b NATURAL JOIN c;").parse()?)?;
+        let scan = |name: &str| {
+            Box::new(Node::Scan {
+                table_name: name.to_string(),
+                filter: None,
+            })
+        };
+        assert_eq!(
+            plan,
+            Plan(Node::Join {
+                left: Box::new(Node::Join {
+                    left: scan("a"),
+                    right: scan("b"),
+                    kind: JoinKind::Natural,
+                }),
+                right: scan("c"),
+                kind: JoinKind::Natural,
+            })
+        );
+
+        assert!(
+            Plan::build(Parser::new("SELECT * FROM a NATURAL JOIN b WHERE id = 1;").parse()?)
+                .is_err()
+        );
+        assert!(Plan::build(Parser::new("SELECT a.* FROM a NATURAL JOIN b;").parse()?).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_inner_join() -> Result<()> {
+        let plan = Plan::build(
+            Parser::new("SELECT a.name FROM a JOIN b ON a.id = b.a_id ORDER BY b.score;")
+                .parse()?,
+        )?;
+        assert_eq!(
+            plan.to_string(),
+            [
+                "Projection: a.name",
+                "-> Order: b.score ASC",
+                "   -> Inner Join (on: a.id = b.a_id)",
+                "      -> Scan: a",
+                "      -> Scan: b",
+            ]
+            .join("\n")
+        );
+
+        assert!(Plan::build(
+            Parser::new("SELECT * FROM a JOIN b ON a.id = b.id NATURAL JOIN c;").parse()?
+        )
+        .is_err());
+        Ok(())
+    }
 }
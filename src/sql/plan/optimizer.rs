@@ -0,0 +1,190 @@
+use super::Node;
+use crate::{error::Result, sql::parser::ast::OrderDirection};
+
+// Checks the columns of a sort and its source before the sort is dropped, as running it
+// would have
+type CheckSort<'a> = dyn FnMut(&[(String, OrderDirection)], &Node) -> Result<()> + 'a;
+
+/// Rewrite the plan tree to skip work that can't change the result: nothing under a
+/// `LIMIT 0` is sorted or read, and a source of at most one row isn't sorted. Each sort is
+/// passed to `check_sort` before it is dropped.
+pub(super) fn optimize(node: Node, check_sort: &mut CheckSort) -> Result<Node> {
+    Ok(match node {
+        Node::Limit { source, limit: 0 } => Node::Limit {
+            source: Box::new(discard_rows(*source, check_sort)?),
+            limit: 0,
+        },
+        Node::Order { order_by, source } => {
+            let source = optimize(*source, check_sort)?;
+            if at_most_one_row(&source) {
+                check_sort(&order_by, &source)?;
+                source
+            } else {
+                Node::Order {
+                    order_by,
+                    source: Box::new(source),
+                }
+            }
+        }
+        node => node.map_sources(&mut |source| optimize(source, check_sort))?,
+    })
+}
+
+// The rows of the node are all thrown away: the sorts are dropped and the tables are not
+// read, only their columns are still needed for the result
+fn discard_rows(node: Node, check_sort: &mut CheckSort) -> Result<Node> {
+    Ok(match node {
+        Node::Order { order_by, source } => {
+            check_sort(&order_by, &source)?;
+            discard_rows(*source, check_sort)?
+        }
+        Node::Scan { table_name, .. } => Node::EmptyScan { table_name },
+        node => node.map_sources(&mut |source| discard_rows(source, check_sort))?,
+    })
+}
+
+fn at_most_one_row(node: &Node) -> bool {
+    match node {
+        Node::EmptyScan { .. } => true,
+        Node::ValuesScan { rows, .. } => rows.len() <= 1,
+        // Without GROUP BY all the rows fold into a single one
+        Node::Aggregate { group_by, .. } => group_by.is_empty(),
+        Node::Limit { limit, .. } => *limit <= 1,
+        Node::Projection { source, .. } | Node::Offset { source, .. } => at_most_one_row(source),
+        _ => false,
+    }
+}
+
+impl Node {
+    // Rebuild the node with each of its sources replaced by `f` of it
+    fn map_sources(self, f: &mut dyn FnMut(Node) -> Result<Node>) -> Result<Node> {
+        let mut map = |source: Box<Node>| f(*source).map(Box::new);
+        Ok(match self {
+            Node::CreateTableAs {
+                table_name,
+                if_not_exists,
+                temporary,
+                source,
+            } => Node::CreateTableAs {
+                table_name,
+                if_not_exists,
+                temporary,
+                source: map(source)?,
+            },
+            Node::Union { left, right, all } => Node::Union {
+                left: map(left)?,
+                right: map(right)?,
+                all,
+            },
+            Node::Join { left, right, kind } => Node::Join {
+                left: map(left)?,
+                right: map(right)?,
+                kind,
+            },
+            Node::Update {
+                table_name,
+                columns,
+                source,
+            } => Node::Update {
+                table_name,
+                columns,
+                source: map(source)?,
+            },
+            Node::Delete { table_name, source } => Node::Delete {
+                table_name,
+                source: map(source)?,
+            },
+            Node::Aggregate {
+                source,
+                exprs,
+                group_by,
+            } => Node::Aggregate {
+                source: map(source)?,
+                exprs,
+                group_by,
+            },
+            Node::Projection { source, exprs } => Node::Projection {
+                source: map(source)?,
+                exprs,
+            },
+            Node::Order { order_by, source } => Node::Order {
+                order_by,
+                source: map(source)?,
+            },
+            Node::Limit { source, limit } => Node::Limit {
+                source: map(source)?,
+                limit,
+            },
+            Node::Offset { source, offset } => Node::Offset {
+                source: map(source)?,
+                offset,
+            },
+            node => node,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Result;
+    use crate::sql::parser::Parser;
+    use crate::sql::plan::Plan;
+
+    fn plan(sql: &str) -> Result<String> {
+        Ok(Plan::build(Parser::new(sql).parse()?)?
+            .explain()?
+            .to_string())
+    }
+
+    #[test]
+    fn test_limit_zero() -> Result<()> {
+        assert_eq!(
+            plan("SELECT * FROM t ORDER BY a LIMIT 0;")?,
+            ["Limit: 0", "-> Empty Scan: t"].join("\n")
+        );
+        assert_eq!(
+            plan("SELECT a FROM t WHERE a > 1 ORDER BY a LIMIT 0 OFFSET 2;")?,
+            [
+                "Limit: 0",
+                "-> Offset: 2",
+                "   -> Projection: a",
+                "      -> Empty Scan: t",
+            ]
+            .join("\n")
+        );
+        assert_eq!(
+            plan("SELECT * FROM a JOIN b ON a.id = b.id LIMIT 0;")?,
+            [
+                "Limit: 0",
+                "-> Inner Join (on: a.id = b.id)",
+                "   -> Empty Scan: a",
+                "   -> Empty Scan: b",
+            ]
+            .join("\n")
+        );
+        // Any other limit still sorts the rows
+        assert_eq!(
+            plan("SELECT * FROM t ORDER BY a LIMIT 2;")?,
+            ["Limit: 2", "-> Order: a ASC", "   -> Scan: t"].join("\n")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_order_of_single_row() -> Result<()> {
+        assert_eq!(
+            plan("SELECT COUNT(*) AS n FROM t ORDER BY n;")?,
+            ["Aggregate: COUNT(*) AS n", "-> Scan: t"].join("\n")
+        );
+        assert_eq!(
+            plan("SELECT a, COUNT(*) FROM t GROUP BY a ORDER BY a;")?,
+            [
+                "Order: a ASC",
+                "-> Aggregate: a, COUNT(*) (group by: a)",
+                "   -> Scan: t",
+            ]
+            .join("\n")
+        );
+        Ok(())
+    }
+}
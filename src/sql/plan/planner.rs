@@ -21,50 +21,145 @@ impl Planner {
 
     fn build_statement(&mut self, stmt: ast::Statement) -> Result<Node> {
         Ok(match stmt {
-            ast::Statement::CreateTable { name, columns } => Node::CreateTable {
+            ast::Statement::DropTable { name, if_exists } => Node::DropTable {
+                table_name: name,
+                if_exists,
+            },
+            ast::Statement::CreateTable {
+                name,
+                columns,
+                if_not_exists,
+                temporary,
+            } => Node::CreateTable {
+                if_not_exists,
+                temporary,
                 schema: Table {
                     name,
                     columns: columns
                         .into_iter()
-                        .map(|c| {
-                            let nullable = c.nullable.unwrap_or(!c.primary_key);
-                            let default = match c.default {
-                                Some(express) => Some(Value::from(&express)),
-                                None if nullable => Some(Value::Null),
-                                None => None,
-                            };
-
-                            schema::Column {
-                                name: c.name,
-                                datatype: c.data_type,
-                                nullable,
-                                default,
-                                primary_key: c.primary_key,
-                            }
-                        })
-                        .collect(),
+                        .map(Self::build_column)
+                        .collect::<Result<_>>()?,
                 },
             },
+            ast::Statement::ShowTables => Node::ShowTables,
+            ast::Statement::Describe { table_name } => Node::Describe { table_name },
+            ast::Statement::AddColumn { table_name, column } => Node::AddColumn {
+                table_name,
+                column: Self::build_column(column)?,
+            },
+            ast::Statement::CreateTableAs {
+                name,
+                if_not_exists,
+                temporary,
+                query,
+            } => Node::CreateTableAs {
+                table_name: name,
+                if_not_exists,
+                temporary,
+                source: Box::new(self.build_statement(*query)?),
+            },
             ast::Statement::Insert {
                 table_name,
                 columns,
                 values,
+                if_not_exists,
             } => Node::Insert {
                 table_name,
                 values,
                 columns: columns.unwrap_or_default(),
+                if_not_exists,
             },
             ast::Statement::Select {
-                table_name,
+                select,
+                from,
+                joins,
+                as_of,
                 where_clause,
+                group_by,
                 order_by,
                 limit,
                 offset,
             } => {
-                let mut node = Node::Scan {
-                    table_name,
-                    filter: where_clause,
+                if as_of.is_some() {
+                    return Err(Error::InternalError(
+                        "AS OF TIMESTAMP is only supported on a single SELECT statement"
+                            .to_string(),
+                    ));
+                }
+                if !joins.is_empty() && where_clause.is_some() {
+                    return Err(Error::InternalError(
+                        "WHERE isn't supported on joins yet".to_string(),
+                    ));
+                }
+                // Inner joins qualify their columns with the table names, natural joins match
+                // them by their plain names
+                let inner = joins
+                    .iter()
+                    .filter(|join| matches!(join.kind, ast::JoinKind::Inner(_)))
+                    .count();
+                if inner > 0 && inner < joins.len() {
+                    return Err(Error::InternalError(
+                        "NATURAL JOIN can't be combined with JOIN ... ON".to_string(),
+                    ));
+                }
+                let select = Self::expand_wildcards(from.name(), !joins.is_empty(), select)?;
+                let mut node = match from {
+                    ast::FromItem::Table(table_name) => Node::Scan {
+                        table_name,
+                        filter: where_clause,
+                    },
+                    ast::FromItem::Values { rows, columns, .. } => {
+                        if where_clause.is_some() {
+                            return Err(Error::InternalError(
+                                "WHERE isn't supported on VALUES yet".to_string(),
+                            ));
+                        }
+                        self.build_values_scan(rows, columns)?
+                    }
                 };
+                for join in joins {
+                    node = Node::Join {
+                        left: Box::new(node),
+                        right: Box::new(Node::Scan {
+                            table_name: join.table_name,
+                            filter: None,
+                        }),
+                        kind: join.kind,
+                    }
+                }
+
+                // Without aggregates the select list is a projection of the source columns. It
+                // comes after ORDER BY, so rows can be sorted on columns that aren't selected.
+                let is_aggregate =
+                    !group_by.is_empty() || select.iter().any(|(expr, _)| expr.has_function());
+                let mut projection = None;
+                if is_aggregate {
+                    if select.is_empty() {
+                        return Err(Error::InternalError(
+                            "* can't be selected with GROUP BY".to_string(),
+                        ));
+                    }
+                    // Outside of aggregate functions, a group only has a single value for the
+                    // columns it is grouped by
+                    for (expr, _) in select.iter() {
+                        match expr {
+                            ast::Expression::Function(..) => {}
+                            ast::Expression::Field(name) if group_by.contains(name) => {}
+                            expr => {
+                                return Err(Error::InternalError(format!(
+                                    "{expr} must be in GROUP BY or used in an aggregate function"
+                                )))
+                            }
+                        }
+                    }
+                    node = Node::Aggregate {
+                        source: Box::new(node),
+                        exprs: select,
+                        group_by,
+                    }
+                } else if !select.is_empty() {
+                    projection = Some(select);
+                }
 
                 if !order_by.is_empty() {
                     node = Node::Order {
@@ -72,51 +167,177 @@ impl Planner {
                         source: Box::new(node),
                     }
                 }
-                // TODO: limit/offset are constrained by Value::Integer i64 need to be usize
-                if let Some(offset) = offset {
-                    node = Node::Offset {
+                if let Some(exprs) = projection {
+                    node = Node::Projection {
                         source: Box::new(node),
-                        offset: match Value::from(&offset) {
-                            Value::Integer(i) => i as usize,
-                            _ => return Err(Error::InternalError(format!("invald offset"))),
-                        },
+                        exprs,
                     }
                 }
-
-                if let Some(limit) = limit {
-                    node = Node::Limit {
+                let node = self.build_offset(node, offset)?;
+                self.build_limit(node, limit)?
+            }
+            // The union is sorted and cut as a whole, by the names of its first query's columns
+            ast::Statement::Union {
+                left,
+                right,
+                all,
+                order_by,
+                limit,
+                offset,
+            } => {
+                let mut node = Node::Union {
+                    left: Box::new(self.build_statement(*left)?),
+                    right: Box::new(self.build_statement(*right)?),
+                    all,
+                };
+                if !order_by.is_empty() {
+                    node = Node::Order {
+                        order_by,
                         source: Box::new(node),
-                        limit: match Value::from(&limit) {
-                            Value::Integer(i) => i as usize,
-                            _ => return Err(Error::InternalError(format!("invald limit"))),
-                        },
                     }
                 }
-
-                node
+                let node = self.build_offset(node, offset)?;
+                self.build_limit(node, limit)?
             }
             ast::Statement::Update {
                 table_name,
                 columns,
                 where_clause,
+                limit,
             } => Node::Update {
                 table_name: table_name.clone(),
                 columns,
-                source: Box::new(Node::Scan {
-                    table_name,
-                    filter: where_clause,
-                }),
+                // Only the first `limit` matching rows are updated
+                source: Box::new(self.build_limit(
+                    Node::Scan {
+                        table_name,
+                        filter: where_clause,
+                    },
+                    limit,
+                )?),
             },
             ast::Statement::Delete {
                 table_name,
                 where_clause,
+                limit,
             } => Node::Delete {
                 table_name: table_name.clone(),
-                source: Box::new(Node::Scan {
-                    table_name,
-                    filter: where_clause,
-                }),
+                source: Box::new(self.build_limit(
+                    Node::Scan {
+                        table_name,
+                        filter: where_clause,
+                    },
+                    limit,
+                )?),
+            },
+            // The open transaction belongs to the session, a plan runs inside of it
+            stmt @ (ast::Statement::Begin | ast::Statement::Commit | ast::Statement::Rollback) => {
+                return Err(Error::InternalError(format!(
+                    "{stmt} can only be executed by a session"
+                )))
+            }
+        })
+    }
+
+    // Columns are nullable unless they're the primary key, and nullable columns default to
+    // NULL
+    fn build_column(c: ast::Column) -> Result<schema::Column> {
+        let nullable = c.nullable.unwrap_or(!c.primary_key);
+        let default = match c.default {
+            Some(express) => Some(express.evaluate(None)?),
+            None if nullable => Some(Value::Null),
+            None => None,
+        };
+
+        Ok(schema::Column {
+            name: c.name,
+            datatype: c.data_type,
+            nullable,
+            default,
+            primary_key: c.primary_key,
+            width: c.width,
+            index: c.index,
+        })
+    }
+
+    // Resolve `table.*` against the FROM table. With a single source table it stands for all
+    // the columns, same as `*`. The planner doesn't know the columns of the table, so it
+    // can't be expanded next to other select expressions
+    fn expand_wildcards(
+        table_name: &str,
+        has_joins: bool,
+        select: Vec<(ast::Expression, Option<String>)>,
+    ) -> Result<Vec<(ast::Expression, Option<String>)>> {
+        let mut wildcards = 0;
+        for (expr, _) in select.iter() {
+            if let ast::Expression::Wildcard(table) = expr {
+                if has_joins {
+                    return Err(Error::InternalError(format!(
+                        "{table}.* isn't supported on joins yet"
+                    )));
+                }
+                if table != table_name {
+                    return Err(Error::InternalError(format!(
+                        "table {table} is not in the FROM clause"
+                    )));
+                }
+                wildcards += 1;
+            }
+        }
+
+        match wildcards {
+            0 => Ok(select),
+            _ if select.len() == 1 => Ok(vec![]),
+            _ => Err(Error::InternalError(format!(
+                "{table_name}.* can't be combined with other select expressions"
+            ))),
+        }
+    }
+
+    // Every row of an inline VALUES source must have one value per column, the parser
+    // already made sure they all have the same number of values
+    fn build_values_scan(
+        &mut self,
+        rows: Vec<Vec<ast::Expression>>,
+        columns: Option<Vec<String>>,
+    ) -> Result<Node> {
+        let width = rows[0].len();
+        let columns =
+            columns.unwrap_or_else(|| (1..=width).map(|i| format!("column{i}")).collect());
+        if width != columns.len() {
+            return Err(Error::InternalError(format!(
+                "VALUES rows have {width} values for {} columns",
+                columns.len()
+            )));
+        }
+
+        Ok(Node::ValuesScan { columns, rows })
+    }
+
+    // TODO: limit/offset are constrained by Value::Integer i64 need to be usize
+    fn build_offset(&mut self, source: Node, offset: Option<ast::Expression>) -> Result<Node> {
+        Ok(match offset {
+            Some(offset) => Node::Offset {
+                source: Box::new(source),
+                offset: match offset.evaluate(None)? {
+                    Value::Integer(i) => i as usize,
+                    _ => return Err(Error::InternalError("invald offset".to_string())),
+                },
+            },
+            None => source,
+        })
+    }
+
+    fn build_limit(&mut self, source: Node, limit: Option<ast::Expression>) -> Result<Node> {
+        Ok(match limit {
+            Some(limit) => Node::Limit {
+                source: Box::new(source),
+                limit: match limit.evaluate(None)? {
+                    Value::Integer(i) => i as usize,
+                    _ => return Err(Error::InternalError("invald limit".to_string())),
+                },
             },
+            None => source,
         })
     }
 }
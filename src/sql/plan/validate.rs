@@ -0,0 +1,240 @@
+use super::{Node, Plan};
+use crate::{
+    error::{Error, Result},
+    sql::{
+        engine::Transaction,
+        executor::{column_name, insert_row, qualify},
+        parser::ast::{Expression, JoinKind, OrderDirection},
+        schema::Table,
+    },
+};
+
+impl Plan {
+    /// Semantic analysis of the plan against the schema seen by the transaction: tables
+    /// and columns must exist and values must fit their columns. Nothing is written.
+    pub fn validate<T: Transaction>(&self, txn: &mut T) -> Result<()> {
+        validate_node(&self.0, txn)?;
+        Ok(())
+    }
+}
+
+// Check that the columns an expression references exist in the table
+fn check_fields(expr: &Expression, table: &Table) -> Result<()> {
+    match expr {
+        Expression::Field(name) => table.get_col_index(name).map(|_| ()),
+        Expression::Operation(op) => {
+            let (left, right) = op.operands();
+            check_fields(left, table)?;
+            check_fields(right, table)
+        }
+        _ => Ok(()),
+    }
+}
+
+// Validate a node and return the names of the columns it outputs, empty for statements
+// that don't return rows
+fn validate_node<T: Transaction>(node: &Node, txn: &mut T) -> Result<Vec<String>> {
+    Ok(match node {
+        Node::CreateTable {
+            schema,
+            if_not_exists,
+            ..
+        } => {
+            schema.is_validate()?;
+            if !if_not_exists && txn.get_table(&schema.name)?.is_some() {
+                return Err(Error::InternalError(format!(
+                    "Table {} already exists",
+                    schema.name
+                )));
+            }
+            vec![]
+        }
+        Node::CreateTableAs {
+            table_name,
+            if_not_exists,
+            source,
+            ..
+        } => {
+            validate_node(source, txn)?;
+            if !if_not_exists && txn.get_table(table_name)?.is_some() {
+                return Err(Error::InternalError(format!(
+                    "Table {table_name} already exists"
+                )));
+            }
+            vec![]
+        }
+        Node::DropTable {
+            table_name,
+            if_exists,
+        } => {
+            if !if_exists {
+                txn.must_get_table(table_name)?;
+            }
+            vec![]
+        }
+        Node::ShowTables => vec!["name".to_string()],
+        Node::Describe { table_name } => {
+            txn.must_get_table(table_name)?;
+            vec![]
+        }
+        Node::AddColumn { table_name, column } => {
+            let mut table = txn.must_get_table(table_name)?;
+            table.add_column(column.clone())?;
+            vec![]
+        }
+        Node::Insert {
+            table_name,
+            columns,
+            values,
+            ..
+        } => {
+            let table = txn.must_get_table(table_name)?;
+            for column in columns {
+                table.get_col_index(column)?;
+            }
+            for row in values {
+                table.check_row(&insert_row(&table, columns, row)?)?;
+            }
+            vec![]
+        }
+        Node::Scan { table_name, filter } => {
+            let table = txn.must_get_table(table_name)?;
+            if let Some(predicate) = filter {
+                for column in predicate.columns() {
+                    table.get_col_index(column)?;
+                }
+            }
+            table.columns.into_iter().map(|c| c.name).collect()
+        }
+        Node::EmptyScan { table_name } => txn
+            .must_get_table(table_name)?
+            .columns
+            .into_iter()
+            .map(|c| c.name)
+            .collect(),
+        Node::Union { left, right, .. } => {
+            let columns = validate_node(left, txn)?;
+            let right_columns = validate_node(right, txn)?;
+            if columns.len() != right_columns.len() {
+                return Err(Error::InternalError(format!(
+                    "UNION of {} columns with {} columns",
+                    columns.len(),
+                    right_columns.len()
+                )));
+            }
+            columns
+        }
+        Node::Join {
+            left,
+            right,
+            kind: JoinKind::Natural,
+        } => {
+            let mut columns = validate_node(left, txn)?;
+            for column in validate_node(right, txn)? {
+                if !columns.contains(&column) {
+                    columns.push(column);
+                }
+            }
+            columns
+        }
+        Node::Join {
+            left,
+            right,
+            kind: JoinKind::Inner(on),
+        } => {
+            let mut columns = qualify(validate_node(left, txn)?, left.table_name().as_deref());
+            columns.extend(qualify(
+                validate_node(right, txn)?,
+                right.table_name().as_deref(),
+            ));
+            for name in on.columns() {
+                if !columns.iter().any(|c| c == name) {
+                    return Err(Error::InternalError(format!("column {name} not found")));
+                }
+            }
+            columns
+        }
+        Node::ValuesScan { columns, rows } => {
+            for expr in rows.iter().flatten() {
+                expr.evaluate(None)?;
+            }
+            columns.clone()
+        }
+        Node::Update {
+            table_name,
+            columns,
+            source,
+        } => {
+            validate_node(source, txn)?;
+            let table = txn.must_get_table(table_name)?;
+            for (name, expr) in columns {
+                let column = &table.columns[table.get_col_index(name)?];
+                // The type of an expression on the row's columns is only known once evaluated
+                if !expr.is_constant() {
+                    check_fields(expr, &table)?;
+                    continue;
+                }
+                column.check_value(&expr.evaluate(None)?)?;
+            }
+            vec![]
+        }
+        Node::Delete { source, .. } => {
+            validate_node(source, txn)?;
+            vec![]
+        }
+        Node::Aggregate {
+            source,
+            exprs,
+            group_by,
+        } => {
+            let columns = validate_node(source, txn)?;
+            for name in group_by {
+                if !columns.contains(name) {
+                    return Err(Error::InternalError(format!("column {name} not found")));
+                }
+            }
+            let mut names = vec![];
+            for (expr, alias) in exprs {
+                if let Expression::Function(_, arg) = expr {
+                    if arg != "*" && !columns.contains(arg) {
+                        return Err(Error::InternalError(format!("column {arg} not found")));
+                    }
+                }
+                names.push(alias.clone().unwrap_or_else(|| column_name(expr)));
+            }
+            names
+        }
+        Node::Projection { source, exprs } => {
+            let columns = validate_node(source, txn)?;
+            let mut names = vec![];
+            for (expr, alias) in exprs {
+                for name in expr.columns() {
+                    if !columns.iter().any(|c| c == name) {
+                        return Err(Error::InternalError(format!("column {name} not found")));
+                    }
+                }
+                names.push(alias.clone().unwrap_or_else(|| column_name(expr)));
+            }
+            names
+        }
+        Node::Order { order_by, source } => validate_order(order_by, source, txn)?,
+        Node::Limit { source, .. } | Node::Offset { source, .. } => validate_node(source, txn)?,
+    })
+}
+
+// Validate the source of a sort and check that it outputs the sorted columns
+pub(super) fn validate_order<T: Transaction>(
+    order_by: &[(String, OrderDirection)],
+    source: &Node,
+    txn: &mut T,
+) -> Result<Vec<String>> {
+    let columns = validate_node(source, txn)?;
+    for (name, _) in order_by {
+        if !columns.contains(name) {
+            return Err(Error::InternalError(format!(
+                "order by colum {name} isn't in table"
+            )));
+        }
+    }
+    Ok(columns)
+}
@@ -45,6 +45,22 @@ impl Table {
                 )));
             }
 
+            // The rows are already found by their primary key
+            if col.primary_key && col.index {
+                return Err(Error::InternalError(format!(
+                    "Column {} in table {} is primary key and can't also be indexed",
+                    col.name, self.name
+                )));
+            }
+
+            // A row relying on the default of the column could never be stored
+            if !col.nullable && col.default == Some(Value::Null) {
+                return Err(Error::InternalError(format!(
+                    "Column {} in table {} is NOT NULL but its default is NULL",
+                    col.name, self.name
+                )));
+            }
+
             // check if default value is valid
             if let Some(default) = &col.default {
                 match default.datatype() {
@@ -56,22 +72,90 @@ impl Table {
                     }
                     _ => {}
                 }
+                col.check_width(default)?;
             }
         }
 
         Ok(())
     }
 
+    /// Append a column to the table. It must have a default value, which fills it in the
+    /// rows already stored, so a NOT NULL column without DEFAULT is rejected, and it can't
+    /// be a primary key.
+    pub fn add_column(&mut self, column: Column) -> Result<()> {
+        if self.columns.iter().any(|c| c.name == column.name) {
+            return Err(Error::InternalError(format!(
+                "Column {} already exists in table {}",
+                column.name, self.name
+            )));
+        }
+        if column.primary_key {
+            return Err(Error::InternalError(format!(
+                "Column {} can't be added to table {} as a primary key",
+                column.name, self.name
+            )));
+        }
+        if column.default.is_none() {
+            return Err(Error::InternalError(format!(
+                "Column {} added to table {} needs a default value or to be nullable",
+                column.name, self.name
+            )));
+        }
+
+        self.columns.push(column);
+        self.is_validate()
+    }
+
     pub fn get_primary_key<'a>(&self, row: &'a Row) -> Result<&'a Value> {
         let col = self
             .columns
             .iter()
             .position(|c| c.primary_key)
-            .expect(format!("Table {} has no primary key", self.name).as_str());
+            .unwrap_or_else(|| panic!("Table {} has no primary key", self.name));
 
         Ok(&row[col])
     }
 
+    /// Check that every value of the row fits its column
+    pub fn check_row(&self, row: &Row) -> Result<()> {
+        for (i, col) in self.columns.iter().enumerate() {
+            col.check_value(&row[i])?;
+        }
+
+        Ok(())
+    }
+
+    /// Hash of the column definitions, equal for equal columns on any run or machine: a plan
+    /// prepared against the table can compare it to notice an ALTER in between. The name of
+    /// the table isn't part of it.
+    pub fn fingerprint(&self) -> u64 {
+        // FNV-1a over each field prefixed by its length, so no two schemas give the same bytes
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut write = |bytes: &[u8]| {
+            for b in (bytes.len() as u64).to_be_bytes().iter().chain(bytes) {
+                hash = (hash ^ *b as u64).wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        for column in &self.columns {
+            write(column.name.as_bytes());
+            write(format!("{:?}", column.datatype).as_bytes());
+            write(&[
+                column.nullable as u8,
+                column.primary_key as u8,
+                column.index as u8,
+            ]);
+            match column.width {
+                Some(width) => write(&width.to_be_bytes()),
+                None => write(&[]),
+            }
+            match &column.default {
+                Some(value) => write(value.to_sql_literal().as_bytes()),
+                None => write(&[0xff]),
+            }
+        }
+        hash
+    }
+
     pub fn get_col_index(&self, col_name: &str) -> Result<usize> {
         self.columns
             .iter()
@@ -90,4 +174,69 @@ pub struct Column {
     pub nullable: bool,
     pub default: Option<Value>,
     pub primary_key: bool,
+    /// Bit width of an integer column narrower than 64 bits, e.g. 16 for SMALLINT
+    pub width: Option<u32>,
+    /// Whether the rows are also indexed by the value of the column, for equality lookups
+    pub index: bool,
+}
+
+impl Column {
+    /// Check that a value matches the type, nullability and width of the column
+    pub fn check_value(&self, value: &Value) -> Result<()> {
+        match value.datatype() {
+            None if !self.nullable => {
+                return Err(Error::InternalError(format!(
+                    "Column {} expects type {:?}, got NULL",
+                    self.name, self.datatype
+                )));
+            }
+            Some(dt) if dt != self.datatype => {
+                return Err(Error::InternalError(format!(
+                    "Column {} expects type {:?}, got {:?}",
+                    self.name, self.datatype, dt
+                )))
+            }
+            _ => {}
+        }
+        self.check_width(value)
+    }
+
+    /// Convert an inserted value to the type of the column where other databases would:
+    /// a BOOLEAN column takes 1 and 0, and 't', 'f', 'true' and 'false' in any case.
+    /// Any other integer or string is rejected, other values are left to `check_value`.
+    pub fn coerce(&self, value: Value) -> Result<Value> {
+        if self.datatype != DataType::Boolean {
+            return Ok(value);
+        }
+        match value {
+            Value::Integer(1) => Ok(Value::Boolean(true)),
+            Value::Integer(0) => Ok(Value::Boolean(false)),
+            Value::String(s) if ["t", "true"].contains(&s.to_lowercase().as_str()) => {
+                Ok(Value::Boolean(true))
+            }
+            Value::String(s) if ["f", "false"].contains(&s.to_lowercase().as_str()) => {
+                Ok(Value::Boolean(false))
+            }
+            v @ (Value::Integer(_) | Value::String(_)) => Err(Error::InternalError(format!(
+                "Column {} expects a boolean, {v} can't be read as one",
+                self.name
+            ))),
+            v => Ok(v),
+        }
+    }
+
+    // A narrow integer column holds the values of a signed integer of its width
+    fn check_width(&self, value: &Value) -> Result<()> {
+        if let (Some(width), Value::Integer(i)) = (self.width, value) {
+            let max = (1i64 << (width - 1)) - 1;
+            let min = -max - 1;
+            if !(min..=max).contains(i) {
+                return Err(Error::InternalError(format!(
+                    "Column {} expects an integer between {min} and {max}, got {i}",
+                    self.name
+                )));
+            }
+        }
+        Ok(())
+    }
 }
@@ -1,6 +1,13 @@
-use crate::sql::parser::ast::Expression;
+use crate::{
+    error::{Error, Result},
+    sql::parser::ast::Consts,
+};
 use serde::{Deserialize, Serialize};
-use std::{cmp::Ordering, fmt::{Display, Formatter}};
+use std::{
+    cmp::Ordering,
+    fmt::{Display, Formatter},
+    hash::{Hash, Hasher},
+};
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum DataType {
@@ -10,6 +17,17 @@ pub enum DataType {
     String,
 }
 
+impl Display for DataType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Boolean => write!(f, "BOOLEAN"),
+            Self::Float => write!(f, "FLOAT"),
+            Self::Integer => write!(f, "INTEGER"),
+            Self::String => write!(f, "STRING"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Value {
     Null,
@@ -27,7 +45,18 @@ impl Display for Value {
                 if *b { write!(f, "TRUE") } else { write!(f, "FALSE") }
             }
             Self::Integer(i) => write!(f, "{}", i),
-            Self::Float(fl) => write!(f, "{}", fl),
+            // Debug is the shortest form that reads back exactly, `Float(1.0)` shows as `1.0`.
+            // Its exponent form has no decimal point, `1e20` gets one so that no float can
+            // be taken for an Integer
+            Self::Float(fl) => {
+                let s = format!("{fl:?}");
+                match s.split_once('e') {
+                    Some((mantissa, exponent)) if !mantissa.contains('.') => {
+                        write!(f, "{mantissa}.0e{exponent}")
+                    }
+                    _ => write!(f, "{s}"),
+                }
+            }
             Self::String(s) => write!(f, "'{}'", s),
         }
     }
@@ -43,18 +72,78 @@ impl Value {
             Self::String(_) => Some(DataType::String),
         }
     }
+
+    /// Formats the value as a SQL literal that the parser reads back to the same value,
+    /// unlike Display a string has its quotes doubled. A negative number is read back with
+    /// its minus sign. NaN and infinite floats have no literal and come out as `NaN` and
+    /// `inf`.
+    pub fn to_sql_literal(&self) -> String {
+        match self {
+            Self::String(s) => format!("'{}'", s.replace('\'', "''")),
+            v => v.to_string(),
+        }
+    }
 }
 
-impl From<&Expression> for Value {
-    fn from(expr: &Expression) -> Self {
-        match expr {
-            Expression::Consts(consts) => match consts {
-                crate::sql::parser::ast::Consts::Null => Value::Null,
-                crate::sql::parser::ast::Consts::Boolean(b) => Value::Boolean(*b),
-                crate::sql::parser::ast::Consts::Integer(i) => Value::Integer(*i),
-                crate::sql::parser::ast::Consts::String(s) => Value::String(s.clone()),
-                crate::sql::parser::ast::Consts::Float(f) => Value::Float(*f),
-            },
+impl From<&Consts> for Value {
+    fn from(consts: &Consts) -> Self {
+        match consts {
+            Consts::Null => Value::Null,
+            Consts::Boolean(b) => Value::Boolean(*b),
+            Consts::Integer(i) => Value::Integer(*i),
+            Consts::String(s) => Value::String(s.clone()),
+            Consts::Float(f) => Value::Float(*f),
+        }
+    }
+}
+
+// Conversions between native Rust types and `Value`, a `None` is NULL. Converting back
+// fails unless the value holds exactly that type, an Integer isn't taken as a Float.
+macro_rules! native_value {
+    ($($native:ty => $variant:ident),*) => {$(
+        impl From<$native> for Value {
+            fn from(v: $native) -> Self {
+                Value::$variant(v)
+            }
+        }
+
+        impl TryFrom<Value> for $native {
+            type Error = Error;
+
+            fn try_from(value: Value) -> Result<Self> {
+                match value {
+                    Value::$variant(v) => Ok(v),
+                    v => Err(Error::InternalError(format!(
+                        "expected {}, got {v}",
+                        DataType::$variant
+                    ))),
+                }
+            }
+        }
+    )*};
+}
+
+native_value!(bool => Boolean, i64 => Integer, f64 => Float, String => String);
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(v: Option<T>) -> Self {
+        v.map_or(Value::Null, Into::into)
+    }
+}
+
+impl<T: TryFrom<Value, Error = Error>> TryFrom<Value> for Option<T> {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self> {
+        match value {
+            Value::Null => Ok(None),
+            v => T::try_from(v).map(Some),
         }
     }
 }
@@ -77,3 +166,191 @@ impl PartialOrd for Value {
 }
 
 pub type Row = Vec<Value>;
+
+/// Wrapper giving `Value` a total order, an equality and a matching `Hash`, to use values as
+/// keys when grouping or deduplicating rows. Unlike `Value`'s own comparisons, NaN equals NaN
+/// (sorting after every other number) and -0.0 equals +0.0. Integers and floats sort by value,
+/// but an integer and a float are never equal, an integer sorts first on ties.
+#[derive(Debug, Clone)]
+pub struct OrderedValue(pub Value);
+
+impl OrderedValue {
+    // Map every NaN to the same NaN and -0.0 to +0.0
+    fn canonical(f: f64) -> f64 {
+        if f.is_nan() {
+            f64::NAN
+        } else if f == 0.0 {
+            0.0
+        } else {
+            f
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self.0 {
+            Value::Null => 0,
+            Value::Boolean(_) => 1,
+            Value::Integer(_) | Value::Float(_) => 2,
+            Value::String(_) => 3,
+        }
+    }
+}
+
+impl Ord for OrderedValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let float = |f: f64| Self::canonical(f);
+        match (&self.0, &other.0) {
+            (Value::Boolean(a), Value::Boolean(b)) => a.cmp(b),
+            (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+            (Value::Float(a), Value::Float(b)) => float(*a).total_cmp(&float(*b)),
+            (Value::Integer(a), Value::Float(b)) => {
+                float(*a as f64).total_cmp(&float(*b)).then(Ordering::Less)
+            }
+            (Value::Float(a), Value::Integer(b)) => float(*a)
+                .total_cmp(&float(*b as f64))
+                .then(Ordering::Greater),
+            (Value::String(a), Value::String(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for OrderedValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for OrderedValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OrderedValue {}
+
+impl Hash for OrderedValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match &self.0 {
+            Value::Null => 0u8.hash(state),
+            Value::Boolean(b) => (1u8, b).hash(state),
+            Value::Integer(i) => (2u8, i).hash(state),
+            Value::Float(f) => (3u8, Self::canonical(*f).to_bits()).hash(state),
+            Value::String(s) => (4u8, s).hash(state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OrderedValue, Value};
+    use crate::error::Result;
+    use crate::sql::parser::{ast::Statement, Parser};
+    use std::collections::{BTreeMap, HashMap};
+
+    #[test]
+    fn test_native_round_trip() -> Result<()> {
+        assert_eq!(Value::from(true), Value::Boolean(true));
+        assert_eq!(Value::from(7i64), Value::Integer(7));
+        assert_eq!(Value::from(1.5), Value::Float(1.5));
+        assert_eq!(Value::from("a"), Value::String("a".to_string()));
+        assert_eq!(Value::from(None::<i64>), Value::Null);
+        assert_eq!(Value::from(Some("b")), Value::String("b".to_string()));
+
+        assert!(!bool::try_from(Value::from(false))?);
+        assert_eq!(i64::try_from(Value::from(-3i64))?, -3);
+        assert_eq!(f64::try_from(Value::from(0.25))?, 0.25);
+        assert_eq!(String::try_from(Value::from("s".to_string()))?, "s");
+        assert_eq!(Option::<i64>::try_from(Value::Null)?, None);
+        assert_eq!(Option::<i64>::try_from(Value::from(Some(4i64)))?, Some(4));
+        Ok(())
+    }
+
+    #[test]
+    fn test_float_display() {
+        assert_eq!(Value::Float(1.0).to_string(), "1.0");
+        assert_eq!(Value::Float(-2.0).to_string(), "-2.0");
+        assert_eq!(Value::Float(0.1 + 0.2).to_string(), "0.30000000000000004");
+        assert_eq!(Value::Float(1e300).to_string(), "1.0e300");
+        assert_eq!(Value::Float(1e20).to_string(), "1.0e20");
+        assert_eq!(Value::Float(1e-7).to_string(), "1.0e-7");
+        assert_eq!(Value::Float(-2.5e-8).to_string(), "-2.5e-8");
+        assert_eq!(Value::Float(f64::NAN).to_string(), "NaN");
+        assert_eq!(Value::Integer(1).to_string(), "1");
+    }
+
+    #[test]
+    fn test_sql_literal_round_trip() -> Result<()> {
+        for value in [
+            Value::Null,
+            Value::Boolean(true),
+            Value::Integer(42),
+            Value::Float(1.0),
+            Value::Float(0.1 + 0.2),
+            Value::Float(1e300),
+            Value::Float(2.5e-8),
+            Value::Float(1e20),
+            Value::Float(-1e-7),
+            Value::Float(-1.5),
+            Value::Float(-0.0),
+            Value::Integer(-5),
+            Value::Integer(i64::MIN),
+            Value::String("it's".to_string()),
+        ] {
+            let sql = format!("INSERT INTO t VALUES ({});", value.to_sql_literal());
+            let Statement::Insert { values, .. } = Parser::new(&sql).parse()? else {
+                panic!("expected an insert from {sql}");
+            };
+            let parsed = values[0][0].evaluate(None)?;
+            assert_eq!(parsed, value, "{sql}");
+            assert_eq!(parsed.to_string(), value.to_string(), "{sql}");
+        }
+        assert_eq!(
+            Value::String("it's".to_string()).to_sql_literal(),
+            "'it''s'"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_native_type_mismatch() {
+        assert!(i64::try_from(Value::String("1".to_string())).is_err());
+        assert!(f64::try_from(Value::Integer(1)).is_err());
+        assert!(bool::try_from(Value::Null).is_err());
+        assert!(Option::<String>::try_from(Value::Integer(1)).is_err());
+    }
+
+    #[test]
+    fn test_ordered_value_float_groups() {
+        let values = [
+            Value::Float(f64::NAN),
+            Value::Float(0.0),
+            Value::Float(-f64::NAN),
+            Value::Float(-0.0),
+            Value::Float(1.5),
+            Value::Float(f64::INFINITY - f64::INFINITY),
+            Value::Integer(0),
+            Value::Null,
+        ];
+
+        // Hash and ordered grouping agree: NaN, +-0.0, 1.5, integer 0 and NULL
+        let mut hashed = HashMap::new();
+        let mut ordered = BTreeMap::new();
+        for v in values.iter() {
+            *hashed.entry(OrderedValue(v.clone())).or_insert(0) += 1;
+            *ordered.entry(OrderedValue(v.clone())).or_insert(0) += 1;
+        }
+        assert_eq!(hashed.len(), 5);
+        assert_eq!(hashed[&OrderedValue(Value::Float(f64::NAN))], 3);
+        assert_eq!(hashed[&OrderedValue(Value::Float(0.0))], 2);
+        assert_eq!(hashed[&OrderedValue(Value::Integer(0))], 1);
+
+        let buckets = ordered.into_values().collect::<Vec<_>>();
+        // NULL, 0, 0.0 and -0.0, 1.5, the NaNs
+        assert_eq!(buckets, vec![1, 1, 2, 1, 3]);
+
+        // Value's own equality is unchanged
+        assert_ne!(Value::Float(f64::NAN), Value::Float(f64::NAN));
+        assert_eq!(Value::Float(0.0), Value::Float(-0.0));
+    }
+}
@@ -1,5 +1,5 @@
-use crate::error::Result;
-use crate::storage::{self, engine::EngineIterator};
+use crate::error::{Error, Result};
+use crate::storage::{self, engine::EngineIterator, mvcc};
 
 use fs4::fs_std::FileExt;
 use std::{
@@ -10,13 +10,77 @@ use std::{
     path::PathBuf,
 };
 
-const LOG_HEADER_SIZE: u32 = 8;
+const LOG_HEADER_SIZE: u32 = 12;
+
+/// +-------------+-------------+ \
+/// | magic (8)   | version (4) | \
+/// +-------------+-------------+ \
+/// Written once at the start of every log file, the entries follow it
+const FILE_MAGIC: &[u8; 8] = b"MRDBLOG\0";
+const FILE_HEADER_SIZE: u64 = 12;
+
+/// Version of the log format written by this build. Version 0 is the original format
+/// without a file header, its entries are rewritten with checksums and the current SQL
+/// encodings when opened. Version 1 logs are refused: builds writing them changed how keys
+/// and values are encoded and kept the version, so the encoding of their entries is
+/// unknown. Version 2 entries are already the current ones, only the header is updated.
+///
+/// The version covers everything stored in the log, down to the encoding of the MVCC and
+/// SQL keys and values. Any change to those bumps it, and older logs are then migrated or
+/// refused when opened.
+pub const FORMAT_VERSION: u32 = 3;
+
+// CRC-32 (IEEE) lookup table, computed at compile time
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, b| {
+        CRC32_TABLE[((crc ^ *b as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
 
 pub type KeyDir = BTreeMap<Vec<u8>, (u64, u32)>;
 
+/// When the engine waits for its writes to reach the disk. Entries are always written to the
+/// file right away, so they survive the process crashing, but until they are synced the
+/// operating system may lose them if the machine crashes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SyncMode {
+    /// Every set and delete is synced before returning, safest but the slowest by far
+    Always,
+    /// Writes are synced on `flush`, which the MVCC layer calls when a transaction commits
+    #[default]
+    OnCommit,
+    /// Never synced, the operating system writes the data back when it sees fit
+    Never,
+}
+
 pub struct BitCastDiskEngine {
     key_dir: KeyDir,
     log: Log,
+    sync_mode: SyncMode,
+    /// Size of the entries the key dir points to, the rest of the log is overwritten data
+    live_bytes: u64,
+    /// Fraction of the log entries still live under which the log is compacted, never when None
+    compaction_threshold: Option<f64>,
 }
 
 impl BitCastDiskEngine {
@@ -24,8 +88,59 @@ impl BitCastDiskEngine {
         let mut log = Log::new(file_path)?;
         // Recover key_dir from the log
         let key_dir = log.build_key_dir()?;
+        let live_bytes = key_dir
+            .iter()
+            .map(|(key, (_, val_size))| entry_size(key, *val_size))
+            .sum();
+
+        Ok(Self {
+            key_dir,
+            log,
+            sync_mode: SyncMode::default(),
+            live_bytes,
+            compaction_threshold: None,
+        })
+    }
+
+    pub fn new_with_sync_mode(file_path: PathBuf, sync_mode: SyncMode) -> Result<Self> {
+        let mut eng = Self::new(file_path)?;
+        eng.sync_mode = sync_mode;
+        Ok(eng)
+    }
+
+    // Sync a write right away in `SyncMode::Always`
+    fn sync_write(&mut self) -> Result<()> {
+        if self.sync_mode == SyncMode::Always {
+            self.log.file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// Compact the log once less than `threshold` of its entries, e.g. 0.5 for half, hold
+    /// live data, checked after every write. None, the default, leaves it to `new_compact`.
+    pub fn set_compaction_threshold(&mut self, threshold: Option<f64>) -> Result<()> {
+        if let Some(threshold) = threshold {
+            if !(0.0..=1.0).contains(&threshold) {
+                return Err(Error::InternalError(format!(
+                    "compaction threshold must be between 0 and 1, got {threshold}"
+                )));
+            }
+        }
+        self.compaction_threshold = threshold;
+        Ok(())
+    }
 
-        Ok(Self { key_dir, log })
+    // Compact the log when too little of it is live, the file header doesn't count. The
+    // write that triggered it is done either way: a failed compaction leaves the log as it
+    // was, and is tried again after the next write.
+    fn maybe_compact(&mut self, log_size: u64) {
+        let Some(threshold) = self.compaction_threshold else {
+            return;
+        };
+        let entries = log_size - FILE_HEADER_SIZE;
+        if (self.live_bytes as f64) < threshold * entries as f64 {
+            let _ = self.compact();
+        }
     }
 
     pub fn new_compact(file_path: PathBuf) -> Result<Self> {
@@ -40,21 +155,24 @@ impl BitCastDiskEngine {
         let mut new_path = self.log.file_path.clone();
         new_path.set_extension("compact");
 
+        // A file left behind by a compaction that crashed holds stale entries
+        Log::open(&new_path)?.set_len(0)?;
         let mut new_log = Log::new(new_path)?;
         let new_key_dir = self
             .key_dir
             .iter()
             .map(|(key, (offset, val_size))| {
                 // read the value from the old log
-                let value = self.log.read_value(*offset, *val_size)?;
-                let (new_offset, new_size) = new_log.write_entry(&key, Some(&value))?;
+                let value = self.log.read_value(key, *offset, *val_size)?;
+                let (new_offset, new_size) = new_log.write_entry(key, Some(&value))?;
                 let total_offset = new_offset + new_size as u64 - *val_size as u64;
 
                 Ok((key.clone(), (total_offset, *val_size)))
             })
             .collect::<Result<KeyDir>>()?;
 
-        // rename the new log file to the old one
+        // rename the new log file to the old one, once its entries are on the disk
+        new_log.file.sync_all()?;
         std::fs::rename(&new_log.file_path, &self.log.file_path)?;
 
         new_log.file_path = self.log.file_path.clone();
@@ -76,15 +194,23 @@ impl storage::Engine for BitCastDiskEngine {
         //                130
         // value size = 20
         let value_offset = offset + size as u64 - value.len() as u64;
-        self.key_dir.insert(key, (value_offset, value.len() as u32));
+        let replaced = self
+            .key_dir
+            .insert(key.clone(), (value_offset, value.len() as u32));
+        if let Some((_, val_size)) = replaced {
+            self.live_bytes -= entry_size(&key, val_size);
+        }
+        self.live_bytes += size as u64;
 
+        self.sync_write()?;
+        self.maybe_compact(offset + size as u64);
         Ok(())
     }
 
     fn get(&mut self, key: Vec<u8>) -> Result<Option<Vec<u8>>> {
         match self.key_dir.get(&key) {
             Some((val_offset, val_len)) => {
-                let value = self.log.read_value(*val_offset, *val_len)?;
+                let value = self.log.read_value(&key, *val_offset, *val_len)?;
                 Ok(Some(value))
             }
             None => Ok(None),
@@ -92,8 +218,13 @@ impl storage::Engine for BitCastDiskEngine {
     }
 
     fn delete(&mut self, key: Vec<u8>) -> Result<()> {
-        self.log.write_entry(&key, None)?;
-        self.key_dir.remove(&key);
+        let (offset, size) = self.log.write_entry(&key, None)?;
+        if let Some((_, val_size)) = self.key_dir.remove(&key) {
+            self.live_bytes -= entry_size(&key, val_size);
+        }
+
+        self.sync_write()?;
+        self.maybe_compact(offset + size as u64);
         Ok(())
     }
 
@@ -103,6 +234,22 @@ impl storage::Engine for BitCastDiskEngine {
             log: &mut self.log,
         }
     }
+
+    fn flush(&mut self) -> Result<()> {
+        // entries are already written to the file, wait until they reach the disk
+        if self.sync_mode == SyncMode::OnCommit {
+            self.log.file.sync_data()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for BitCastDiskEngine {
+    fn drop(&mut self) {
+        // Errors can't be returned from drop, the entries were written to the file anyway
+        let _ = storage::Engine::flush(self);
+        let _ = FileExt::unlock(&self.log.file);
+    }
 }
 
 pub struct BitcaskDiskEngineIterator<'a> {
@@ -113,7 +260,7 @@ pub struct BitcaskDiskEngineIterator<'a> {
 impl BitcaskDiskEngineIterator<'_> {
     fn map(&mut self, item: (&Vec<u8>, &(u64, u32))) -> <Self as Iterator>::Item {
         let (k, (offset, val_size)) = item;
-        let value = self.log.read_value(*offset, *val_size)?;
+        let value = self.log.read_value(k, *offset, *val_size)?;
         Ok((k.clone(), value))
     }
 }
@@ -145,26 +292,127 @@ impl Log {
         if let Some(parent) = file_path.parent() {
             std::fs::create_dir_all(parent)?;
         }
+        let mut file = Self::open(&file_path)?;
+
+        match Self::read_version(&mut file)? {
+            Some(FORMAT_VERSION) => {}
+            Some(2) => {
+                file.seek(SeekFrom::Start(FILE_MAGIC.len() as u64))?;
+                file.write_all(&FORMAT_VERSION.to_le_bytes())?;
+                file.sync_all()?;
+            }
+            Some(1) => {
+                return Err(Error::InternalError(format!(
+                    "log file {} has format version 1, whose key and value encodings varied between builds, it can't be migrated",
+                    file_path.display()
+                )))
+            }
+            Some(version) => {
+                return Err(Error::InternalError(format!(
+                    "log file {} has format version {version}, only version {FORMAT_VERSION} is supported",
+                    file_path.display()
+                )))
+            }
+            None if file.metadata()?.len() == 0 => {
+                file.write_all(FILE_MAGIC)?;
+                file.write_all(&FORMAT_VERSION.to_le_bytes())?;
+            }
+            // No header: a version 0 log, its entries start right away
+            None => file = Self::migrate(&mut file, &file_path)?,
+        }
+
+        Ok(Self { file, file_path })
+    }
+
+    // Rewrite a log of version 0 in the current format. Its entries have no checksum, and
+    // their keys and values are upgraded to the current encodings.
+    fn migrate(file: &mut File, file_path: &PathBuf) -> Result<File> {
+        let mut old = vec![];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_end(&mut old)?;
+
+        let mut new_path = file_path.clone();
+        new_path.set_extension("migrate");
+        let new_file = Self::open(&new_path)?;
+        new_file.set_len(0)?;
+        let mut writer = BufWriter::new(&new_file);
+        writer.write_all(FILE_MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+
+        let mut pos = 0;
+        while pos + 8 <= old.len() {
+            let key_size = u32::from_le_bytes(old[pos..pos + 4].try_into()?) as usize;
+            let val_size = u32::from_le_bytes(old[pos + 4..pos + 8].try_into()?);
+            let key_end = pos + 8 + key_size;
+            let end = key_end
+                + if val_size == u32::MAX {
+                    0
+                } else {
+                    val_size as usize
+                };
+            // An entry cut short at the end of the file was never completely written
+            if end > old.len() {
+                break;
+            }
+            let value = (val_size != u32::MAX).then(|| &old[key_end..end]);
+            let (key, value) = mvcc::upgrade_v0_entry(&old[pos + 8..key_end], value)?;
+            writer.write_all(&encode_entry(&key, value.as_deref()))?;
+            pos = end;
+        }
+        writer.flush()?;
+        drop(writer);
+        new_file.sync_all()?;
+
+        std::fs::rename(&new_path, file_path)?;
+        Ok(new_file)
+    }
+
+    fn open(file_path: &PathBuf) -> Result<File> {
         let file = std::fs::OpenOptions::new()
             .create(true)
+            .truncate(false)
             .read(true)
             .write(true)
-            .open(&file_path)?;
+            .open(file_path)?;
 
         // add exclusive lock to the file, to be sure only one process can use it
-        file.try_lock_exclusive()?;
+        if !file.try_lock_exclusive()? {
+            return Err(Error::InternalError(format!(
+                "log file {} is already in use",
+                file_path.display()
+            )));
+        }
 
-        Ok(Self { file, file_path })
+        Ok(file)
+    }
+
+    // Read the format version from the file header, None when the file doesn't start with one
+    fn read_version(file: &mut File) -> Result<Option<u32>> {
+        if file.metadata()?.len() < FILE_HEADER_SIZE {
+            return Ok(None);
+        }
+
+        let mut header = [0; FILE_HEADER_SIZE as usize];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut header)?;
+        if header[..8] != FILE_MAGIC[..] {
+            return Ok(None);
+        }
+
+        Ok(Some(u32::from_le_bytes(header[8..].try_into()?)))
     }
 
     fn build_key_dir(&mut self) -> Result<KeyDir> {
         let mut key_dir = KeyDir::new();
         let mut buf_reader = BufReader::new(&self.file);
         let file_size = self.file.metadata()?.len();
-        let mut offset = 0;
+        let mut offset = FILE_HEADER_SIZE;
+        buf_reader.seek(SeekFrom::Start(offset))?;
 
         while offset < file_size {
-            let (key, val_len) = Self::read_entry(&mut buf_reader, offset)?;
+            let Some((key, val_len)) = Self::read_entry(&mut buf_reader, offset, file_size)? else {
+                break;
+            };
             let val_offset = offset + LOG_HEADER_SIZE as u64 + key.len() as u64;
 
             match val_len {
@@ -178,85 +426,320 @@ impl Log {
                 }
             }
         }
+        drop(buf_reader);
+
+        // A write cut short by a crash leaves part of an entry at the end, it's dropped so
+        // the next entries follow the last complete one
+        if offset < file_size {
+            self.file.set_len(offset)?;
+        }
 
         Ok(key_dir)
     }
 
-    /// +-------------+-------------+----------------+----------------+ \
-    /// | key len(4)  | val len(4)  | key (variant)   | val (variant) | \
-    /// +-------------+-------------+----------------+----------------+ \
-    fn write_entry(&mut self, key: &Vec<u8>, value: Option<&Vec<u8>>) -> Result<(u64, u32)> {
+    /// +-----------+-------------+-------------+----------------+----------------+ \
+    /// | crc (4)   | key len(4)  | val len(4)  | key (variant)   | val (variant) | \
+    /// +-----------+-------------+-------------+----------------+----------------+ \
+    fn write_entry(&mut self, key: &[u8], value: Option<&[u8]>) -> Result<(u64, u32)> {
         // first move the file cursor to the end of the file
         let offset = self.file.seek(SeekFrom::End(0))?;
-        let key_size = key.len() as u32;
-        let value_size = value.map_or(u32::MAX, |v| v.len() as u32);
+        let entry = encode_entry(key, value);
+        self.file.write_all(&entry)?;
 
-        let payload_size = if value_size == u32::MAX {
-            0
-        } else {
-            value_size
-        };
-        let total_size = key_size + payload_size + LOG_HEADER_SIZE;
-
-        // write the key size, value size, key, and value
-        let mut writer = BufWriter::with_capacity(total_size as usize, &self.file);
-        writer.write_all(&key_size.to_le_bytes())?;
-        writer.write_all(&value_size.to_le_bytes())?;
-        writer.write_all(key)?;
-        if let Some(v) = value {
-            writer.write_all(v)?;
-        }
-        writer.flush()?;
-
-        Ok((offset, total_size))
+        Ok((offset, entry.len() as u32))
     }
 
-    fn read_value(&mut self, offset: u64, val_size: u32) -> Result<Vec<u8>> {
-        self.file.seek(SeekFrom::Start(offset))?;
-        let mut buf = vec![0; val_size as usize];
-        self.file.read_exact(&mut buf)?;
+    // Read the value of `key` stored at `offset`, checking the checksum of its entry
+    fn read_value(&mut self, key: &[u8], offset: u64, val_size: u32) -> Result<Vec<u8>> {
+        let entry_offset = offset - key.len() as u64 - LOG_HEADER_SIZE as u64;
+        self.file.seek(SeekFrom::Start(entry_offset))?;
+        let mut entry = vec![0; LOG_HEADER_SIZE as usize + key.len() + val_size as usize];
+        self.file.read_exact(&mut entry)?;
+        check_entry(&entry, entry_offset)?;
 
-        Ok(buf)
+        Ok(entry.split_off(LOG_HEADER_SIZE as usize + key.len()))
     }
 
+    // Read the key and value size of the checked entry at `offset`, where the reader stands.
+    // None when the file ends before the entry does.
     fn read_entry(
         buf_reader: &mut BufReader<&File>,
         offset: u64,
-    ) -> Result<(Vec<u8>, Option<u32>)> {
-        buf_reader.seek(SeekFrom::Start(offset))?;
-        let mut len_buf = [0; 4];
-
-        // read key size
-        buf_reader.read_exact(&mut len_buf)?;
-        let key_size = u32::from_le_bytes(len_buf);
-        // read value size
-        buf_reader.read_exact(&mut len_buf)?;
-        let val_size = u32::from_le_bytes(len_buf);
-
-        // read key
-        let mut key_buf = vec![0; key_size as usize];
-        buf_reader.read_exact(&mut key_buf)?;
-
-        if val_size != u32::MAX {
-            buf_reader.seek(SeekFrom::Current(val_size as i64))?;
-            Ok((key_buf, Some(val_size)))
-        } else {
-            Ok((key_buf, None))
+        file_size: u64,
+    ) -> Result<Option<(Vec<u8>, Option<u32>)>> {
+        if offset + LOG_HEADER_SIZE as u64 > file_size {
+            return Ok(None);
         }
+        let mut header = [0; LOG_HEADER_SIZE as usize];
+        buf_reader.read_exact(&mut header)?;
+        let key_size = u32::from_le_bytes(header[4..8].try_into()?);
+        let val_size = u32::from_le_bytes(header[8..].try_into()?);
+        let payload_size = key_size as u64
+            + if val_size == u32::MAX {
+                0
+            } else {
+                val_size as u64
+            };
+        if offset + LOG_HEADER_SIZE as u64 + payload_size > file_size {
+            return Ok(None);
+        }
+
+        let mut entry = header.to_vec();
+        entry.resize(LOG_HEADER_SIZE as usize + payload_size as usize, 0);
+        buf_reader.read_exact(&mut entry[LOG_HEADER_SIZE as usize..])?;
+        check_entry(&entry, offset)?;
+
+        let key = entry[LOG_HEADER_SIZE as usize..][..key_size as usize].to_vec();
+        Ok(Some((key, (val_size != u32::MAX).then_some(val_size))))
     }
 }
 
+// Size of the entry of a key and its value in the log
+fn entry_size(key: &[u8], val_size: u32) -> u64 {
+    LOG_HEADER_SIZE as u64 + key.len() as u64 + val_size as u64
+}
+
+// Encode an entry, the value size is u32::MAX for a delete. The checksum covers all of the
+// entry after it.
+fn encode_entry(key: &[u8], value: Option<&[u8]>) -> Vec<u8> {
+    let value_size = value.map_or(u32::MAX, |v| v.len() as u32);
+    let mut entry =
+        Vec::with_capacity(LOG_HEADER_SIZE as usize + key.len() + value.map_or(0, |v| v.len()));
+    entry.extend([0; 4]);
+    entry.extend((key.len() as u32).to_le_bytes());
+    entry.extend(value_size.to_le_bytes());
+    entry.extend(key);
+    entry.extend(value.unwrap_or_default());
+
+    let crc = crc32(&entry[4..]);
+    entry[..4].copy_from_slice(&crc.to_le_bytes());
+    entry
+}
+
+// Check the checksum of a whole entry read at `offset`
+fn check_entry(entry: &[u8], offset: u64) -> Result<()> {
+    let crc = u32::from_le_bytes(entry[..4].try_into()?);
+    if crc32(&entry[4..]) != crc {
+        return Err(Error::InternalError(format!(
+            "log entry at offset {offset} is corrupt, its checksum doesn't match"
+        )));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::BitCastDiskEngine;
-    use crate::{error::Result, storage::Engine};
-    use std::env;
+    use super::{crc32, encode_entry, BitCastDiskEngine, SyncMode, FILE_MAGIC, FORMAT_VERSION};
+    use crate::{
+        error::{Error, Result},
+        storage::{test_utils::TempDb, Engine},
+    };
+
+    #[test]
+    fn test_format_version() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("log");
+
+        // A new log starts with the header of the current version, and opens again
+        let mut eng = BitCastDiskEngine::new(path.clone())?;
+        eng.set(b"a".to_vec(), b"1".to_vec())?;
+        drop(eng);
+        let bytes = std::fs::read(&path)?;
+        assert_eq!(&bytes[..8], FILE_MAGIC);
+        assert_eq!(bytes[8..12], FORMAT_VERSION.to_le_bytes());
+        let mut eng = BitCastDiskEngine::new(path.clone())?;
+        assert_eq!(eng.get(b"a".to_vec())?, Some(b"1".to_vec()));
+        drop(eng);
+
+        // A version this build doesn't know is refused
+        let mut bytes = FILE_MAGIC.to_vec();
+        bytes.extend(99u32.to_le_bytes());
+        std::fs::write(&path, bytes)?;
+        match BitCastDiskEngine::new(path.clone()) {
+            Err(Error::InternalError(msg)) => {
+                assert!(msg.contains("format version 99"), "{msg}")
+            }
+            Err(e) => panic!("Expected a version error, got {e:?}"),
+            Ok(_) => panic!("Expected a version error"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_old_logs() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        // Entries without a checksum: key len, value len (u32::MAX for a delete), key, value
+        let mut entries = vec![];
+        for (key, value) in [
+            (&b"a"[..], Some(&b"1"[..])),
+            (b"b", Some(b"2")),
+            (b"a", None),
+        ] {
+            entries.extend((key.len() as u32).to_le_bytes());
+            entries.extend(value.map_or(u32::MAX, |v| v.len() as u32).to_le_bytes());
+            entries.extend(key);
+            entries.extend(value.unwrap_or_default());
+        }
+        // Version 0 has no file header
+        let path = dir.path().join("v0");
+        std::fs::write(&path, &entries)?;
+
+        let mut eng = BitCastDiskEngine::new(path.clone())?;
+        assert_eq!(
+            eng.scan(..).collect::<Result<Vec<_>>>()?,
+            vec![(b"b".to_vec(), b"2".to_vec())]
+        );
+        eng.set(b"c".to_vec(), b"3".to_vec())?;
+        drop(eng);
+
+        let migrated = std::fs::read(&path)?;
+        assert_eq!(&migrated[..8], FILE_MAGIC);
+        assert_eq!(migrated[8..12], FORMAT_VERSION.to_le_bytes());
+        let mut eng = BitCastDiskEngine::new(path)?;
+        assert_eq!(eng.get(b"b".to_vec())?, Some(b"2".to_vec()));
+        assert_eq!(eng.get(b"c".to_vec())?, Some(b"3".to_vec()));
+        assert_eq!(eng.get(b"a".to_vec())?, None);
+
+        // Version 1 has a file header, and is refused untouched
+        let path = dir.path().join("v1");
+        let mut version_1 = FILE_MAGIC.to_vec();
+        version_1.extend(1u32.to_le_bytes());
+        version_1.extend(&entries);
+        std::fs::write(&path, &version_1)?;
+        match BitCastDiskEngine::new(path.clone()) {
+            Err(Error::InternalError(msg)) => {
+                assert!(msg.contains("format version 1,"), "{msg}")
+            }
+            Err(e) => panic!("Expected a version error, got {e:?}"),
+            Ok(_) => panic!("Expected a version error"),
+        }
+        assert_eq!(std::fs::read(&path)?, version_1);
+
+        // Version 2 entries are the current ones, only the header changes
+        let path = dir.path().join("v2");
+        let mut version_2 = FILE_MAGIC.to_vec();
+        version_2.extend(2u32.to_le_bytes());
+        version_2.extend(encode_entry(b"b", Some(b"2")));
+        std::fs::write(&path, &version_2)?;
+        let mut eng = BitCastDiskEngine::new(path.clone())?;
+        assert_eq!(eng.get(b"b".to_vec())?, Some(b"2".to_vec()));
+        drop(eng);
+        version_2[8..12].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+        assert_eq!(std::fs::read(&path)?, version_2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_corrupt_entry() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("log");
+
+        let mut eng = BitCastDiskEngine::new(path.clone())?;
+        eng.set(b"a".to_vec(), b"value1".to_vec())?;
+        eng.set(b"b".to_vec(), b"value2".to_vec())?;
+        eng.flush()?;
+
+        // Flip a byte of the first value behind the engine's back
+        let mut bytes = std::fs::read(&path)?;
+        let pos = bytes.windows(6).position(|w| w == b"value1").unwrap();
+        bytes[pos] ^= 0xff;
+        std::fs::write(&path, &bytes)?;
+
+        let check = |result: Result<_>| match result {
+            Err(Error::InternalError(msg)) => assert!(msg.contains("checksum"), "{msg}"),
+            Err(e) => panic!("Expected a checksum error, got {e:?}"),
+            Ok(_) => panic!("Expected a checksum error"),
+        };
+        check(eng.get(b"a".to_vec()).map(|_| ()));
+        assert_eq!(eng.get(b"b".to_vec())?, Some(b"value2".to_vec()));
+        drop(eng);
+
+        // Opening the log reads every entry
+        check(BitCastDiskEngine::new(path).map(|_| ()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncated_entry() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("log");
+
+        let mut eng = BitCastDiskEngine::new(path.clone())?;
+        eng.set(b"a".to_vec(), b"1".to_vec())?;
+        eng.set(b"b".to_vec(), b"2".to_vec())?;
+        drop(eng);
+
+        // The last entry lost its final bytes, as in a crash during the write
+        for cut in [1, 5, 13] {
+            let mut eng = BitCastDiskEngine::new(path.clone())?;
+            eng.set(b"c".to_vec(), b"33333".to_vec())?;
+            drop(eng);
+            let len = std::fs::metadata(&path)?.len();
+            std::fs::File::options()
+                .write(true)
+                .open(&path)?
+                .set_len(len - cut)?;
+
+            // The partial entry is dropped, and later ones are written after the previous one
+            let mut eng = BitCastDiskEngine::new(path.clone())?;
+            assert_eq!(eng.get(b"c".to_vec())?, None);
+            eng.set(b"d".to_vec(), b"4".to_vec())?;
+            drop(eng);
+            let mut eng = BitCastDiskEngine::new(path.clone())?;
+            assert_eq!(
+                eng.scan(..).collect::<Result<Vec<_>>>()?,
+                vec![
+                    (b"a".to_vec(), b"1".to_vec()),
+                    (b"b".to_vec(), b"2".to_vec()),
+                    (b"d".to_vec(), b"4".to_vec()),
+                ]
+            );
+            eng.delete(b"d".to_vec())?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_drop_flushes_and_unlocks() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("log");
+
+        let mut eng = BitCastDiskEngine::new(path.clone())?;
+        eng.set(b"a".to_vec(), b"1".to_vec())?;
+        eng.set(b"b".to_vec(), b"2".to_vec())?;
+        eng.delete(b"a".to_vec())?;
+        // The log stays locked while the engine is alive
+        match BitCastDiskEngine::new(path.clone()) {
+            Err(Error::InternalError(msg)) => assert!(msg.contains("already in use"), "{msg}"),
+            Err(e) => panic!("Expected a lock error, got {e:?}"),
+            Ok(_) => panic!("Expected a lock error"),
+        }
+        drop(eng);
+
+        let mut eng = BitCastDiskEngine::new(path)?;
+        assert_eq!(
+            eng.scan(..).collect::<Result<Vec<_>>>()?,
+            vec![(b"b".to_vec(), b"2".to_vec())]
+        );
+
+        Ok(())
+    }
 
     #[test]
     fn test_disk_engine_compact() -> Result<()> {
-        let mut temp_file = env::temp_dir();
-        temp_file.push("sqldb-bitcast/test_bitcast_disk_compact.mrdb.log");
-        let mut eng = BitCastDiskEngine::new(temp_file.clone())?;
+        let db = TempDb::new()?;
+        let mut eng = db.engine()?;
 
         // write some data
         eng.set(b"key1".to_vec(), b"value".to_vec())?;
@@ -285,7 +768,7 @@ mod tests {
         drop(eng);
 
         // compact the log
-        let mut eng2 = BitCastDiskEngine::new_compact(temp_file.clone())?;
+        let mut eng2 = BitCastDiskEngine::new_compact(db.path())?;
         let iter2 = eng2.scan(..);
         let v2 = iter2.collect::<Result<Vec<_>>>()?;
         assert_eq!(
@@ -298,7 +781,126 @@ mod tests {
         );
         drop(eng2);
 
-        std::fs::remove_dir_all(temp_file.parent().unwrap())?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_mode() -> Result<()> {
+        let db = TempDb::new()?;
+        for (i, mode) in [SyncMode::Always, SyncMode::OnCommit, SyncMode::Never]
+            .into_iter()
+            .enumerate()
+        {
+            let key = format!("key{i}").into_bytes();
+            let mut eng = BitCastDiskEngine::new_with_sync_mode(db.path(), mode)?;
+            eng.set(key.clone(), b"value".to_vec())?;
+            eng.set(b"gone".to_vec(), b"value".to_vec())?;
+            eng.delete(b"gone".to_vec())?;
+            eng.flush()?;
+            drop(eng);
+
+            let mut eng = db.engine()?;
+            assert_eq!(eng.get(key)?, Some(b"value".to_vec()), "{mode:?}");
+            assert_eq!(eng.get(b"gone".to_vec())?, None, "{mode:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_compaction_threshold() -> Result<()> {
+        let db = TempDb::new()?;
+        let mut eng = db.engine()?;
+        assert!(eng.set_compaction_threshold(Some(1.5)).is_err());
+        eng.set_compaction_threshold(Some(0.5))?;
+
+        // the same writes as test_disk_engine_compact
+        eng.set(b"key1".to_vec(), b"value".to_vec())?;
+        eng.set(b"key2".to_vec(), b"value".to_vec())?;
+        eng.set(b"key3".to_vec(), b"value".to_vec())?;
+        eng.delete(b"key1".to_vec())?;
+        eng.delete(b"key2".to_vec())?;
+        eng.set(b"aa".to_vec(), b"value1".to_vec())?;
+        eng.set(b"aa".to_vec(), b"value2".to_vec())?;
+        eng.set(b"aa".to_vec(), b"value3".to_vec())?;
+        eng.set(b"bb".to_vec(), b"value4".to_vec())?;
+        eng.set(b"bb".to_vec(), b"value5".to_vec())?;
+        let expected = vec![
+            (b"aa".to_vec(), b"value3".to_vec()),
+            (b"bb".to_vec(), b"value5".to_vec()),
+            (b"key3".to_vec(), b"value".to_vec()),
+        ];
+        assert_eq!(eng.scan(..).collect::<Result<Vec<_>>>()?, expected);
+
+        // overwriting a key keeps the log from growing past twice its live data
+        for i in 0..1000u32 {
+            eng.set(b"aa".to_vec(), i.to_be_bytes().to_vec())?;
+        }
+        let entries = std::fs::metadata(db.path())?.len() - super::FILE_HEADER_SIZE;
+        assert!(entries <= 2 * eng.live_bytes, "{entries} bytes in the log");
+        assert_eq!(
+            eng.get(b"aa".to_vec())?,
+            Some(999u32.to_be_bytes().to_vec())
+        );
+        drop(eng);
+
+        let mut eng = db.engine()?;
+        assert_eq!(
+            eng.scan(..).collect::<Result<Vec<_>>>()?,
+            vec![
+                (b"aa".to_vec(), 999u32.to_be_bytes().to_vec()),
+                (b"bb".to_vec(), b"value5".to_vec()),
+                (b"key3".to_vec(), b"value".to_vec()),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact_stale_temp_file() -> Result<()> {
+        let db = TempDb::new()?;
+        // Left behind by a compaction that crashed, with a key deleted since
+        let mut stale = BitCastDiskEngine::new(db.file("sqldb-log.compact"))?;
+        stale.set(b"gone".to_vec(), b"value".to_vec())?;
+        drop(stale);
+
+        let mut eng = db.engine()?;
+        eng.set(b"gone".to_vec(), b"value".to_vec())?;
+        eng.set(b"kept".to_vec(), b"value".to_vec())?;
+        eng.delete(b"gone".to_vec())?;
+        drop(eng);
+
+        let mut eng = BitCastDiskEngine::new_compact(db.path())?;
+        let expected = vec![(b"kept".to_vec(), b"value".to_vec())];
+        assert_eq!(eng.scan(..).collect::<Result<Vec<_>>>()?, expected);
+        drop(eng);
+        let mut eng = db.engine()?;
+        assert_eq!(eng.scan(..).collect::<Result<Vec<_>>>()?, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_compaction_keeps_write() -> Result<()> {
+        let db = TempDb::new()?;
+        // The temporary log can't be created over a directory
+        std::fs::create_dir(db.file("sqldb-log.compact"))?;
+        let mut eng = db.engine()?;
+        eng.set_compaction_threshold(Some(1.0))?;
+
+        eng.set(b"key".to_vec(), b"value1".to_vec())?;
+        eng.set(b"key".to_vec(), b"value2".to_vec())?;
+        eng.delete(b"other".to_vec())?;
+        assert_eq!(eng.get(b"key".to_vec())?, Some(b"value2".to_vec()));
+
+        // Compacted by the next write once possible
+        std::fs::remove_dir(db.file("sqldb-log.compact"))?;
+        eng.set(b"key".to_vec(), b"value3".to_vec())?;
+        let entries = std::fs::metadata(db.path())?.len() - super::FILE_HEADER_SIZE;
+        assert_eq!(entries, eng.live_bytes);
+        drop(eng);
+        let mut eng = db.engine()?;
+        assert_eq!(eng.get(b"key".to_vec())?, Some(b"value3".to_vec()));
 
         Ok(())
     }
@@ -0,0 +1,159 @@
+use super::engine::{Engine, EngineIterator};
+use crate::error::Result;
+use std::{
+    ops::RangeBounds,
+    sync::{Arc, Mutex},
+};
+
+/// Number of calls made to the wrapped engine, and how many entries its scans yielded
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct OpCounts {
+    pub get: usize,
+    pub set: usize,
+    pub delete: usize,
+    pub scan: usize,
+    pub scanned: usize,
+    /// Values handed back by `get` and by scans, each one is a read of the log on disk
+    pub read_value: usize,
+    pub flush: usize,
+}
+
+/// Handle to read the counts after the engine has been moved into a `Mvcc`
+#[derive(Clone, Default)]
+pub struct Counter {
+    counts: Arc<Mutex<OpCounts>>,
+    /// Keys passed to `set`, in call order
+    writes: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl Counter {
+    pub fn snapshot(&self) -> OpCounts {
+        *self.counts.lock().unwrap()
+    }
+
+    pub fn writes(&self) -> Vec<Vec<u8>> {
+        self.writes.lock().unwrap().clone()
+    }
+
+    pub fn reset(&self) {
+        *self.counts.lock().unwrap() = OpCounts::default();
+        self.writes.lock().unwrap().clear();
+    }
+
+    fn update(&self, f: impl FnOnce(&mut OpCounts)) {
+        f(&mut self.counts.lock().unwrap())
+    }
+}
+
+/// Engine wrapper counting the operations reaching the inner engine, used by tests
+/// to check how much data a query actually reads
+pub struct CountingEngine<E: Engine> {
+    inner: E,
+    counter: Counter,
+}
+
+impl<E: Engine> CountingEngine<E> {
+    pub fn new(inner: E) -> Self {
+        Self {
+            inner,
+            counter: Counter::default(),
+        }
+    }
+
+    pub fn counter(&self) -> Counter {
+        self.counter.clone()
+    }
+}
+
+impl<E: Engine> Engine for CountingEngine<E> {
+    type EngineIterator<'a>
+        = CountingIterator<E::EngineIterator<'a>>
+    where
+        E: 'a;
+
+    fn set(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
+        self.counter.update(|c| c.set += 1);
+        self.counter.writes.lock().unwrap().push(key.clone());
+        self.inner.set(key, value)
+    }
+
+    fn get(&mut self, key: Vec<u8>) -> Result<Option<Vec<u8>>> {
+        let value = self.inner.get(key)?;
+        self.counter.update(|c| {
+            c.get += 1;
+            c.read_value += value.is_some() as usize;
+        });
+        Ok(value)
+    }
+
+    fn delete(&mut self, key: Vec<u8>) -> Result<()> {
+        self.counter.update(|c| c.delete += 1);
+        self.inner.delete(key)
+    }
+
+    fn scan(&mut self, range: impl RangeBounds<Vec<u8>>) -> Self::EngineIterator<'_> {
+        self.counter.update(|c| c.scan += 1);
+        CountingIterator {
+            inner: self.inner.scan(range),
+            counter: self.counter.clone(),
+        }
+    }
+
+    fn scan_with(
+        &mut self,
+        range: impl RangeBounds<Vec<u8>>,
+        reverse: bool,
+        mut visit: impl FnMut(&[u8], &[u8]) -> Result<bool>,
+    ) -> Result<()> {
+        self.counter.update(|c| c.scan += 1);
+        let counter = self.counter.clone();
+        self.inner.scan_with(range, reverse, |key, value| {
+            counter.update(|c| {
+                c.scanned += 1;
+                c.read_value += 1;
+            });
+            visit(key, value)
+        })
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.counter.update(|c| c.flush += 1);
+        self.inner.flush()
+    }
+}
+
+pub struct CountingIterator<I> {
+    inner: I,
+    counter: Counter,
+}
+
+impl<I> CountingIterator<I> {
+    fn count_item(counts: &mut OpCounts) {
+        counts.scanned += 1;
+        counts.read_value += 1;
+    }
+}
+
+impl<I: EngineIterator> EngineIterator for CountingIterator<I> {}
+
+impl<I: EngineIterator> DoubleEndedIterator for CountingIterator<I> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let item = self.inner.next_back();
+        if item.is_some() {
+            self.counter.update(CountingIterator::<I>::count_item);
+        }
+        item
+    }
+}
+
+impl<I: EngineIterator> Iterator for CountingIterator<I> {
+    type Item = Result<(Vec<u8>, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.inner.next();
+        if item.is_some() {
+            self.counter.update(CountingIterator::<I>::count_item);
+        }
+        item
+    }
+}
@@ -18,6 +18,33 @@ pub trait Engine {
 
     fn scan(&mut self, range: impl RangeBounds<Vec<u8>>) -> Self::EngineIterator<'_>;
 
+    // Visit the key-value pairs of a range in key order, or backwards when `reverse` is set,
+    // until the visitor returns false. Unlike `scan` the pairs are only borrowed, so an engine
+    // keeping its data in memory doesn't have to copy every key and value it yields.
+    fn scan_with(
+        &mut self,
+        range: impl RangeBounds<Vec<u8>>,
+        reverse: bool,
+        mut visit: impl FnMut(&[u8], &[u8]) -> Result<bool>,
+    ) -> Result<()> {
+        let mut iter = self.scan(range);
+        loop {
+            let item = match reverse {
+                true => iter.next_back(),
+                false => iter.next(),
+            };
+            match item.transpose()? {
+                Some((key, value)) if visit(&key, &value)? => {}
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    // make the written data durable, engines without persistent storage have nothing to do
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
     // Scans for all key-value pairs where the key starts with the given prefix
     fn scan_prefix(&mut self, prefix: Vec<u8>) -> Self::EngineIterator<'_> {
         // Special case: empty prefix should return all key-value pairs
@@ -25,42 +52,33 @@ pub trait Engine {
             return self.scan(..); // Full range scan
         }
 
-        // Define the start bound: keys must be >= prefix
-        let start = Bound::Included(prefix.clone());
-
-        // Calculate the end bound: the first key that would not start with the prefix
-        let end = {
-            let mut bound_prefix = prefix;
-
-            // To find the end bound, we need to find the lexicographically smallest key
-            // that doesn't start with the prefix. This is done by incrementing the last
-            // non-0xFF byte and truncating.
-
-            // Find the first non-0xFF byte from right to left
-            let mut i = bound_prefix.len();
-            while i > 0 {
-                i -= 1;
-                if bound_prefix[i] < 0xFF {
-                    // If we find a byte that isn't 0xFF, increment it and truncate
-                    // Example: prefix "ab\x01" becomes "ab\x02" (everything after is truncated)
-                    bound_prefix[i] += 1;
-                    bound_prefix.truncate(i + 1);
-                    break;
-                } else if i == 0 {
-                    // Edge case: All bytes are 0xFF (e.g., "\xFF\xFF\xFF")
-                    // In this case, there's no clear "next" prefix, so we use Unbounded
-                    // This means we'll scan from the prefix to the end of the database
-                    return self.scan((start, Bound::Unbounded));
-                }
-            }
+        // Keys must be >= prefix and < the first key that would not start with the prefix
+        // Example: scan_prefix("ab") will scan keys from "ab" (inclusive) to "ac" (exclusive)
+        let end = prefix_end(&prefix);
+        self.scan((Bound::Included(prefix), end))
+    }
+}
 
+/// A range of keys given by its start and end bounds
+pub type KeyRange = (Bound<Vec<u8>>, Bound<Vec<u8>>);
+
+/// Calculate the end bound of a prefix scan: the first key that would not start with the prefix.
+pub fn prefix_end(prefix: &[u8]) -> Bound<Vec<u8>> {
+    // To find the end bound, we need to find the lexicographically smallest key
+    // that doesn't start with the prefix. This is done by incrementing the last
+    // non-0xFF byte and truncating.
+    // Example: prefix "ab\x01" becomes "ab\x02" (everything after is truncated)
+    match prefix.iter().rposition(|b| *b != 0xFF) {
+        Some(i) => {
+            let mut end = prefix[..=i].to_vec();
+            end[i] += 1;
             // We exclude the end bound since we want keys strictly less than this value
-            // Example: scan_prefix("ab") will scan keys from "ab" (inclusive) to "ac" (exclusive)
-            Bound::Excluded(bound_prefix)
-        };
-
-        // Perform a range scan with our calculated bounds
-        self.scan((start, end))
+            Bound::Excluded(end)
+        }
+        // Edge case: All bytes are 0xFF (e.g., "\xFF\xFF\xFF") or the prefix is empty.
+        // In this case, there's no clear "next" prefix, so we use Unbounded
+        // This means we'll scan from the prefix to the end of the database
+        None => Bound::Unbounded,
     }
 }
 
@@ -78,6 +96,7 @@ mod tests {
 
         test_point_operations(MemoryEngine::new())?;
         test_scan_operations(MemoryEngine::new())?;
+        test_scan_with(MemoryEngine::new())?;
 
         test_scan_prefix_operations(MemoryEngine::new())?;
         test_scan_prefix_basic(MemoryEngine::new())?;
@@ -85,44 +104,44 @@ mod tests {
         test_scan_prefix_multi_byte_overflow(MemoryEngine::new())?;
         test_scan_prefix_empty(MemoryEngine::new())?;
         test_scan_prefix_mixed_overflow(MemoryEngine::new())?;
+        test_scan_prefix_all_overflow(MemoryEngine::new())?;
         Ok(())
     }
 
     #[test]
     fn test_bitcast_disk() -> Result<()> {
-        use crate::storage::bitcast_disk::BitCastDiskEngine;
-        use std::env;
-
-        let mut temp_file = env::temp_dir();
-        temp_file.push("sqldb/test_bitcast_disk.mrdb.log");
-        test_point_operations(BitCastDiskEngine::new(temp_file.clone())?)?;
-
-        let mut temp_file = env::temp_dir();
-        temp_file.push("sqldb/test_bitcast_disk2.mrdb.log");
-        test_scan_operations(BitCastDiskEngine::new(temp_file.clone())?)?;
-
-        let mut temp_file = env::temp_dir();
-        temp_file.push("sqldb/test_bitcast_disk4.mrdb.log");
-        test_scan_prefix_basic(BitCastDiskEngine::new(temp_file.clone())?)?;
-
-        let mut temp_file = env::temp_dir();
-        temp_file.push("sqldb/test_bitcast_disk5.mrdb.log");
-        test_scan_prefix_single_byte_overflow(BitCastDiskEngine::new(temp_file.clone())?)?;
-
-        let mut temp_file = env::temp_dir();
-        temp_file.push("sqldb/test_bitcast_disk6.mrdb.log");
-        test_scan_prefix_multi_byte_overflow(BitCastDiskEngine::new(temp_file.clone())?)?;
-
-        let mut temp_file = env::temp_dir();
-        temp_file.push("sqldb/test_bitcast_disk7.mrdb.log");
-        test_scan_prefix_empty(BitCastDiskEngine::new(temp_file.clone())?)?;
+        use crate::storage::{bitcast_disk::BitCastDiskEngine, test_utils::TempDb};
+
+        // One log file per test, all removed with the directory
+        let db = TempDb::new()?;
+        let engine = |name: &str| BitCastDiskEngine::new(db.file(name));
+        test_point_operations(engine("point")?)?;
+        test_scan_operations(engine("scan")?)?;
+        test_scan_with(engine("scan_with")?)?;
+        test_scan_prefix_basic(engine("prefix_basic")?)?;
+        test_scan_prefix_single_byte_overflow(engine("prefix_single_byte")?)?;
+        test_scan_prefix_multi_byte_overflow(engine("prefix_multi_byte")?)?;
+        test_scan_prefix_empty(engine("prefix_empty")?)?;
+        test_scan_prefix_mixed_overflow(engine("prefix_mixed")?)?;
+        test_scan_prefix_all_overflow(engine("prefix_all")?)?;
+        Ok(())
+    }
 
-        let mut temp_file = env::temp_dir();
-        temp_file.push("sqldb/test_bitcast_disk8.mrdb.log");
-        test_scan_prefix_mixed_overflow(BitCastDiskEngine::new(temp_file.clone())?)?;
+    #[test]
+    fn test_prefix_end() {
+        use super::prefix_end;
 
-        std::fs::remove_dir_all(temp_file.parent().unwrap())?;
-        Ok(())
+        assert_eq!(prefix_end(b"ab"), Bound::Excluded(b"ac".to_vec()));
+        assert_eq!(prefix_end(&[0xfe]), Bound::Excluded(vec![0xff]));
+        assert_eq!(prefix_end(&[0x01, 0xff]), Bound::Excluded(vec![0x02]));
+        assert_eq!(
+            prefix_end(&[0x01, 0xfe, 0xff]),
+            Bound::Excluded(vec![0x01, 0xff])
+        );
+        // No key starting with something other than 0xFF bytes comes after the prefix
+        assert_eq!(prefix_end(&[0xff]), Bound::Unbounded);
+        assert_eq!(prefix_end(&[0xff, 0xff]), Bound::Unbounded);
+        assert_eq!(prefix_end(&[]), Bound::Unbounded);
     }
 
     fn test_point_operations(mut eng: impl Engine) -> Result<()> {
@@ -204,6 +223,42 @@ mod tests {
         Ok(())
     }
 
+    // scan_with yields the same pairs as scan, in both directions, and stops when told to
+    fn test_scan_with(mut eng: impl Engine) -> Result<()> {
+        for (key, value) in [(b"b", b"2"), (b"a", b"1"), (b"d", b"4"), (b"c", b"3")] {
+            eng.set(key.to_vec(), value.to_vec())?;
+        }
+        let range = || (Bound::Included(b"b".to_vec()), Bound::Unbounded);
+        let mut visited = vec![];
+
+        for reverse in [false, true] {
+            visited.clear();
+            eng.scan_with(range(), reverse, |key, value| {
+                visited.push((key.to_vec(), value.to_vec()));
+                Ok(true)
+            })?;
+            let mut expected = eng.scan(range()).collect::<Result<Vec<_>>>()?;
+            if reverse {
+                expected.reverse();
+            }
+            assert_eq!(visited, expected);
+        }
+
+        visited.clear();
+        eng.scan_with(.., true, |key, value| {
+            visited.push((key.to_vec(), value.to_vec()));
+            Ok(visited.len() < 2)
+        })?;
+        assert_eq!(
+            visited,
+            vec![
+                (b"d".to_vec(), b"4".to_vec()),
+                (b"c".to_vec(), b"3".to_vec())
+            ]
+        );
+        Ok(())
+    }
+
     fn test_scan_prefix_operations(mut eng: impl Engine) -> Result<()> {
         println!("Testing scan prefix operations...");
 
@@ -241,7 +296,7 @@ mod tests {
         for result in iter {
             let (key, _) = result?;
             assert!(
-                key.starts_with(&[b'a', b'b']),
+                key.starts_with(b"ab"),
                 "Key {:?} does not belong to prefix ab",
                 key
             );
@@ -345,4 +400,33 @@ mod tests {
         println!("Mixed carry test passed!\n");
         Ok(())
     }
+
+    fn test_scan_prefix_all_overflow(mut eng: impl Engine) -> Result<()> {
+        // A prefix made only of 0xFF bytes has no end bound, every key from it on matches
+        eng.set(vec![0xfe, 0xff], b"v1".to_vec())?; // Should not be scanned
+        eng.set(vec![0xff], b"v2".to_vec())?;
+        eng.set(vec![0xff, 0x00], b"v3".to_vec())?;
+        eng.set(vec![0xff, 0xff], b"v4".to_vec())?;
+        eng.set(vec![0xff, 0xff, 0xff], b"v5".to_vec())?;
+
+        fn keys(eng: &mut impl Engine, prefix: Vec<u8>) -> Result<Vec<Vec<u8>>> {
+            eng.scan_prefix(prefix)
+                .map(|result| result.map(|(key, _)| key))
+                .collect()
+        }
+        assert_eq!(
+            keys(&mut eng, vec![0xff])?,
+            vec![
+                vec![0xff],
+                vec![0xff, 0x00],
+                vec![0xff, 0xff],
+                vec![0xff, 0xff, 0xff]
+            ]
+        );
+        assert_eq!(
+            keys(&mut eng, vec![0xff, 0xff])?,
+            vec![vec![0xff, 0xff], vec![0xff, 0xff, 0xff]]
+        );
+        Ok(())
+    }
 }
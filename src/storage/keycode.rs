@@ -14,15 +14,23 @@ where
     T: serde::Deserialize<'de>,
 {
     let mut deserializer = Deserializer { input: key };
+    let value = T::deserialize(&mut deserializer)?;
+    // The whole key must be read, leftover bytes mean it was decoded as the wrong type
+    if !deserializer.input.is_empty() {
+        return Err(Error::InternalError(format!(
+            "unexpected {} trailing bytes in key",
+            deserializer.input.len()
+        )));
+    }
 
-    T::deserialize(&mut deserializer)
+    Ok(value)
 }
 
 pub struct Serializer {
     output: Vec<u8>,
 }
 
-impl<'a> ser::Serializer for &'a mut Serializer {
+impl ser::Serializer for &mut Serializer {
     type Ok = ();
     type Error = Error;
     type SerializeSeq = Self;
@@ -33,6 +41,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     type SerializeStruct = serde::ser::Impossible<Self::Ok, Self::Error>;
     type SerializeStructVariant = serde::ser::Impossible<Self::Ok, Self::Error>;
 
+    /// Serializes a bool as a single byte, `false` (0) sorts before `true` (1).
     fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
         self.output.push(v as u8);
         Ok(())
@@ -50,8 +59,10 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         unimplemented!("do not support i32")
     }
 
+    /// Serializes an i64 as big-endian with the sign bit flipped,
+    /// so that negative numbers sort before positive ones.
     fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
-        self.output.extend(v.to_be_bytes());
+        self.output.extend((v ^ i64::MIN).to_be_bytes());
         Ok(())
     }
 
@@ -76,18 +87,27 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         unimplemented!()
     }
 
-    fn serialize_f64(self, _v: f64) -> Result<Self::Ok> {
-        unimplemented!()
+    /// Serializes an f64 as big-endian bits with the sign bit flipped for positive numbers
+    /// and all the bits flipped for negative ones, so that they sort by value: negative
+    /// numbers first, larger magnitudes first among them. `-0.0` sorts just before `0.0`.
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
+        let bits = v.to_bits();
+        let bits = match v.is_sign_negative() {
+            true => !bits,
+            false => bits ^ (1 << 63),
+        };
+        self.output.extend(bits.to_be_bytes());
+        Ok(())
     }
 
     fn serialize_char(self, _v: char) -> Result<Self::Ok> {
         unimplemented!()
     }
 
+    /// Serializes a string as its bytes, terminated like a byte slice so that it can be
+    /// read back, and so that a string is never a prefix of another one in a composite key.
     fn serialize_str(self, v: &str) -> Result<()> {
-        self.output.extend(v.as_bytes());
-
-        Ok(())
+        self.serialize_bytes(v.as_bytes())
     }
 
     /// Serializes a byte slice.
@@ -114,15 +134,20 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         Ok(())
     }
 
+    /// Serializes `None` as the single byte 0, so that it sorts before any `Some`,
+    /// the same way `Value::Null` sorts first.
     fn serialize_none(self) -> Result<Self::Ok> {
-        unimplemented!()
+        self.output.push(0);
+        Ok(())
     }
 
-    fn serialize_some<T>(self, _value: &T) -> Result<Self::Ok>
+    /// Serializes `Some(value)` as the byte 1 followed by the value.
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok>
     where
         T: ?Sized + Serialize,
     {
-        unimplemented!()
+        self.output.push(1);
+        value.serialize(self)
     }
 
     fn serialize_unit(self) -> Result<Self::Ok> {
@@ -211,7 +236,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 }
 
-impl<'a> ser::SerializeSeq for &'a mut Serializer {
+impl ser::SerializeSeq for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -227,7 +252,7 @@ impl<'a> ser::SerializeSeq for &'a mut Serializer {
     }
 }
 
-impl<'a> ser::SerializeTuple for &'a mut Serializer {
+impl ser::SerializeTuple for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -243,7 +268,7 @@ impl<'a> ser::SerializeTuple for &'a mut Serializer {
     }
 }
 
-impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
+impl ser::SerializeTupleVariant for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -264,11 +289,17 @@ pub struct Deserializer<'de> {
 }
 
 impl<'de> Deserializer<'de> {
-    fn take_bytes(&mut self, len: usize) -> &[u8] {
+    fn take_bytes(&mut self, len: usize) -> Result<&[u8]> {
+        if self.input.len() < len {
+            return Err(Error::InternalError(format!(
+                "unexpected end of key, {len} bytes expected and {} left",
+                self.input.len()
+            )));
+        }
         let (bytes, rest) = self.input.split_at(len);
         self.input = rest;
 
-        bytes
+        Ok(bytes)
     }
 
     fn next_bytes(&mut self) -> Result<Vec<u8>> {
@@ -293,7 +324,7 @@ impl<'de> Deserializer<'de> {
 }
 
 #[allow(unused_variables)]
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     type Error = Error;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
@@ -307,8 +338,11 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let v = self.take_bytes(1)[0];
-        visitor.visit_bool(v != 0)
+        match self.take_bytes(1)?[0] {
+            0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            v => Err(Error::InternalError(format!("unexpected bool byte {v}"))),
+        }
     }
 
     fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
@@ -336,9 +370,9 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let bytes = self.take_bytes(size_of::<i64>());
+        let bytes = self.take_bytes(size_of::<i64>())?;
 
-        visitor.visit_i64(i64::from_be_bytes(bytes.try_into()?))
+        visitor.visit_i64(i64::from_be_bytes(bytes.try_into()?) ^ i64::MIN)
     }
 
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
@@ -366,7 +400,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let bytes = self.take_bytes(size_of::<u64>());
+        let bytes = self.take_bytes(size_of::<u64>())?;
 
         visitor.visit_u64(u64::from_be_bytes(bytes.try_into()?))
     }
@@ -382,7 +416,14 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        let bits = u64::from_be_bytes(self.take_bytes(size_of::<f64>())?.try_into()?);
+        // Positive numbers were stored with the sign bit set
+        let bits = match bits >> 63 {
+            1 => bits ^ (1 << 63),
+            _ => !bits,
+        };
+
+        visitor.visit_f64(f64::from_bits(bits))
     }
 
     fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
@@ -426,7 +467,11 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        match self.take_bytes(1)?[0] {
+            0 => visitor.visit_none(),
+            1 => visitor.visit_some(self),
+            v => Err(Error::InternalError(format!("unexpected option byte {v}"))),
+        }
     }
 
     fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
@@ -522,7 +567,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     }
 }
 
-impl<'de, 'a> de::SeqAccess<'de> for Deserializer<'de> {
+impl<'de> de::SeqAccess<'de> for Deserializer<'de> {
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
@@ -533,7 +578,7 @@ impl<'de, 'a> de::SeqAccess<'de> for Deserializer<'de> {
     }
 }
 
-impl<'de, 'a> de::EnumAccess<'de> for &mut Deserializer<'de> {
+impl<'de> de::EnumAccess<'de> for &mut Deserializer<'de> {
     type Error = Error;
     type Variant = Self;
 
@@ -541,14 +586,14 @@ impl<'de, 'a> de::EnumAccess<'de> for &mut Deserializer<'de> {
     where
         V: DeserializeSeed<'de>,
     {
-        let index = self.take_bytes(1)[0] as u32;
+        let index = self.take_bytes(1)?[0] as u32;
         let varint_index: Result<_> = seed.deserialize(index.into_deserializer());
 
         Ok((varint_index?, self))
     }
 }
 
-impl<'de, 'a> de::VariantAccess<'de> for &mut Deserializer<'de> {
+impl<'de> de::VariantAccess<'de> for &mut Deserializer<'de> {
     type Error = Error;
 
     fn unit_variant(self) -> Result<()> {
@@ -579,6 +624,7 @@ impl<'de, 'a> de::VariantAccess<'de> for &mut Deserializer<'de> {
 
 #[cfg(test)]
 mod tests {
+    use crate::sql::types::Value;
     use crate::storage::mvcc::{MvccKey, MvccKeyPrefix};
 
     use super::deserialize_key;
@@ -637,4 +683,149 @@ mod tests {
             vec![3, 97, 98, 99, 0, 0, 0, 0, 0, 0, 0, 0, 0, 11],
         );
     }
+
+    #[test]
+    fn test_round_trip() {
+        // The fixtures of test_encode decode back to the key they were encoded from
+        for key in [
+            MvccKey::NextVersion,
+            MvccKey::TxnActive(1),
+            MvccKey::TxnWrite(1, vec![1, 2, 3]),
+            MvccKey::Version(b"abc".to_vec(), 11),
+            MvccKey::TxnWrite(u64::MAX, vec![0, 0, 255, 0]),
+            MvccKey::Version(vec![], 0),
+        ] {
+            let encoded = serialize_key(&key).unwrap();
+            assert_eq!(deserialize_key::<MvccKey>(&encoded).unwrap(), key);
+        }
+
+        for value in [
+            Value::Null,
+            Value::Boolean(true),
+            Value::Integer(-42),
+            Value::Float(-1.5),
+            Value::Float(f64::INFINITY),
+            Value::String("a\0b\0\0".to_string()),
+            Value::String(String::new()),
+        ] {
+            let encoded = serialize_key(&value).unwrap();
+            assert_eq!(deserialize_key::<Value>(&encoded).unwrap(), value);
+        }
+
+        // Truncated keys and leftover bytes are errors, not panics
+        let encoded = serialize_key(&MvccKey::Version(b"abc".to_vec(), 11)).unwrap();
+        assert!(deserialize_key::<MvccKey>(&encoded[..encoded.len() - 1]).is_err());
+        assert!(deserialize_key::<MvccKey>(&encoded[..3]).is_err());
+        assert!(deserialize_key::<MvccKey>(&[]).is_err());
+        assert!(deserialize_key::<MvccKey>(&[encoded.as_slice(), &[0]].concat()).is_err());
+    }
+
+    #[test]
+    fn test_f64_order() {
+        let values = [
+            f64::NEG_INFINITY,
+            -1e300,
+            -1.5,
+            -f64::MIN_POSITIVE,
+            -0.0,
+            0.0,
+            f64::MIN_POSITIVE,
+            1.0,
+            1.5,
+            f64::INFINITY,
+        ];
+        let encoded = values
+            .iter()
+            .map(|v| serialize_key(v).unwrap())
+            .collect::<Vec<_>>();
+
+        assert!(encoded.windows(2).all(|w| w[0] < w[1]));
+        for (v, enc) in values.iter().zip(encoded.iter()) {
+            let decoded = deserialize_key::<f64>(enc).unwrap();
+            assert_eq!(decoded.to_bits(), v.to_bits());
+        }
+    }
+
+    #[test]
+    fn test_i64_order() {
+        let values = [i64::MIN, -256, -1, 0, 1, 255, i64::MAX];
+        let encoded = values
+            .iter()
+            .map(|v| serialize_key(v).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(encoded[3], vec![128, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(encoded.windows(2).all(|w| w[0] < w[1]));
+        for (v, enc) in values.iter().zip(encoded.iter()) {
+            assert_eq!(deserialize_key::<i64>(enc).unwrap(), *v);
+        }
+    }
+
+    #[test]
+    fn test_bool_and_null_order() {
+        // NULL sorts first, as in Value::partial_cmp, then false before true
+        let values = [Value::Null, Value::Boolean(false), Value::Boolean(true)];
+        let encoded = values
+            .iter()
+            .map(|v| serialize_key(v).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(encoded, vec![vec![0], vec![1, 0], vec![1, 1]]);
+        for (v, enc) in values.iter().zip(encoded.iter()) {
+            assert_eq!(deserialize_key::<Value>(enc).unwrap(), *v);
+        }
+        assert!(deserialize_key::<bool>(&[2]).is_err());
+
+        let options = [None, Some(false), Some(true)];
+        let encoded = options
+            .iter()
+            .map(|v| serialize_key(v).unwrap())
+            .collect::<Vec<_>>();
+        assert!(encoded.windows(2).all(|w| w[0] < w[1]));
+        for (v, enc) in options.iter().zip(encoded.iter()) {
+            assert_eq!(deserialize_key::<Option<bool>>(enc).unwrap(), *v);
+        }
+    }
+
+    #[test]
+    fn test_string_order() {
+        let values = ["", "\0", "a", "a\0b", "ab", "b"];
+        let encoded = values
+            .iter()
+            .map(|v| serialize_key(v).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(encoded[3], vec![b'a', 0, 255, b'b', 0, 0]);
+        assert!(encoded.windows(2).all(|w| w[0] < w[1]));
+        for (v, enc) in values.iter().zip(encoded.iter()) {
+            assert_eq!(deserialize_key::<String>(enc).unwrap(), *v);
+        }
+
+        // A string followed by another value doesn't run into it
+        let key = (Value::String("a".to_string()), Value::Integer(1));
+        assert_eq!(
+            deserialize_key::<(Value, Value)>(&serialize_key(&key).unwrap()).unwrap(),
+            key
+        );
+    }
+
+    #[test]
+    fn test_composite_key_order() {
+        // Composite keys sort by their first column, then by the second one
+        let keys = [
+            (Value::Null, Value::Integer(5)),
+            (Value::Boolean(false), Value::Null),
+            (Value::Boolean(false), Value::Integer(-1)),
+            (Value::Boolean(false), Value::Integer(3)),
+            (Value::Boolean(true), Value::Null),
+            (Value::Boolean(true), Value::Boolean(false)),
+            (Value::Boolean(true), Value::Boolean(true)),
+        ];
+        let encoded = keys
+            .iter()
+            .map(|k| serialize_key(k).unwrap())
+            .collect::<Vec<_>>();
+        assert!(encoded.windows(2).all(|w| w[0] < w[1]));
+        for (k, enc) in keys.iter().zip(encoded.iter()) {
+            assert_eq!(deserialize_key::<(Value, Value)>(enc).unwrap(), *k);
+        }
+    }
 }
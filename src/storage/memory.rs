@@ -12,6 +12,20 @@ impl MemoryEngine {
             data: BTreeMap::new(),
         }
     }
+
+    /// Copy of the engine with all its data, changes to either one don't show in the other.
+    /// Lets tests set up a populated state once and branch from it.
+    pub fn snapshot(&self) -> MemoryEngine {
+        MemoryEngine {
+            data: self.data.clone(),
+        }
+    }
+}
+
+impl Default for MemoryEngine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Engine for MemoryEngine {
@@ -38,6 +52,24 @@ impl Engine for MemoryEngine {
             inner: self.data.range(range),
         }
     }
+
+    fn scan_with(
+        &mut self,
+        range: impl std::ops::RangeBounds<Vec<u8>>,
+        reverse: bool,
+        mut visit: impl FnMut(&[u8], &[u8]) -> Result<bool>,
+    ) -> Result<()> {
+        let mut range = self.data.range(range);
+        while let Some((key, value)) = match reverse {
+            true => range.next_back(),
+            false => range.next(),
+        } {
+            if !visit(key, value)? {
+                break;
+            }
+        }
+        Ok(())
+    }
 }
 
 // memory engine iterator
@@ -68,3 +100,41 @@ impl<'a> Iterator for MemoryEngineIterator<'a> {
         self.inner.next().map(Self::map)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::MemoryEngine;
+    use crate::{error::Result, storage::engine::Engine};
+
+    #[test]
+    fn test_snapshot() -> Result<()> {
+        let mut engine = MemoryEngine::new();
+        engine.set(b"a".to_vec(), b"1".to_vec())?;
+        engine.set(b"b".to_vec(), b"2".to_vec())?;
+
+        let mut snapshot = engine.snapshot();
+        engine.set(b"a".to_vec(), b"changed".to_vec())?;
+        engine.delete(b"b".to_vec())?;
+        engine.set(b"c".to_vec(), b"3".to_vec())?;
+
+        assert_eq!(
+            snapshot.scan(..).collect::<Result<Vec<_>>>()?,
+            vec![
+                (b"a".to_vec(), b"1".to_vec()),
+                (b"b".to_vec(), b"2".to_vec()),
+            ]
+        );
+
+        // Nor do the changes to the snapshot show in the original
+        snapshot.set(b"d".to_vec(), b"4".to_vec())?;
+        assert_eq!(engine.get(b"d".to_vec())?, None);
+        assert_eq!(
+            engine.scan(..).collect::<Result<Vec<_>>>()?,
+            vec![
+                (b"a".to_vec(), b"changed".to_vec()),
+                (b"c".to_vec(), b"3".to_vec()),
+            ]
+        );
+        Ok(())
+    }
+}
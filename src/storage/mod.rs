@@ -2,9 +2,26 @@ pub mod engine;
 pub use engine::Engine;
 
 pub mod bitcast_disk;
+#[cfg(test)]
+pub mod counting;
 pub mod memory;
+#[cfg(test)]
+pub mod test_utils;
 
 pub mod keycode;
 
 pub mod mvcc;
-pub use mvcc::Mvcc;
+pub use mvcc::{CommitMode, Mvcc};
+
+use bincode::error::DecodeError;
+use serde::de::DeserializeOwned;
+
+// Most bytes bincode may claim while decoding a stored value, no bitcask entry holds more.
+// A corrupt length prefix then fails to decode instead of aborting on a huge allocation
+const MAX_VALUE_SIZE: usize = u32::MAX as usize;
+
+/// Decode a value encoded with `bincode::config::legacy()`, bounded by `MAX_VALUE_SIZE`
+pub(crate) fn decode_value<T: DeserializeOwned>(value: &[u8]) -> Result<T, DecodeError> {
+    let config = bincode::config::legacy().with_limit::<MAX_VALUE_SIZE>();
+    bincode::serde::decode_from_slice(value, config).map(|(value, _)| value)
+}
@@ -1,38 +1,312 @@
 use super::{
-    engine::Engine,
+    decode_value,
+    engine::{prefix_end, Engine, KeyRange},
     keycode::{deserialize_key, serialize_key},
 };
 use crate::error::{Error, Result};
+use crate::sql::engine::kv;
 
 use serde::{Deserialize, Serialize};
 use std::{
     collections::{BTreeMap, HashSet},
-    sync::{Arc, Mutex, MutexGuard},
+    ops::{Bound, RangeBounds},
+    sync::{
+        mpsc::{self, Sender},
+        Arc, Mutex, MutexGuard, Weak,
+    },
+    thread::{self, JoinHandle},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 pub type Version = u64;
 
 pub struct Mvcc<E: Engine> {
     engine: Arc<Mutex<E>>,
+    /// Raw key the next `gc_step` resumes from, None starts a new pass
+    gc_cursor: Arc<Mutex<Option<Vec<u8>>>>,
+    /// Background flusher of asynchronous commits, None when commits are synchronous
+    flusher: Option<Arc<Flusher>>,
 }
 
 impl<E: Engine> Clone for Mvcc<E> {
     fn clone(&self) -> Self {
         Self {
             engine: self.engine.clone(),
+            gc_cursor: self.gc_cursor.clone(),
+            flusher: self.flusher.clone(),
         }
     }
 }
 
+/// How a commit makes its writes durable
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum CommitMode {
+    /// `commit` flushes the engine before returning, a committed transaction survives a crash
+    #[default]
+    Sync,
+    /// `commit` returns before the flush, which is left to a background thread.
+    /// Commits are cheaper, but the most recent ones may be lost if the machine crashes
+    /// before they are flushed, use `Mvcc::flush` to wait for them.
+    Async,
+}
+
+// Background thread flushing the engine after asynchronous commits, several commits made
+// while a flush is running are covered by the next single flush
+struct Flusher {
+    notify: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Flusher {
+    fn start<E: Engine + Send + 'static>(engine: Weak<Mutex<E>>) -> Self {
+        let (notify, commits) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            while commits.recv().is_ok() {
+                while commits.try_recv().is_ok() {}
+                let Some(engine) = engine.upgrade() else {
+                    break;
+                };
+                // A failed flush is retried after the next commit
+                let _ = engine.lock().map(|mut engine| engine.flush());
+            }
+        });
+
+        Self {
+            notify: Some(notify),
+            handle: Some(handle),
+        }
+    }
+
+    fn notify(&self) {
+        if let Some(notify) = &self.notify {
+            let _ = notify.send(());
+        }
+    }
+}
+
+impl Drop for Flusher {
+    fn drop(&mut self) {
+        // Closing the channel stops the thread
+        self.notify.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Outcome of an incremental garbage collection step
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct GcProgress {
+    /// Number of obsolete versions removed by this step
+    pub reclaimed: usize,
+    /// The pass reached the end of the keyspace, nothing obsolete is left behind it
+    pub done: bool,
+}
+
 impl<E: Engine> Mvcc<E> {
     pub fn new(eng: E) -> Self {
         Self {
             engine: Arc::new(Mutex::new(eng)),
+            gc_cursor: Arc::new(Mutex::new(None)),
+            flusher: None,
+        }
+    }
+
+    /// Create an MVCC layer whose transactions commit with the given mode
+    pub fn new_with_commit_mode(eng: E, mode: CommitMode) -> Self
+    where
+        E: Send + 'static,
+    {
+        let mut mvcc = Self::new(eng);
+        if mode == CommitMode::Async {
+            let flusher = Flusher::start(Arc::downgrade(&mvcc.engine));
+            mvcc.flusher = Some(Arc::new(flusher));
         }
+        mvcc
     }
 
     pub fn begin(&self) -> Result<MvccTransaction<E>> {
-        MvccTransaction::begin(self.engine.clone())
+        let mut txn = MvccTransaction::begin(self.engine.clone())?;
+        txn.flusher = self.flusher.clone();
+        Ok(txn)
+    }
+
+    /// Start a read-only transaction seeing the data as it was at `timestamp`, in unix
+    /// milliseconds: the writes of the transactions committed by then and none of the
+    /// others. Times before the versions removed by the garbage collection are refused, and
+    /// the versions the transaction sees are kept until it is committed or rolled back.
+    pub fn begin_as_of(&self, timestamp: u64) -> Result<MvccTransaction<E>> {
+        MvccTransaction::begin_as_of(self.engine.clone(), timestamp)
+    }
+
+    /// Flush the engine, making every transaction committed so far durable
+    pub fn flush(&self) -> Result<()> {
+        self.engine.lock()?.flush()
+    }
+
+    /// Remove every version that no current or future transaction can read anymore,
+    /// returns the number of removed versions.
+    pub fn gc(&self) -> Result<usize> {
+        let mut engine = self.engine.lock()?;
+        let watermark = Self::watermark(&mut engine)?;
+        Self::prune_commit_times(&mut engine, watermark)?;
+        let garbage = Self::find_garbage(&mut engine, watermark, &mut None, usize::MAX)?;
+
+        let reclaimed = garbage.len();
+        for key in garbage {
+            engine.delete(key)?;
+        }
+
+        Ok(reclaimed)
+    }
+
+    /// Incremental variant of `gc` which removes at most `max_keys` versions per call,
+    /// which must be at least 1.
+    /// Each step only holds the engine lock for its own work and continues where the
+    /// previous one stopped, so transactions can run in between steps.
+    pub fn gc_step(&self, max_keys: usize) -> Result<GcProgress> {
+        // No step could ever move past an obsolete version
+        if max_keys == 0 {
+            return Err(Error::InternalError(
+                "a gc step has to remove at least one version".to_string(),
+            ));
+        }
+        let mut cursor = self.gc_cursor.lock()?;
+        let mut engine = self.engine.lock()?;
+        let watermark = Self::watermark(&mut engine)?;
+        Self::prune_commit_times(&mut engine, watermark)?;
+        let garbage = Self::find_garbage(&mut engine, watermark, &mut cursor, max_keys)?;
+
+        let reclaimed = garbage.len();
+        for key in garbage {
+            engine.delete(key)?;
+        }
+
+        Ok(GcProgress {
+            reclaimed,
+            done: cursor.is_none(),
+        })
+    }
+
+    // Collect the obsolete versions below `watermark` starting from the raw key in `cursor`.
+    // Stops before going over `limit` and leaves the raw key to resume from in `cursor`,
+    // None when done.
+    fn find_garbage(
+        engine: &mut MutexGuard<E>,
+        watermark: Version,
+        cursor: &mut Option<Vec<u8>>,
+        limit: usize,
+    ) -> Result<Vec<Vec<u8>>> {
+        let mut garbage = Vec::new();
+        // The raw key being visited, and its versions below the watermark: (key, is deletion)
+        let mut current: Option<Vec<u8>> = None;
+        let mut versions = Vec::new();
+
+        let start = MvccKey::Version(cursor.take().unwrap_or_default(), 0).encode()?;
+        // The commit times come right after the versions
+        let end = MvccKeyPrefix::TxnCommitTime.encode()?;
+        let mut iter = engine.scan(start..end);
+        loop {
+            let entry = match iter.next().transpose()? {
+                Some((key, value)) => match MvccKey::decode(&key)? {
+                    MvccKey::Version(raw_key, version) => Some((key, value, raw_key, version)),
+                    _ => {
+                        return Err(Error::InternalError(format!(
+                            "unexpected Mvcc key: {:?}",
+                            String::from_utf8(key)
+                        )))
+                    }
+                },
+                None => None,
+            };
+
+            // Versions of a key are adjacent, settle the previous key once another one shows up
+            if current.as_ref() != entry.as_ref().map(|e| &e.2) {
+                if let Some(raw_key) = current.take() {
+                    for key in Self::obsolete(std::mem::take(&mut versions)) {
+                        if garbage.len() == limit {
+                            *cursor = Some(raw_key);
+                            return Ok(garbage);
+                        }
+                        garbage.push(key);
+                    }
+                }
+            }
+
+            let Some((key, value, raw_key, version)) = entry else {
+                break;
+            };
+            current.get_or_insert(raw_key);
+            if version < watermark {
+                let value: Option<Vec<u8>> = decode_value(&value)?;
+                versions.push((key, value.is_none()));
+            }
+        }
+
+        Ok(garbage)
+    }
+
+    // Below the watermark only the latest version of a key can still be read,
+    // and not even that one if it's a deletion.
+    fn obsolete(mut versions: Vec<(Vec<u8>, bool)>) -> Vec<Vec<u8>> {
+        if let Some((_, false)) = versions.last() {
+            versions.pop();
+        }
+        versions.into_iter().map(|(key, _)| key).collect()
+    }
+
+    // Remove the commit times of the versions below the watermark, whose older versions the
+    // collection may remove, but the latest one which still resolves reads as of later times.
+    // Reads as of times before the latest of them are refused from then on: the horizon.
+    fn prune_commit_times(engine: &mut MutexGuard<E>, watermark: Version) -> Result<()> {
+        let start = MvccKey::TxnCommitTime(0).encode()?;
+        let end = MvccKey::TxnCommitTime(watermark).encode()?;
+        let mut horizon = Self::gc_horizon(engine)?;
+        let mut below = Vec::new();
+        let mut iter = engine.scan(start..end);
+        while let Some((key, value)) = iter.next().transpose()? {
+            let time: u64 = decode_value(&value)?;
+            horizon = horizon.max(Some(time));
+            below.push(key);
+        }
+        drop(iter);
+
+        // The keys come in version order
+        below.pop();
+        for key in below {
+            engine.delete(key)?;
+        }
+        if let Some(horizon) = horizon {
+            engine.set(
+                MvccKey::GcHorizon.encode()?,
+                bincode::serde::encode_to_vec(horizon, bincode::config::legacy())?,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn gc_horizon(engine: &mut MutexGuard<E>) -> Result<Option<u64>> {
+        Ok(match engine.get(MvccKey::GcHorizon.encode()?)? {
+            Some(value) => Some(decode_value(&value)?),
+            None => None,
+        })
+    }
+
+    // The oldest version hidden from some active transaction, every version
+    // below it is visible to all current and future transactions.
+    fn watermark(engine: &mut MutexGuard<E>) -> Result<Version> {
+        let mut watermark = match engine.get(MvccKey::NextVersion.encode()?)? {
+            Some(value) => decode_value(&value)?,
+            None => 0,
+        };
+
+        let mut iter = engine.scan_prefix(MvccKeyPrefix::TxnActive.encode()?);
+        while let Some((_, value)) = iter.next().transpose()? {
+            let oldest: Version = decode_value(&value)?;
+            watermark = watermark.min(oldest);
+        }
+
+        Ok(watermark)
     }
 }
 
@@ -45,18 +319,30 @@ pub enum MvccKey {
 
     /// Records the version numbers of active transactions (uncommitted transactions, \
     /// deleted after transaction commit), used for conflict detection and visibility judgment.
+    /// - Value: the oldest version the transaction can't see, used by the garbage collection.
     TxnActive(Version),
 
     /// Records the write operations of the transaction (used for rollback)
     /// - Key format: {version} - {key}
     /// - Purpose: Record which transaction keys were modified by the transaction, used to clean up
-    /// corresponding versions during transaction rollback.
+    ///   corresponding versions during transaction rollback.
     TxnWrite(Version, #[serde(with = "serde_bytes")] Vec<u8>),
 
     /// Actually stored transaction version
     /// - Key format: {key} - {version}
     /// - Purpose: Store the value of the transaction key under a specific version, achieving multi-version coexistence.
     Version(#[serde(with = "serde_bytes")] Vec<u8>, Version),
+
+    /// Records when a transaction that wrote something committed
+    /// - Value: the unix time of the commit in milliseconds.
+    /// - Purpose: resolve a wall-clock time to the versions committed by then, for reads as of
+    ///   that time.
+    TxnCommitTime(Version),
+
+    /// The time before which reads as of a past time are refused
+    /// - Value: the latest commit time of the versions the garbage collection collapsed.
+    /// - Purpose: the versions such a read would see may have been removed.
+    GcHorizon,
 }
 
 impl MvccKey {
@@ -69,12 +355,57 @@ impl MvccKey {
     }
 }
 
+/// Re-encode an entry of a version 0 log, see `bitcast_disk::FORMAT_VERSION`. The MVCC keys
+/// are encoded as before, but the SQL keys and values inside them changed, and an active
+/// transaction had no oldest hidden version stored.
+pub(crate) fn upgrade_v0_entry(
+    key: &[u8],
+    value: Option<&[u8]>,
+) -> Result<(Vec<u8>, Option<Vec<u8>>)> {
+    let value = value.map(<[u8]>::to_vec);
+    Ok(match MvccKey::decode(key) {
+        // The versions the transaction couldn't see are unknown, 0 keeps all of them from
+        // the garbage collector
+        Ok(MvccKey::TxnActive(_)) => (
+            key.to_vec(),
+            value
+                .map(|_| bincode::serde::encode_to_vec(0 as Version, bincode::config::legacy()))
+                .transpose()?,
+        ),
+        Ok(MvccKey::TxnWrite(version, raw_key)) => (
+            MvccKey::TxnWrite(version, kv::upgrade_v0_key(&raw_key)?).encode()?,
+            value,
+        ),
+        Ok(MvccKey::Version(raw_key, version)) => {
+            let value = match value {
+                Some(value) => {
+                    let raw_value: Option<Vec<u8>> = decode_value(&value)?;
+                    let raw_value = raw_value
+                        .map(|raw_value| kv::upgrade_v0_value(&raw_key, &raw_value))
+                        .transpose()?;
+                    Some(bincode::serde::encode_to_vec(
+                        raw_value,
+                        bincode::config::legacy(),
+                    )?)
+                }
+                None => None,
+            };
+            (
+                MvccKey::Version(kv::upgrade_v0_key(&raw_key)?, version).encode()?,
+                value,
+            )
+        }
+        _ => (key.to_vec(), value),
+    })
+}
+
 #[derive(Debug, Serialize)]
 pub enum MvccKeyPrefix {
     NextVersion,
     TxnActive,
     TxnWrite(Version),
     Version(#[serde(with = "serde_bytes")] Vec<u8>),
+    TxnCommitTime,
 }
 
 impl MvccKeyPrefix {
@@ -102,40 +433,127 @@ impl TransactionState {
 pub struct MvccTransaction<E: Engine> {
     engine: Arc<Mutex<E>>,
     state: TransactionState,
+    /// Set in asynchronous commit mode, otherwise `commit` flushes by itself
+    flusher: Option<Arc<Flusher>>,
+    /// Set for reads as of a past time, nothing can be written: the version the transaction
+    /// is registered as active under, apart from the version it reads at
+    as_of: Option<Version>,
 }
 
 impl<E: Engine> MvccTransaction<E> {
     /// Start a transaction (get version number, record active transactions)
     pub fn begin(eng: Arc<Mutex<E>>) -> Result<Self> {
         let mut engine = eng.lock()?;
+        let next_version = Self::next_version(&mut engine)?;
+
+        // get current active transactions
+        let active_versions = Self::scan_active(&mut engine)?;
+
+        // mark current transaction as active, along with the oldest version hidden from it
+        let oldest_hidden = active_versions
+            .iter()
+            .min()
+            .copied()
+            .unwrap_or(next_version);
+        engine.set(
+            MvccKey::TxnActive(next_version).encode()?,
+            bincode::serde::encode_to_vec(oldest_hidden, bincode::config::legacy())?,
+        )?;
+
+        Ok(Self {
+            engine: eng.clone(),
+            state: TransactionState {
+                version: next_version,
+                active_versions,
+            },
+            flusher: None,
+            as_of: None,
+        })
+    }
+
+    // Take the next version number
+    fn next_version(engine: &mut MutexGuard<E>) -> Result<Version> {
         // get newest version
         let next_version = match engine.get(MvccKey::NextVersion.encode()?)? {
-            Some(value) => bincode::serde::decode_from_slice(&value, bincode::config::legacy())?.0,
+            Some(value) => decode_value(&value)?,
             None => 0, // initial version number
         };
         // increment next version
         engine.set(
             MvccKey::NextVersion.encode()?,
-            bincode::serde::encode_to_vec(&(next_version + 1), bincode::config::legacy())?,
+            bincode::serde::encode_to_vec(next_version + 1, bincode::config::legacy())?,
         )?;
+        Ok(next_version)
+    }
 
-        // get current active transactions
-        let active_versions = Self::scan_active(&mut engine)?;
+    /// Start a read-only transaction seeing the data as of `timestamp`, in unix milliseconds.
+    /// It sees the versions up to the last one committed by then, except those committed
+    /// later or still running, as if it had begun at that time. It is registered as active
+    /// under a version of its own, keeping the garbage collection off the versions it sees.
+    pub fn begin_as_of(eng: Arc<Mutex<E>>, timestamp: u64) -> Result<Self> {
+        let mut engine = eng.lock()?;
+        if let Some(horizon) = Mvcc::gc_horizon(&mut engine)? {
+            if timestamp < horizon {
+                return Err(Error::InternalError(format!(
+                    "the versions as of {timestamp} were removed by the garbage collection, \
+                     the oldest time that can be read is {horizon}"
+                )));
+            }
+        }
+        let mut version = None;
+        let mut later = HashSet::new();
+        let mut iter = engine.scan_prefix(MvccKeyPrefix::TxnCommitTime.encode()?);
+        while let Some((key, value)) = iter.next().transpose()? {
+            let MvccKey::TxnCommitTime(committed) = MvccKey::decode(&key)? else {
+                return Err(Error::InternalError(format!(
+                    "unexpected Mvcc key: {:?}",
+                    String::from_utf8(key)
+                )));
+            };
+            let time: u64 = decode_value(&value)?;
+            if time <= timestamp {
+                version = version.max(Some(committed));
+            } else {
+                later.insert(committed);
+            }
+        }
+        drop(iter);
+
+        let Some(version) = version else {
+            return Err(Error::InternalError(format!(
+                "no transaction committed at or before {timestamp}"
+            )));
+        };
+        let mut hidden = Self::scan_active(&mut engine)?;
+        hidden.extend(later);
+        hidden.retain(|v| *v < version);
 
-        // mark current transaction as active
-        engine.set(MvccKey::TxnActive(next_version).encode()?, vec![])?;
+        let reader = Self::next_version(&mut engine)?;
+        let oldest_hidden = hidden.iter().min().copied().unwrap_or(version + 1);
+        engine.set(
+            MvccKey::TxnActive(reader).encode()?,
+            bincode::serde::encode_to_vec(oldest_hidden, bincode::config::legacy())?,
+        )?;
 
         Ok(Self {
             engine: eng.clone(),
             state: TransactionState {
-                version: next_version,
-                active_versions,
+                version,
+                active_versions: hidden,
             },
+            flusher: None,
+            as_of: Some(reader),
         })
     }
 
     // Commit transaction
     pub fn commit(&self) -> Result<()> {
+        if let Some(reader) = self.as_of {
+            return self
+                .engine
+                .lock()?
+                .delete(MvccKey::TxnActive(reader).encode()?);
+        }
         // Get the storage engine
         let mut engine = self.engine.lock()?;
 
@@ -147,16 +565,41 @@ impl<E: Engine> MvccTransaction<E> {
         }
         drop(iter);
 
+        // A transaction that wrote something records when its writes became visible
+        if !delete_keys.is_empty() {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|err| Error::InternalError(format!("system clock error: {err}")))?;
+            engine.set(
+                MvccKey::TxnCommitTime(self.state.version).encode()?,
+                bincode::serde::encode_to_vec(now.as_millis() as u64, bincode::config::legacy())?,
+            )?;
+        }
+
         for key in delete_keys.into_iter() {
             engine.delete(key)?;
         }
 
         // Remove from the list of active transactions
-        engine.delete(MvccKey::TxnActive(self.state.version).encode()?)
+        engine.delete(MvccKey::TxnActive(self.state.version).encode()?)?;
+
+        match &self.flusher {
+            Some(flusher) => {
+                flusher.notify();
+                Ok(())
+            }
+            None => engine.flush(),
+        }
     }
 
     // Rollback transaction
     pub fn rollback(&self) -> Result<()> {
+        if let Some(reader) = self.as_of {
+            return self
+                .engine
+                .lock()?
+                .delete(MvccKey::TxnActive(reader).encode()?);
+        }
         // Get the storage engine
         let mut engine = self.engine.lock()?;
         let mut delete_keys = Vec::new();
@@ -195,7 +638,20 @@ impl<E: Engine> MvccTransaction<E> {
     pub fn get(&self, key: Vec<u8>) -> Result<Option<Vec<u8>>> {
         // Get the storage engine
         let mut engine = self.engine.lock()?;
+        self.get_visible(&mut engine, key)
+    }
+
+    /// Get the latest visible values of several keys, in the order of the keys. The storage
+    /// engine is locked once for all of them.
+    pub fn multi_get(&self, keys: &[Vec<u8>]) -> Result<Vec<Option<Vec<u8>>>> {
+        let mut engine = self.engine.lock()?;
+        keys.iter()
+            .map(|key| self.get_visible(&mut engine, key.clone()))
+            .collect()
+    }
 
+    // Read the latest version of the key this transaction can see
+    fn get_visible(&self, engine: &mut E, key: Vec<u8>) -> Result<Option<Vec<u8>>> {
         // version: 9
         // The scanned version range should be 0-8
         let from = MvccKey::Version(key.clone(), 0).encode()?;
@@ -207,11 +663,7 @@ impl<E: Engine> MvccTransaction<E> {
             match MvccKey::decode(&key)? {
                 MvccKey::Version(_, version) => {
                     if self.state.is_visible(version) {
-                        return Ok(bincode::serde::decode_from_slice(
-                            &value,
-                            bincode::config::legacy(),
-                        )?
-                        .0);
+                        return Ok(decode_value(&value)?);
                     }
                 }
                 _ => {
@@ -227,32 +679,47 @@ impl<E: Engine> MvccTransaction<E> {
     }
 
     pub fn scan_prefix(&self, prefix: Vec<u8>) -> Result<Vec<ScanResult>> {
-        let mut eng = self.engine.lock()?;
-        let mut enc_prefix = MvccKeyPrefix::Version(prefix).encode()?;
-        // origin            encode
-        // 98 97 99    ->    98 97 99 0 0
-        // prefix origin    prefix encode
-        // 98 97       ->   98 97 0 0
-        // remove [0, 0] prefix
-        enc_prefix.truncate(enc_prefix.len() - 2);
+        // origin             range
+        // 98 97       ->     [98 97, 98 98)
+        let end = prefix_end(&prefix);
+        self.scan((Bound::Included(prefix), end))
+    }
+
+    /// Same as `scan_prefix`, but the results come in descending key order, see `scan_rev`
+    pub fn scan_prefix_rev(&self, prefix: Vec<u8>) -> Result<Vec<ScanResult>> {
+        let end = prefix_end(&prefix);
+        self.scan_rev((Bound::Included(prefix), end))
+    }
 
+    /// Scan the latest visible values of the raw keys within the given range,
+    /// only the versions of keys inside the range are read from the storage engine
+    pub fn scan(&self, range: impl RangeBounds<Vec<u8>>) -> Result<Vec<ScanResult>> {
+        self.scan_limit(range, usize::MAX)
+    }
+
+    /// Same as `scan`, but stops reading the storage engine once the first `limit` keys
+    /// of the range have been found.
+    pub fn scan_limit(
+        &self,
+        range: impl RangeBounds<Vec<u8>>,
+        limit: usize,
+    ) -> Result<Vec<ScanResult>> {
+        let mut eng = self.engine.lock()?;
         let mut results = BTreeMap::new();
-        let mut iter = eng.scan_prefix(enc_prefix);
-        while let Some((key, value)) = iter.next().transpose()? {
-            if let MvccKey::Version(raw_key, version) = MvccKey::decode(&key)? {
-                if self.state.is_visible(version) {
-                    match bincode::serde::decode_from_slice(&value, bincode::config::legacy())?.0 {
-                        Some(raw_value) => results.insert(raw_key, raw_value),
-                        None => results.remove(&raw_key),
-                    };
-                }
-            } else {
-                return Err(Error::InternalError(format!(
-                    "Unexpected key {:?}",
-                    String::from_utf8(key)
-                )));
+        eng.scan_with(Self::version_range(range)?, false, |key, value| {
+            let (raw_key, version) = Self::decode_version(key)?;
+            // Versions come in key order, so the keys already found can't change anymore
+            if results.len() >= limit && !results.contains_key(&raw_key) {
+                return Ok(false);
             }
-        }
+            if self.state.is_visible(version) {
+                match decode_value(value)? {
+                    Some(raw_value) => results.insert(raw_key, raw_value),
+                    None => results.remove(&raw_key),
+                };
+            }
+            Ok(true)
+        })?;
 
         Ok(results
             .into_iter()
@@ -260,17 +727,112 @@ impl<E: Engine> MvccTransaction<E> {
             .collect())
     }
 
+    /// Same as `scan`, but the results come in descending key order. The storage engine is
+    /// iterated backwards, so the first visible version met for a key is its latest one.
+    pub fn scan_rev(&self, range: impl RangeBounds<Vec<u8>>) -> Result<Vec<ScanResult>> {
+        let mut eng = self.engine.lock()?;
+        let mut results: Vec<ScanResult> = Vec::new();
+        // The raw key whose latest visible version was a delete
+        let mut deleted: Option<Vec<u8>> = None;
+        eng.scan_with(Self::version_range(range)?, true, |key, value| {
+            let (raw_key, version) = Self::decode_version(key)?;
+            let found = results.last().map(|r| &r.key) == Some(&raw_key)
+                || deleted.as_ref() == Some(&raw_key);
+            if found || !self.state.is_visible(version) {
+                return Ok(true);
+            }
+            match decode_value(value)? {
+                Some(raw_value) => results.push(ScanResult {
+                    key: raw_key,
+                    value: raw_value,
+                }),
+                None => deleted = Some(raw_key),
+            }
+            Ok(true)
+        })?;
+
+        Ok(results)
+    }
+
+    // Map a range of raw keys to the range of their versions in the storage engine
+    fn version_range(range: impl RangeBounds<Vec<u8>>) -> Result<KeyRange> {
+        // Every version of a key sorts between {key}-0 and {key}-u64::MAX
+        let start = match range.start_bound() {
+            Bound::Included(k) => Bound::Included(MvccKey::Version(k.clone(), 0).encode()?),
+            Bound::Excluded(k) => Bound::Excluded(MvccKey::Version(k.clone(), u64::MAX).encode()?),
+            Bound::Unbounded => Bound::Included(MvccKey::Version(vec![], 0).encode()?),
+        };
+        // The commit times and the GC horizon are the only kinds of MvccKey sorting after the
+        // versions
+        let end = match range.end_bound() {
+            Bound::Included(k) => Bound::Included(MvccKey::Version(k.clone(), u64::MAX).encode()?),
+            Bound::Excluded(k) => Bound::Excluded(MvccKey::Version(k.clone(), 0).encode()?),
+            Bound::Unbounded => Bound::Excluded(MvccKeyPrefix::TxnCommitTime.encode()?),
+        };
+
+        Ok((start, end))
+    }
+
+    fn decode_version(key: &[u8]) -> Result<(Vec<u8>, Version)> {
+        match MvccKey::decode(key)? {
+            MvccKey::Version(raw_key, version) => Ok((raw_key, version)),
+            _ => Err(Error::InternalError(format!(
+                "Unexpected key {:?}",
+                String::from_utf8_lossy(key)
+            ))),
+        }
+    }
+
+    /// Write several keys at once, `None` deletes the key. The keys are applied in sorted
+    /// order, giving every multi-key write the same canonical lock order, and a later
+    /// write of the same key in the batch wins. Conflicts are checked for all keys before
+    /// anything is written.
+    pub fn write_batch(&self, mut writes: Vec<(Vec<u8>, Option<Vec<u8>>)>) -> Result<()> {
+        // Stable sort keeps the writes of a key in their original order, then keep the last one
+        writes.sort_by(|(a, _), (b, _)| a.cmp(b));
+        writes.reverse();
+        writes.dedup_by(|(a, _), (b, _)| a == b);
+        writes.reverse();
+
+        self.check_writable()?;
+        let mut engine = self.engine.lock()?;
+        for (key, _) in writes.iter() {
+            self.check_conflict(&mut engine, key)?;
+        }
+        for (key, value) in writes {
+            self.write_version(&mut engine, key, value)?;
+        }
+
+        Ok(())
+    }
+
     /// Internal write handler (conflict detection)
     fn write_inner(&self, key: Vec<u8>, value: Option<Vec<u8>>) -> Result<()> {
+        self.check_writable()?;
         // Get the storage engine
         let mut engine = self.engine.lock()?;
 
+        self.check_conflict(&mut engine, &key)?;
+        self.write_version(&mut engine, key, value)
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if self.as_of.is_some() {
+            return Err(Error::InternalError(format!(
+                "transaction reading as of version {} is read-only",
+                self.state.version
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_conflict(&self, engine: &mut MutexGuard<E>, key: &[u8]) -> Result<()> {
         // Detect conflicts
         //  3 4 5
         //  6
         //  key1-3 key2-4 key3-5
         let from = MvccKey::Version(
-            key.clone(),
+            key.to_vec(),
             self.state
                 .active_versions
                 .iter()
@@ -280,7 +842,7 @@ impl<E: Engine> MvccTransaction<E> {
         )
         .encode()?;
 
-        let to = MvccKey::Version(key.clone(), u64::MAX).encode()?;
+        let to = MvccKey::Version(key.to_vec(), u64::MAX).encode()?;
         //  Current active transaction list 3 4 5
         //  Current transaction 6
         // Only need to check the last version number
@@ -304,6 +866,15 @@ impl<E: Engine> MvccTransaction<E> {
             }
         }
 
+        Ok(())
+    }
+
+    fn write_version(
+        &self,
+        engine: &mut MutexGuard<E>,
+        key: Vec<u8>,
+        value: Option<Vec<u8>>,
+    ) -> Result<()> {
         // Record which keys this version wrote, for transaction rollback
         engine.set(
             MvccKey::TxnWrite(self.state.version, key.clone()).encode()?,
@@ -350,7 +921,9 @@ mod tests {
         storage::{bitcast_disk::BitCastDiskEngine, engine::Engine, memory::MemoryEngine},
     };
 
-    use super::{Error, Mvcc};
+    use super::{CommitMode, Error, GcProgress, Mvcc, MvccKey, MvccKeyPrefix, ScanResult};
+    use crate::storage::counting::CountingEngine;
+    use crate::storage::test_utils::TempDb;
 
     // 1. Get
     fn get(eng: impl Engine) -> Result<()> {
@@ -375,9 +948,8 @@ mod tests {
     fn test_get() -> Result<()> {
         get(MemoryEngine::new())?;
 
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
-        get(BitCastDiskEngine::new(p.clone())?)?;
-        std::fs::remove_dir_all(p.parent().unwrap())?;
+        let db = TempDb::new()?;
+        get(db.engine()?)?;
         Ok(())
     }
 
@@ -411,9 +983,8 @@ mod tests {
     fn test_get_isolation() -> Result<()> {
         get_isolation(MemoryEngine::new())?;
 
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
-        get_isolation(BitCastDiskEngine::new(p.clone())?)?;
-        std::fs::remove_dir_all(p.parent().unwrap())?;
+        let db = TempDb::new()?;
+        get_isolation(db.engine()?)?;
         Ok(())
     }
 
@@ -483,9 +1054,8 @@ mod tests {
     #[test]
     fn test_scan_prefix() -> Result<()> {
         scan_prefix(MemoryEngine::new())?;
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
-        scan_prefix(BitCastDiskEngine::new(p.clone())?)?;
-        std::fs::remove_dir_all(p.parent().unwrap())?;
+        let db = TempDb::new()?;
+        scan_prefix(db.engine()?)?;
         Ok(())
     }
 
@@ -564,9 +1134,8 @@ mod tests {
     #[test]
     fn test_scan_isolation() -> Result<()> {
         scan_isolation(MemoryEngine::new())?;
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
-        scan_isolation(BitCastDiskEngine::new(p.clone())?)?;
-        std::fs::remove_dir_all(p.parent().unwrap())?;
+        let db = TempDb::new()?;
+        scan_isolation(db.engine()?)?;
         Ok(())
     }
 
@@ -605,9 +1174,8 @@ mod tests {
     #[test]
     fn test_set() -> Result<()> {
         set(MemoryEngine::new())?;
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
-        set(BitCastDiskEngine::new(p.clone())?)?;
-        std::fs::remove_dir_all(p.parent().unwrap())?;
+        let db = TempDb::new()?;
+        set(db.engine()?)?;
         Ok(())
     }
 
@@ -649,9 +1217,46 @@ mod tests {
     #[test]
     fn test_set_conflict() -> Result<()> {
         set_conflict(MemoryEngine::new())?;
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
-        set_conflict(BitCastDiskEngine::new(p.clone())?)?;
-        std::fs::remove_dir_all(p.parent().unwrap())?;
+        let db = TempDb::new()?;
+        set_conflict(db.engine()?)?;
+        Ok(())
+    }
+
+    // 6b. Writes after a committed transaction hidden from the writer
+    fn hidden_commit_conflict(eng: impl Engine) -> Result<()> {
+        let mvcc = Mvcc::new(eng);
+        let tx = mvcc.begin()?;
+        tx.set(b"A".to_vec(), b"0".to_vec())?;
+        tx.set(b"B".to_vec(), b"0".to_vec())?;
+        tx.commit()?;
+
+        // tx2 reads A before tx1 commits A=1 and B=1, then writes B without reading it:
+        // keeping A=1, B=0 would match no serial order of the two
+        let tx1 = mvcc.begin()?;
+        let tx2 = mvcc.begin()?;
+        assert_eq!(tx2.get(b"A".to_vec())?, Some(b"0".to_vec()));
+        tx1.set(b"A".to_vec(), b"1".to_vec())?;
+        tx1.set(b"B".to_vec(), b"1".to_vec())?;
+        tx1.commit()?;
+        assert_eq!(
+            tx2.set(b"B".to_vec(), b"0".to_vec()),
+            Err(Error::WriteConflict)
+        );
+        tx2.rollback()?;
+
+        let tx = mvcc.begin()?;
+        assert_eq!(tx.get(b"A".to_vec())?, Some(b"1".to_vec()));
+        assert_eq!(tx.get(b"B".to_vec())?, Some(b"1".to_vec()));
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hidden_commit_conflict() -> Result<()> {
+        hidden_commit_conflict(MemoryEngine::new())?;
+        let db = TempDb::new()?;
+        hidden_commit_conflict(db.engine()?)?;
         Ok(())
     }
 
@@ -690,9 +1295,8 @@ mod tests {
     #[test]
     fn test_delete() -> Result<()> {
         delete(MemoryEngine::new())?;
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
-        delete(BitCastDiskEngine::new(p.clone())?)?;
-        std::fs::remove_dir_all(p.parent().unwrap())?;
+        let db = TempDb::new()?;
+        delete(db.engine()?)?;
         Ok(())
     }
 
@@ -718,9 +1322,8 @@ mod tests {
     #[test]
     fn test_delete_conflict() -> Result<()> {
         delete_conflict(MemoryEngine::new())?;
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
-        delete_conflict(BitCastDiskEngine::new(p.clone())?)?;
-        std::fs::remove_dir_all(p.parent().unwrap())?;
+        let db = TempDb::new()?;
+        delete_conflict(db.engine()?)?;
         Ok(())
     }
 
@@ -745,9 +1348,8 @@ mod tests {
     #[test]
     fn test_dirty_read() -> Result<()> {
         dirty_read(MemoryEngine::new())?;
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
-        dirty_read(BitCastDiskEngine::new(p.clone())?)?;
-        std::fs::remove_dir_all(p.parent().unwrap())?;
+        let db = TempDb::new()?;
+        dirty_read(db.engine()?)?;
         Ok(())
     }
 
@@ -774,9 +1376,8 @@ mod tests {
     #[test]
     fn test_unrepeatable_read() -> Result<()> {
         unrepeatable_read(MemoryEngine::new())?;
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
-        unrepeatable_read(BitCastDiskEngine::new(p.clone())?)?;
-        std::fs::remove_dir_all(p.parent().unwrap())?;
+        let db = TempDb::new()?;
+        unrepeatable_read(db.engine()?)?;
         Ok(())
     }
 
@@ -839,9 +1440,8 @@ mod tests {
     #[test]
     fn test_phantom_read() -> Result<()> {
         phantom_read(MemoryEngine::new())?;
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
-        phantom_read(BitCastDiskEngine::new(p.clone())?)?;
-        std::fs::remove_dir_all(p.parent().unwrap())?;
+        let db = TempDb::new()?;
+        phantom_read(db.engine()?)?;
         Ok(())
     }
 
@@ -871,9 +1471,546 @@ mod tests {
     #[test]
     fn test_rollback() -> Result<()> {
         rollback(MemoryEngine::new())?;
-        let p = tempfile::tempdir()?.into_path().join("sqldb-log");
-        rollback(BitCastDiskEngine::new(p.clone())?)?;
-        std::fs::remove_dir_all(p.parent().unwrap())?;
+        let db = TempDb::new()?;
+        rollback(db.engine()?)?;
+        Ok(())
+    }
+
+    // 14. Incremental GC
+    fn write_history<E: Engine>(mvcc: &Mvcc<E>) -> Result<()> {
+        for i in 0..5 {
+            let tx = mvcc.begin()?;
+            tx.set(b"key1".to_vec(), format!("val1-{i}").into_bytes())?;
+            tx.set(b"key2".to_vec(), format!("val2-{i}").into_bytes())?;
+            tx.commit()?;
+        }
+
+        let tx = mvcc.begin()?;
+        tx.set(b"key3".to_vec(), b"val3".to_vec())?;
+        tx.delete(b"key2".to_vec())?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    // The stored entries, but the commit times and the GC horizon which depend on the clock
+    fn dump<E: Engine>(mvcc: &Mvcc<E>) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut entries = mvcc.engine.lock()?.scan(..).collect::<Result<Vec<_>>>()?;
+        entries.retain(|(key, _)| {
+            !matches!(
+                MvccKey::decode(key),
+                Ok(MvccKey::TxnCommitTime(_) | MvccKey::GcHorizon)
+            )
+        });
+        Ok(entries)
+    }
+
+    fn gc_step(eng: impl Engine, full: impl Engine) -> Result<()> {
+        let full = Mvcc::new(full);
+        write_history(&full)?;
+        assert_eq!(full.gc()?, 10);
+
+        let mvcc = Mvcc::new(eng);
+        write_history(&mvcc)?;
+        assert!(mvcc.gc_step(0).is_err());
+        let mut steps = 0;
+        loop {
+            let progress = mvcc.gc_step(1)?;
+            assert!(progress.reclaimed <= 1);
+            steps += 1;
+            if progress.done {
+                break;
+            }
+        }
+        assert_eq!(steps, 10);
+        assert_eq!(dump(&mvcc)?, dump(&full)?);
+
+        // A new pass has nothing left to reclaim
+        assert_eq!(
+            mvcc.gc_step(1)?,
+            GcProgress {
+                reclaimed: 0,
+                done: true
+            }
+        );
+
+        let tx = mvcc.begin()?;
+        assert_eq!(tx.get(b"key1".to_vec())?, Some(b"val1-4".to_vec()));
+        assert_eq!(tx.get(b"key2".to_vec())?, None);
+        assert_eq!(
+            tx.scan_prefix(b"key".to_vec())?,
+            vec![
+                ScanResult {
+                    key: b"key1".to_vec(),
+                    value: b"val1-4".to_vec()
+                },
+                ScanResult {
+                    key: b"key3".to_vec(),
+                    value: b"val3".to_vec()
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gc_step() -> Result<()> {
+        gc_step(MemoryEngine::new(), MemoryEngine::new())?;
+        let db = TempDb::new()?;
+        gc_step(
+            db.engine()?,
+            BitCastDiskEngine::new(db.file("sqldb-log-full"))?,
+        )?;
+        Ok(())
+    }
+
+    fn gc_step_active(eng: impl Engine) -> Result<()> {
+        let mvcc = Mvcc::new(eng);
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"val1".to_vec())?;
+        tx.commit()?;
+
+        // tx1 can't see the writes of tx2, even once they are committed
+        let tx1 = mvcc.begin()?;
+        let tx2 = mvcc.begin()?;
+        let tx3 = mvcc.begin()?;
+        tx2.set(b"key1".to_vec(), b"val1-2".to_vec())?;
+        tx2.commit()?;
+        tx1.commit()?;
+        let tx4 = mvcc.begin()?;
+        tx4.set(b"key1".to_vec(), b"val1-4".to_vec())?;
+        tx4.commit()?;
+
+        // tx3 started while tx2 was active, so it still reads the first version
+        while !mvcc.gc_step(1)?.done {}
+        assert_eq!(tx3.get(b"key1".to_vec())?, Some(b"val1".to_vec()));
+        tx3.commit()?;
+
+        // Once tx3 is gone only the latest version stays
+        assert_eq!(mvcc.gc()?, 2);
+        let tx = mvcc.begin()?;
+        assert_eq!(tx.get(b"key1".to_vec())?, Some(b"val1-4".to_vec()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gc_step_active() -> Result<()> {
+        gc_step_active(MemoryEngine::new())?;
+        let db = TempDb::new()?;
+        gc_step_active(db.engine()?)?;
+        Ok(())
+    }
+
+    // 15. Reverse scan
+    fn scan_rev(eng: impl Engine) -> Result<()> {
+        let mvcc = Mvcc::new(eng);
+        let tx = mvcc.begin()?;
+        tx.set(b"a".to_vec(), b"val1".to_vec())?;
+        tx.set(b"b".to_vec(), b"val2".to_vec())?;
+        tx.set(b"c".to_vec(), b"val3".to_vec())?;
+        tx.set(b"d".to_vec(), b"val4".to_vec())?;
+        tx.commit()?;
+
+        let tx1 = mvcc.begin()?;
+        tx1.set(b"b".to_vec(), b"val2-1".to_vec())?;
+        tx1.delete(b"c".to_vec())?;
+        tx1.commit()?;
+
+        // Neither the uncommitted writes of tx2 nor the later ones of tx4 are visible to tx3
+        let tx2 = mvcc.begin()?;
+        tx2.set(b"a".to_vec(), b"val1-2".to_vec())?;
+        let tx3 = mvcc.begin()?;
+        let tx4 = mvcc.begin()?;
+        tx4.set(b"c".to_vec(), b"val3-4".to_vec())?;
+        tx4.delete(b"d".to_vec())?;
+        tx4.commit()?;
+
+        let result = |key: &[u8], value: &[u8]| ScanResult {
+            key: key.to_vec(),
+            value: value.to_vec(),
+        };
+        let expected = vec![
+            result(b"d", b"val4"),
+            result(b"b", b"val2-1"),
+            result(b"a", b"val1"),
+        ];
+        assert_eq!(tx3.scan_rev(..)?, expected);
+
+        let mut forward = tx3.scan(..)?;
+        forward.reverse();
+        assert_eq!(forward, expected);
+
+        assert_eq!(
+            tx3.scan_rev(b"b".to_vec()..=b"d".to_vec())?,
+            vec![result(b"d", b"val4"), result(b"b", b"val2-1")]
+        );
+
+        // A prefix scan backwards is the forward one reversed
+        tx3.set(b"ba".to_vec(), b"val5".to_vec())?;
+        tx3.set(b"bb".to_vec(), b"val6".to_vec())?;
+        let mut forward = tx3.scan_prefix(b"b".to_vec())?;
+        forward.reverse();
+        assert_eq!(tx3.scan_prefix_rev(b"b".to_vec())?, forward);
+        assert_eq!(
+            forward,
+            vec![
+                result(b"bb", b"val6"),
+                result(b"ba", b"val5"),
+                result(b"b", b"val2-1"),
+            ]
+        );
+        assert_eq!(tx3.scan_prefix_rev(b"e".to_vec())?, vec![]);
+        tx3.rollback()?;
+
+        tx2.rollback()?;
+        let tx5 = mvcc.begin()?;
+        assert_eq!(
+            tx5.scan_rev(..)?,
+            vec![
+                result(b"c", b"val3-4"),
+                result(b"b", b"val2-1"),
+                result(b"a", b"val1"),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_rev() -> Result<()> {
+        scan_rev(MemoryEngine::new())?;
+        let db = TempDb::new()?;
+        scan_rev(db.engine()?)?;
+        Ok(())
+    }
+
+    // 16. Batched writes
+    fn write_batch(eng: impl Engine) -> Result<()> {
+        let eng = CountingEngine::new(eng);
+        let counter = eng.counter();
+        let mvcc = Mvcc::new(eng);
+
+        let tx = mvcc.begin()?;
+        tx.set(b"b".to_vec(), b"val2".to_vec())?;
+        tx.commit()?;
+
+        let tx1 = mvcc.begin()?;
+        counter.reset();
+        tx1.write_batch(vec![
+            (b"c".to_vec(), Some(b"val3".to_vec())),
+            (b"a".to_vec(), Some(b"val1".to_vec())),
+            (b"b".to_vec(), None),
+            (b"a".to_vec(), Some(b"val1-1".to_vec())),
+        ])?;
+
+        // Each key is written once, in sorted order
+        let written = counter
+            .writes()
+            .into_iter()
+            .filter_map(|key| match MvccKey::decode(&key) {
+                Ok(MvccKey::Version(raw_key, _)) => Some(raw_key),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(written, vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+        tx1.commit()?;
+
+        let tx2 = mvcc.begin()?;
+        assert_eq!(
+            tx2.scan(..)?,
+            vec![
+                ScanResult {
+                    key: b"a".to_vec(),
+                    value: b"val1-1".to_vec()
+                },
+                ScanResult {
+                    key: b"c".to_vec(),
+                    value: b"val3".to_vec()
+                },
+            ]
+        );
+
+        // A conflict on any key leaves the whole batch unwritten
+        let tx3 = mvcc.begin()?;
+        tx3.set(b"c".to_vec(), b"val3-3".to_vec())?;
+        counter.reset();
+        assert_eq!(
+            tx2.write_batch(vec![
+                (b"a".to_vec(), None),
+                (b"c".to_vec(), Some(b"val3-2".to_vec())),
+            ]),
+            Err(Error::WriteConflict)
+        );
+        assert!(counter.writes().is_empty());
+        tx3.commit()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_batch() -> Result<()> {
+        write_batch(MemoryEngine::new())?;
+        let db = TempDb::new()?;
+        write_batch(db.engine()?)?;
+        Ok(())
+    }
+
+    // 17. Commit modes
+    fn commit_mode(eng: impl Engine + Send + 'static, mode: CommitMode) -> Result<()> {
+        let eng = CountingEngine::new(eng);
+        let counter = eng.counter();
+        let mvcc = Mvcc::new_with_commit_mode(eng, mode);
+
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"val1".to_vec())?;
+        tx.rollback()?;
+        assert_eq!(counter.snapshot().flush, 0);
+
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"val1".to_vec())?;
+        tx.commit()?;
+        match mode {
+            // Flushed before commit returns
+            CommitMode::Sync => assert_eq!(counter.snapshot().flush, 1),
+            // Flushed eventually by the background thread
+            CommitMode::Async => {
+                let mut waited = 0;
+                while counter.snapshot().flush == 0 {
+                    assert!(waited < 500, "commit was never flushed");
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                    waited += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_commit_mode() -> Result<()> {
+        for mode in [CommitMode::Sync, CommitMode::Async] {
+            commit_mode(MemoryEngine::new(), mode)?;
+            let db = TempDb::new()?;
+            commit_mode(db.engine()?, mode)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_commit_mode_reopen() -> Result<()> {
+        let db = TempDb::new()?;
+
+        // A synchronous commit is on disk once it returns
+        let mvcc = Mvcc::new_with_commit_mode(db.engine()?, CommitMode::Sync);
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"val1".to_vec())?;
+        tx.commit()?;
+        drop(tx);
+        drop(mvcc);
+
+        // An asynchronous commit is on disk after a flush
+        let mvcc = Mvcc::new_with_commit_mode(db.engine()?, CommitMode::Async);
+        let tx = mvcc.begin()?;
+        assert_eq!(tx.get(b"key1".to_vec())?, Some(b"val1".to_vec()));
+        tx.set(b"key2".to_vec(), b"val2".to_vec())?;
+        tx.commit()?;
+        mvcc.flush()?;
+        drop(tx);
+        drop(mvcc);
+
+        let mvcc = Mvcc::new(db.engine()?);
+        let tx = mvcc.begin()?;
+        assert_eq!(tx.get(b"key1".to_vec())?, Some(b"val1".to_vec()));
+        assert_eq!(tx.get(b"key2".to_vec())?, Some(b"val2".to_vec()));
+        tx.commit()?;
+        drop(mvcc);
+        Ok(())
+    }
+
+    // 18. Scan with a limit
+    fn scan_limit(eng: impl Engine) -> Result<()> {
+        let eng = CountingEngine::new(eng);
+        let counter = eng.counter();
+        let mvcc = Mvcc::new(eng);
+
+        let tx = mvcc.begin()?;
+        for key in [b"a", b"b", b"c", b"d"] {
+            tx.set(key.to_vec(), b"v1".to_vec())?;
+        }
+        tx.commit()?;
+        // A deleted key and a newer version don't count twice
+        let tx = mvcc.begin()?;
+        tx.delete(b"a".to_vec())?;
+        tx.set(b"b".to_vec(), b"v2".to_vec())?;
+        tx.commit()?;
+
+        let tx = mvcc.begin()?;
+        counter.reset();
+        assert_eq!(
+            tx.scan_limit(.., 2)?,
+            vec![
+                ScanResult {
+                    key: b"b".to_vec(),
+                    value: b"v2".to_vec()
+                },
+                ScanResult {
+                    key: b"c".to_vec(),
+                    value: b"v1".to_vec()
+                },
+            ]
+        );
+        // a (2 versions), b (2 versions), c and the first version of d
+        assert_eq!(counter.snapshot().scanned, 6);
+        assert_eq!(tx.scan_limit(.., 10)?, tx.scan(..)?);
+        assert!(tx.scan_limit(.., 0)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_limit() -> Result<()> {
+        scan_limit(MemoryEngine::new())?;
+        let db = TempDb::new()?;
+        scan_limit(db.engine()?)?;
+        Ok(())
+    }
+
+    // 19. Read as of a timestamp
+    fn as_of(eng: impl Engine) -> Result<()> {
+        // Commit times are in milliseconds, keep the writes before and after apart
+        let now = || {
+            let sleep = || std::thread::sleep(std::time::Duration::from_millis(5));
+            sleep();
+            let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH);
+            sleep();
+            now.unwrap().as_millis() as u64
+        };
+        let mvcc = Mvcc::new(eng);
+        let before = now();
+
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"val1".to_vec())?;
+        tx.set(b"key2".to_vec(), b"val2".to_vec())?;
+        tx.commit()?;
+        // Still running at the time read, its writes are hidden even once committed
+        let running = mvcc.begin()?;
+        running.set(b"key3".to_vec(), b"val3".to_vec())?;
+        let first = now();
+
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"val1-1".to_vec())?;
+        tx.delete(b"key2".to_vec())?;
+        tx.commit()?;
+        running.commit()?;
+
+        let tx = mvcc.begin_as_of(first)?;
+        assert_eq!(tx.get(b"key1".to_vec())?, Some(b"val1".to_vec()));
+        assert_eq!(tx.get(b"key2".to_vec())?, Some(b"val2".to_vec()));
+        assert_eq!(tx.get(b"key3".to_vec())?, None);
+        assert!(tx.set(b"key1".to_vec(), b"val1-2".to_vec()).is_err());
+        tx.commit()?;
+
+        let tx = mvcc.begin_as_of(now())?;
+        assert_eq!(tx.get(b"key1".to_vec())?, Some(b"val1-1".to_vec()));
+        assert_eq!(tx.get(b"key2".to_vec())?, None);
+        assert_eq!(tx.get(b"key3".to_vec())?, Some(b"val3".to_vec()));
+        tx.rollback()?;
+
+        assert!(mvcc.begin_as_of(before).is_err());
+
+        // The garbage collection keeps the versions a running reader sees
+        let reader = mvcc.begin_as_of(first)?;
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"val1-2".to_vec())?;
+        tx.commit()?;
+        mvcc.gc()?;
+        assert_eq!(reader.get(b"key1".to_vec())?, Some(b"val1".to_vec()));
+        assert_eq!(reader.get(b"key2".to_vec())?, Some(b"val2".to_vec()));
+        reader.commit()?;
+
+        // Once it is done they are collected, and times before them can't be read anymore.
+        // Only the latest commit time below the watermark is kept.
+        mvcc.gc()?;
+        assert!(mvcc.begin_as_of(first).is_err());
+        let tx = mvcc.begin_as_of(now())?;
+        assert_eq!(tx.get(b"key1".to_vec())?, Some(b"val1-2".to_vec()));
+        assert_eq!(tx.get(b"key3".to_vec())?, Some(b"val3".to_vec()));
+        tx.commit()?;
+        let commit_times = mvcc
+            .engine
+            .lock()?
+            .scan_prefix(MvccKeyPrefix::TxnCommitTime.encode()?)
+            .count();
+        assert_eq!(commit_times, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_as_of() -> Result<()> {
+        as_of(MemoryEngine::new())?;
+        let db = TempDb::new()?;
+        as_of(db.engine()?)?;
+        Ok(())
+    }
+
+    // 20. Batched reads
+    fn multi_get(eng: impl Engine) -> Result<()> {
+        let mvcc = Mvcc::new(eng);
+        let tx = mvcc.begin()?;
+        tx.set(b"key1".to_vec(), b"val1".to_vec())?;
+        tx.set(b"key2".to_vec(), b"val2".to_vec())?;
+        tx.set(b"key3".to_vec(), b"val3".to_vec())?;
+        tx.commit()?;
+
+        let tx1 = mvcc.begin()?;
+        tx1.set(b"key2".to_vec(), b"val2-1".to_vec())?;
+        tx1.delete(b"key3".to_vec())?;
+        // Committed after tx2 began, it stays hidden from it
+        let tx2 = mvcc.begin()?;
+        tx1.commit()?;
+
+        let keys = [
+            b"key3".to_vec(),
+            b"missing".to_vec(),
+            b"key1".to_vec(),
+            b"key2".to_vec(),
+            b"key1".to_vec(),
+        ];
+        let values = tx2.multi_get(&keys)?;
+        assert_eq!(
+            values,
+            vec![
+                Some(b"val3".to_vec()),
+                None,
+                Some(b"val1".to_vec()),
+                Some(b"val2".to_vec()),
+                Some(b"val1".to_vec()),
+            ]
+        );
+        let gets = keys
+            .iter()
+            .map(|key| tx2.get(key.clone()))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(values, gets);
+
+        let tx3 = mvcc.begin()?;
+        assert_eq!(
+            tx3.multi_get(&keys[..4])?,
+            vec![None, None, Some(b"val1".to_vec()), Some(b"val2-1".to_vec())]
+        );
+        assert!(tx3.multi_get(&[])?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_multi_get() -> Result<()> {
+        multi_get(MemoryEngine::new())?;
+        let db = TempDb::new()?;
+        multi_get(db.engine()?)?;
         Ok(())
     }
 }
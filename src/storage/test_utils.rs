@@ -0,0 +1,33 @@
+use super::bitcast_disk::BitCastDiskEngine;
+use crate::error::Result;
+use std::path::PathBuf;
+
+/// Temporary directory holding the log files of a disk engine test. Each one gets a unique
+/// directory, so tests running in parallel don't share files, and the directory is removed
+/// with everything in it when dropped, also when the test panics.
+pub struct TempDb {
+    dir: tempfile::TempDir,
+}
+
+impl TempDb {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            dir: tempfile::tempdir()?,
+        })
+    }
+
+    /// Path of a file in the directory
+    pub fn file(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    /// Path of the default log file
+    pub fn path(&self) -> PathBuf {
+        self.file("sqldb-log")
+    }
+
+    /// Open the disk engine on the default log file, reopening it when it already exists
+    pub fn engine(&self) -> Result<BitCastDiskEngine> {
+        BitCastDiskEngine::new(self.path())
+    }
+}
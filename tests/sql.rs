@@ -0,0 +1,262 @@
+use std::process::Command;
+
+use mini_sqldb_rs::{
+    error::Result,
+    sql::{
+        engine::{kv::KVEngine, Transaction},
+        executor::ResultSet,
+        parser::Parser,
+        plan::Plan,
+        types::Value,
+        Engine,
+    },
+    storage::{self, bitcast_disk::BitCastDiskEngine, memory::MemoryEngine},
+};
+
+#[test]
+fn test_memory_engine_feature_matrix() -> Result<()> {
+    run_feature_matrix(MemoryEngine::new())
+}
+
+#[test]
+fn test_bitcast_disk_engine_feature_matrix() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    run_feature_matrix(BitCastDiskEngine::new(dir.path().join("sqldb-log"))?)
+}
+
+#[test]
+fn test_memory_engine_snapshot_isolation() -> Result<()> {
+    run_snapshot_isolation(MemoryEngine::new())
+}
+
+#[test]
+fn test_bitcast_disk_engine_snapshot_isolation() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    run_snapshot_isolation(BitCastDiskEngine::new(dir.path().join("sqldb-log"))?)
+}
+
+#[test]
+fn test_memory_engine_create_and_insert_in_transaction() -> Result<()> {
+    run_create_and_insert_in_transaction(MemoryEngine::new())
+}
+
+#[test]
+fn test_bitcast_disk_engine_create_and_insert_in_transaction() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("sqldb-log");
+    run_create_and_insert_in_transaction(BitCastDiskEngine::new(path.clone())?)?;
+
+    // The table and its rows are read back from the log file
+    let session = KVEngine::new(BitCastDiskEngine::new(path)?).session()?;
+    assert_eq!(
+        scan_rows(session.execute("SELECT * FROM t;")?),
+        vec![row(1, "a", 10), row(2, "b", 20)]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_memory_engine_update_all_rows() -> Result<()> {
+    run_update_all_rows(MemoryEngine::new())
+}
+
+#[test]
+fn test_bitcast_disk_engine_update_all_rows() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    run_update_all_rows(BitCastDiskEngine::new(dir.path().join("sqldb-log"))?)
+}
+
+// Rows of a scan result, failing on any other result set
+fn scan_rows(rs: ResultSet) -> Vec<Vec<Value>> {
+    match rs {
+        ResultSet::Scan { rows, .. } => rows,
+        rs => panic!("Expected Scan result, got {rs:?}"),
+    }
+}
+
+fn row(id: i64, name: &str, score: i64) -> Vec<Value> {
+    vec![
+        Value::Integer(id),
+        Value::String(name.to_string()),
+        Value::Integer(score),
+    ]
+}
+
+// Runs every statement end to end through a session, from parsing to the storage engine
+fn run_feature_matrix<E: storage::Engine + 'static>(engine: E) -> Result<()> {
+    let session = KVEngine::new(engine).session()?;
+
+    match session.execute("CREATE TABLE t (id INT PRIMARY KEY, name VARCHAR, score INT);")? {
+        ResultSet::CreateTable { table_name } => assert_eq!(table_name, "t"),
+        rs => panic!("Expected CreateTable result, got {rs:?}"),
+    }
+
+    match session.execute(
+        "INSERT INTO t VALUES (1, 'a', 30), (2, 'b', 10), (3, 'c', 20), (4, 'd', 40), (5, 'e', 10);",
+    )? {
+        ResultSet::Insert { count } => assert_eq!(count, 5),
+        rs => panic!("Expected Insert result, got {rs:?}"),
+    }
+
+    match session.execute("SELECT * FROM t;")? {
+        ResultSet::Scan { columns, rows } => {
+            assert_eq!(columns, vec!["id", "name", "score"]);
+            assert_eq!(rows.len(), 5);
+            assert_eq!(rows[0], row(1, "a", 30));
+        }
+        rs => panic!("Expected Scan result, got {rs:?}"),
+    }
+
+    // Filter, order on two columns, then page through the result
+    assert_eq!(
+        scan_rows(session.execute(
+            "SELECT * FROM t WHERE id > 1 ORDER BY score ASC, id DESC LIMIT 3 OFFSET 1;"
+        )?),
+        vec![row(2, "b", 10), row(3, "c", 20), row(4, "d", 40)]
+    );
+    assert_eq!(
+        scan_rows(session.execute("SELECT COUNT(*) FROM t WHERE score >= 20;")?),
+        vec![vec![Value::Integer(3)]]
+    );
+
+    match session.execute("UPDATE t SET score = score + 5, name = 'z' WHERE score = 10;")? {
+        ResultSet::Update { count } => assert_eq!(count, 2),
+        rs => panic!("Expected Update result, got {rs:?}"),
+    }
+    assert_eq!(
+        scan_rows(session.execute("SELECT * FROM t WHERE name = 'z';")?),
+        vec![row(2, "z", 15), row(5, "z", 15)]
+    );
+
+    match session.execute("DELETE FROM t WHERE score < 20;")? {
+        ResultSet::Delete { count, .. } => assert_eq!(count, 2),
+        rs => panic!("Expected Delete result, got {rs:?}"),
+    }
+    assert_eq!(
+        scan_rows(session.execute("SELECT * FROM t ORDER BY score DESC;")?),
+        vec![row(4, "d", 40), row(1, "a", 30), row(3, "c", 20)]
+    );
+
+    // A failing statement leaves the table untouched
+    assert!(session
+        .execute("INSERT INTO t VALUES (1, 'dup', 0);")
+        .is_err());
+    assert_eq!(scan_rows(session.execute("SELECT * FROM t;")?).len(), 3);
+
+    match session.execute("DROP TABLE t;")? {
+        ResultSet::DropTable { table_name } => assert_eq!(table_name, "t"),
+        rs => panic!("Expected DropTable result, got {rs:?}"),
+    }
+    assert!(session.execute("SELECT * FROM t;").is_err());
+
+    Ok(())
+}
+
+// Run a statement inside an open transaction, sessions only run one statement per transaction
+fn execute_in<T: Transaction + 'static>(txn: &mut T, sql: &str) -> Result<ResultSet> {
+    Plan::build(Parser::new(sql).parse()?)?.execute(txn)
+}
+
+// Every statement of a transaction reads the snapshot taken when it began, whatever other
+// transactions commit meanwhile
+fn run_snapshot_isolation<E: storage::Engine + 'static>(engine: E) -> Result<()> {
+    let engine = KVEngine::new(engine);
+    let session = engine.session()?;
+    session.execute("CREATE TABLE t (id INT PRIMARY KEY, name VARCHAR, score INT);")?;
+    session.execute("INSERT INTO t VALUES (1, 'a', 10), (2, 'b', 20);")?;
+
+    let mut txn = engine.begin()?;
+    let before = scan_rows(execute_in(&mut txn, "SELECT * FROM t;")?);
+    assert_eq!(before, vec![row(1, "a", 10), row(2, "b", 20)]);
+
+    session.execute("INSERT INTO t VALUES (3, 'c', 30);")?;
+    session.execute("UPDATE t SET score = 0 WHERE id = 1;")?;
+    session.execute("DELETE FROM t WHERE id = 2;")?;
+
+    assert_eq!(scan_rows(execute_in(&mut txn, "SELECT * FROM t;")?), before);
+    assert_eq!(
+        scan_rows(execute_in(&mut txn, "SELECT COUNT(*) FROM t;")?),
+        vec![vec![Value::Integer(2)]]
+    );
+    txn.commit()?;
+
+    // A transaction started after the commits sees them
+    assert_eq!(
+        scan_rows(session.execute("SELECT * FROM t;")?),
+        vec![row(1, "a", 0), row(3, "c", 30)]
+    );
+    Ok(())
+}
+
+// A table created by a transaction can be written and read by its later statements before it
+// commits, while other transactions don't see it until then
+fn run_create_and_insert_in_transaction<E: storage::Engine + 'static>(engine: E) -> Result<()> {
+    let engine = KVEngine::new(engine);
+    let session = engine.session()?;
+
+    let mut txn = engine.begin()?;
+    execute_in(
+        &mut txn,
+        "CREATE TABLE t (id INT PRIMARY KEY, name VARCHAR, score INT);",
+    )?;
+    match execute_in(&mut txn, "INSERT INTO t VALUES (1, 'a', 10), (2, 'b', 20);")? {
+        ResultSet::Insert { count } => assert_eq!(count, 2),
+        rs => panic!("Expected Insert result, got {rs:?}"),
+    }
+    assert_eq!(
+        scan_rows(execute_in(&mut txn, "SELECT * FROM t;")?),
+        vec![row(1, "a", 10), row(2, "b", 20)]
+    );
+    assert!(session.execute("SELECT * FROM t;").is_err());
+    txn.commit()?;
+
+    assert_eq!(
+        scan_rows(session.execute("SELECT * FROM t;")?),
+        vec![row(1, "a", 10), row(2, "b", 20)]
+    );
+    Ok(())
+}
+
+// An UPDATE without WHERE rewrites every row under its own primary key, none of them may
+// be written over another
+fn run_update_all_rows<E: storage::Engine + 'static>(engine: E) -> Result<()> {
+    let session = KVEngine::new(engine).session()?;
+    session.execute("CREATE TABLE t (id INT PRIMARY KEY, name VARCHAR, score INT);")?;
+    session.execute("INSERT INTO t VALUES (1, 'a', 10), (2, 'b', 20), (3, 'c', 30);")?;
+
+    match session.execute("UPDATE t SET name = 'x';")? {
+        ResultSet::Update { count } => assert_eq!(count, 3),
+        rs => panic!("Expected Update result, got {rs:?}"),
+    }
+    assert_eq!(
+        scan_rows(session.execute("SELECT * FROM t;")?),
+        vec![row(1, "x", 10), row(2, "x", 20), row(3, "x", 30)]
+    );
+    Ok(())
+}
+
+// The library must not write to stdout. The test binary runs this same test again in a child
+// process with output capture off, and the statements must print nothing between the markers.
+#[test]
+fn test_statements_print_nothing() -> Result<()> {
+    if std::env::var_os("SQLDB_STDOUT_CHILD").is_some() {
+        let session = KVEngine::new(MemoryEngine::new()).session()?;
+        println!("<<start>>");
+        session.execute("CREATE TABLE t (id INT PRIMARY KEY, v VARCHAR);")?;
+        session.execute("INSERT INTO t VALUES (1, 'a'), (2, 'b');")?;
+        session.execute("UPDATE t SET v = 'c' WHERE id = 1;")?;
+        session.execute("SELECT * FROM t ORDER BY v;")?;
+        session.execute("DELETE FROM t WHERE id = 2;")?;
+        println!("<<end>>");
+        return Ok(());
+    }
+
+    let output = Command::new(std::env::current_exe()?)
+        .args(["--exact", "test_statements_print_nothing", "--nocapture"])
+        .env("SQLDB_STDOUT_CHILD", "1")
+        .output()?;
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("<<start>>\n<<end>>\n"), "{stdout}");
+    Ok(())
+}